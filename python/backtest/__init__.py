@@ -1,12 +1,77 @@
-from backtest._engine import load_scid, load_bars, run_backtest, run_tick_backtest
+from backtest._engine import (
+    HOLD,
+    load_scid,
+    load_bars,
+    load_scid_range,
+    load_bars_range,
+    load_scid_multi,
+    load_bars_multi,
+    load_bars_intervals,
+    load_scid_batch,
+    load_bars_batch,
+    load_csv,
+    load_csv_bars,
+    load_parquet,
+    load_parquet_bars,
+    generate_synthetic_ticks,
+    write_scid,
+    validate_scid,
+    check_scid_integrity,
+    scid_info,
+    scid_header,
+    load_depth,
+    replay,
+    run_backtest,
+    run_backtest_vs_benchmark,
+    run_incremental_bar_backtest,
+    run_builtin_backtest,
+    run_event_backtest,
+    run_walk_forward,
+    run_hybrid_backtest,
+    run_rolling_backtest,
+    run_param_sweep,
+    run_tick_backtest,
+    monte_carlo_simulation,
+    export_trades_csv,
+)
 from backtest.strategy import Strategy
 from backtest.report import print_report, plot_equity
 
 __all__ = [
+    "HOLD",
     "load_scid",
     "load_bars",
+    "load_scid_range",
+    "load_bars_range",
+    "load_scid_multi",
+    "load_bars_multi",
+    "load_bars_intervals",
+    "load_scid_batch",
+    "load_bars_batch",
+    "load_csv",
+    "load_csv_bars",
+    "load_parquet",
+    "load_parquet_bars",
+    "generate_synthetic_ticks",
+    "write_scid",
+    "validate_scid",
+    "check_scid_integrity",
+    "scid_info",
+    "scid_header",
+    "load_depth",
+    "replay",
     "run_backtest",
+    "run_backtest_vs_benchmark",
+    "run_incremental_bar_backtest",
+    "run_builtin_backtest",
+    "run_event_backtest",
+    "run_walk_forward",
+    "run_hybrid_backtest",
+    "run_rolling_backtest",
+    "run_param_sweep",
     "run_tick_backtest",
+    "monte_carlo_simulation",
+    "export_trades_csv",
     "Strategy",
     "print_report",
     "plot_equity",
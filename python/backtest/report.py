@@ -7,9 +7,13 @@ def print_report(results: dict) -> None:
     print("  BACKTEST RESULTS")
     print("=" * 60)
     print(f"  Total P&L:            ${results['total_pnl']:>12,.2f}")
+    print(f"  Commission Paid:      ${results['total_commission_paid']:>12,.2f}")
+    if results.get("total_funding_paid"):
+        print(f"  Funding Paid:         ${results['total_funding_paid']:>12,.2f}")
     print(f"  Number of Trades:      {results['num_trades']:>12,}")
     print(f"    Long:                {results['num_long']:>12,}")
     print(f"    Short:               {results['num_short']:>12,}")
+    print(f"    Engine exits:        {results['num_forced_exits']:>12,}")
     print(f"  Wins / Losses:         {results['num_wins']:>6,} / {results['num_losses']:,}")
     print(f"  Win Rate:              {results['win_rate']:>11.1%}")
     print(f"  Profit Factor:         {results['profit_factor']:>12.2f}")
@@ -21,8 +25,22 @@ def print_report(results: dict) -> None:
     print("-" * 60)
     print(f"  Max Drawdown:         ${results['max_drawdown']:>12,.2f}")
     print(f"  Max Drawdown %:        {results['max_drawdown_pct']:>11.2f}%")
-    print(f"  Sharpe Ratio:          {results['sharpe_ratio']:>12.3f}")
+    print(f"  Max DD Duration:       {results['max_drawdown_duration_bars']:>12,} bars")
+    print(f"                         {results['max_drawdown_duration_secs'] / 86400:>11.1f}d")
+    print(f"  Sharpe Ratio (daily):  {results['sharpe_ratio']:>12.3f}")
+    print(f"  Sharpe (per trade):    {results['sharpe_per_trade']:>12.3f}")
+    print(f"  Trades per Day:        {results['trades_per_day']:>12.2f}")
+    print(f"  Ann. Return:           {results['annualized_return']:>11.2%}")
+    print(f"  Ann. Volatility:      ${results['annualized_volatility']:>12,.2f}")
+    print(f"  Ulcer Index:           {results['ulcer_index']:>12.3f}")
+    print(f"  Omega Ratio:           {results['omega_ratio']:>12.3f}")
+    print(f"  SQN:                   {results['sqn']:>12.2f}")
     print(f"  Avg Holding Time:      {results['avg_holding_time_secs']:>10.1f}s")
+    if "benchmark_total_pnl" in results:
+        print("-" * 60)
+        print(f"  Buy & Hold P&L:       ${results['benchmark_total_pnl']:>12,.2f}")
+        print(f"  Buy & Hold Sharpe:     {results['benchmark_sharpe']:>12.3f}")
+        print(f"  Buy & Hold Max DD:    ${results['benchmark_max_drawdown']:>12,.2f}")
     print("=" * 60)
 
 
@@ -13,19 +13,40 @@ class Strategy(ABC):
     def on_bars(self, bars: dict) -> np.ndarray:
         """Called with all bars as a dict of numpy arrays.
 
-        Keys: timestamp, open, high, low, close, volume, bid_volume, ask_volume, num_bars
-
-        Must return an int32 array of length num_bars with signals:
-            1 = long, -1 = short, 0 = flat
+        Keys: timestamp, open, high, low, close, volume, bid_volume, ask_volume,
+        delta, delta_high, delta_low, cum_delta, tick_count, is_synthetic,
+        num_bars
+
+        delta is ask_volume - bid_volume per bar, delta_high/delta_low the extremes of
+        the running delta within the bar, and cum_delta the running sum across bars.
+        tick_count is the number of records aggregated into the bar (the exchange's trade
+        count can differ), and is_synthetic marks the flat bars inserted by fill_gaps.
+
+        Must return an integer array (any numpy int dtype, or a list) of length
+        num_bars with signals:
+            1 = long, -1 = short, 0 = flat, backtest.HOLD = keep the current position
+        Float arrays are accepted if they contain only -1, 0, 1 and NaN (hold).
+
+        May instead return a (signals, tags) tuple, where tags is a parallel int
+        array labelling each signal's setup. Trades keep the tag active at entry
+        and results include a per-tag breakdown under "metrics_by_tag".
         """
         raise NotImplementedError
 
     def on_ticks(self, ticks: dict) -> np.ndarray:
         """Called with a batch of ticks as a dict of numpy arrays.
 
-        Keys: timestamp, price, bid, ask, volume, bid_volume, ask_volume, num_ticks
+        Keys: timestamp, price, bid, ask, volume, bid_volume, ask_volume, num_ticks,
+        position (-1/0/1 at the start of the batch), entry_price (NaN when flat)
+
+        Must return an integer array (any numpy int dtype, or a list) of length
+        num_ticks with signals:
+            1 = long, -1 = short, 0 = flat, backtest.HOLD = keep the current position
+
+        May instead return a (signals, tags) tuple, as in on_bars().
 
-        Must return an int32 array of length num_ticks with signals:
-            1 = long, -1 = short, 0 = flat
+        With run_tick_backtest(..., stateful=True) the callback is called as
+        callback(ticks, state) and returns (signals, state) or (signals, tags, state);
+        the returned state is handed to the next batch.
         """
         raise NotImplementedError("Tick strategy not implemented")
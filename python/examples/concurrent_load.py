@@ -0,0 +1,60 @@
+"""Concurrent loading — checks that the engine releases the GIL.
+
+Writes two synthetic SCID files, then loads and validates them one after the
+other and again from two threads at once. Because the Rust side scans files
+without holding the GIL, the threaded run should overlap and take well under
+the sequential time.
+"""
+import sys
+import os
+import tempfile
+import time
+from concurrent.futures import ThreadPoolExecutor
+
+sys.path.insert(0, os.path.join(os.path.dirname(__file__), ".."))
+
+from backtest import generate_synthetic_ticks, write_scid, load_bars, validate_scid
+
+NUM_TICKS = 5_000_000
+ROUNDS = 3
+
+
+def write_file(path, seed):
+    ticks = generate_synthetic_ticks(NUM_TICKS, start_price=4500.0, seed=seed)
+    columns = {
+        name: ticks[name].astype(float).tolist()
+        for name in ("timestamp", "price", "volume", "bid", "ask", "bid_volume", "ask_volume")
+    }
+    write_scid(path, **columns)
+
+
+def work(path):
+    for _ in range(ROUNDS):
+        load_bars(path, "1m")
+        validate_scid(path, 60.0)
+
+
+def timed(fn):
+    start = time.perf_counter()
+    fn()
+    return time.perf_counter() - start
+
+
+def main():
+    with tempfile.TemporaryDirectory() as tmp:
+        paths = [os.path.join(tmp, f"synthetic_{i}.scid") for i in range(2)]
+        for seed, path in enumerate(paths):
+            write_file(path, seed)
+
+        sequential = timed(lambda: [work(path) for path in paths])
+        with ThreadPoolExecutor(max_workers=2) as pool:
+            concurrent = timed(lambda: list(pool.map(work, paths)))
+
+    print(f"Sequential: {sequential:.2f}s")
+    print(f"Concurrent: {concurrent:.2f}s ({sequential / concurrent:.2f}x)")
+    if concurrent > 0.8 * sequential:
+        sys.exit("Concurrent loads did not overlap; is the GIL held while scanning?")
+
+
+if __name__ == "__main__":
+    main()
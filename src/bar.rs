@@ -1,4 +1,12 @@
-use crate::scid::{ScidFile, Tick};
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+use crate::columns::BarColumns;
+use crate::datasource::DataSource;
+use crate::scid::{merge_by_timestamp, SkipStats, Tick, TickOrder, Unbundle, Unbundler};
+use crate::session::SessionClock;
+
+const US_PER_SEC: i64 = 1_000_000;
 
 #[derive(Clone, Copy, Debug)]
 pub struct Bar {
@@ -11,100 +19,938 @@ pub struct Bar {
     pub volume: u64,
     pub bid_volume: u64,
     pub ask_volume: u64,
+    /// Trade count reported by the records
     pub num_trades: u64,
+    /// Number of records aggregated into the bar
+    pub tick_count: u32,
+    /// Ask volume minus bid volume over the bar
+    pub delta: i64,
+    /// Highest running delta reached within the bar
+    pub delta_high: i64,
+    /// Lowest running delta reached within the bar
+    pub delta_low: i64,
+    /// Gap-filler bar for an interval with no ticks
+    pub is_synthetic: bool,
+    /// SCID record indices of the first and last ticks aggregated into the bar (for a
+    /// synthetic bar, both are the preceding bar's last tick)
+    pub first_tick_index: usize,
+    pub last_tick_index: usize,
 }
 
-/// Bar interval in seconds.
-#[derive(Clone, Copy, Debug)]
-pub struct BarInterval(pub u64);
+impl Bar {
+    /// Open a new bar starting at `start_us` from its first tick, SCID record `index`.
+    pub(crate) fn from_tick(start_us: i64, tick: &Tick, index: usize) -> Self {
+        Bar {
+            timestamp_us: start_us,
+            open: tick.price,
+            high: tick.price,
+            low: tick.price,
+            close: tick.price,
+            volume: tick.volume as u64,
+            bid_volume: tick.bid_volume as u64,
+            ask_volume: tick.ask_volume as u64,
+            num_trades: tick.num_trades as u64,
+            tick_count: 1,
+            delta: tick.delta(),
+            delta_high: tick.delta(),
+            delta_low: tick.delta(),
+            is_synthetic: false,
+            first_tick_index: index,
+            last_tick_index: index,
+        }
+    }
 
-impl BarInterval {
-    pub fn from_str(s: &str) -> Result<Self, String> {
+    /// Fold a later tick of the same interval, SCID record `index`, into the bar.
+    pub(crate) fn update(&mut self, tick: &Tick, index: usize) {
+        if tick.price > self.high {
+            self.high = tick.price;
+        }
+        if tick.price < self.low {
+            self.low = tick.price;
+        }
+        self.close = tick.price;
+        self.volume += tick.volume as u64;
+        self.bid_volume += tick.bid_volume as u64;
+        self.ask_volume += tick.ask_volume as u64;
+        self.num_trades += tick.num_trades as u64;
+        self.tick_count += 1;
+        self.delta += tick.delta();
+        self.delta_high = self.delta_high.max(self.delta);
+        self.delta_low = self.delta_low.min(self.delta);
+        self.last_tick_index = index;
+    }
+}
+
+/// Bar length, and where bar boundaries fall.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BarInterval {
+    /// Bar length in seconds
+    pub secs: u64,
+    /// Boundaries are laid from this many seconds after midnight, e.g. 17 * 3600 for daily
+    /// bars running 17:00 to 17:00
+    pub session_offset_secs: i64,
+    /// Clock whose midnight the offset counts from (default UTC). With a time zone the
+    /// boundaries follow daylight saving time, so a 23- or 25-hour day is still one bar.
+    pub clock: SessionClock,
+}
+
+impl std::str::FromStr for BarInterval {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
         match s {
-            "1s" => Ok(BarInterval(1)),
-            "5s" => Ok(BarInterval(5)),
-            "10s" => Ok(BarInterval(10)),
-            "30s" => Ok(BarInterval(30)),
-            "1m" => Ok(BarInterval(60)),
-            "3m" => Ok(BarInterval(180)),
-            "5m" => Ok(BarInterval(300)),
-            "15m" => Ok(BarInterval(900)),
-            "30m" => Ok(BarInterval(1800)),
-            "1h" => Ok(BarInterval(3600)),
-            "4h" => Ok(BarInterval(14400)),
-            "1d" => Ok(BarInterval(86400)),
+            "1s" => Ok(BarInterval::new(1)),
+            "5s" => Ok(BarInterval::new(5)),
+            "10s" => Ok(BarInterval::new(10)),
+            "30s" => Ok(BarInterval::new(30)),
+            "1m" => Ok(BarInterval::new(60)),
+            "3m" => Ok(BarInterval::new(180)),
+            "5m" => Ok(BarInterval::new(300)),
+            "15m" => Ok(BarInterval::new(900)),
+            "30m" => Ok(BarInterval::new(1800)),
+            "1h" => Ok(BarInterval::new(3600)),
+            "4h" => Ok(BarInterval::new(14400)),
+            "1d" => Ok(BarInterval::new(86400)),
             _ => Err(format!("Unknown interval: {s}")),
         }
     }
+}
+
+impl BarInterval {
+    /// Interval of `secs` seconds with boundaries aligned to UTC midnight.
+    pub fn new(secs: u64) -> Self {
+        BarInterval {
+            secs,
+            session_offset_secs: 0,
+            clock: SessionClock::default(),
+        }
+    }
 
-    /// Return the bar boundary (start of the bar) for a given unix-us timestamp.
+    /// Lay boundaries from `offset_secs` after midnight on `clock`.
+    pub fn with_session(self, offset_secs: i64, clock: SessionClock) -> Self {
+        BarInterval {
+            session_offset_secs: offset_secs,
+            clock,
+            ..self
+        }
+    }
+
+    /// Whether boundaries are the plain multiples of the interval since the Unix epoch.
+    pub fn is_utc_aligned(&self) -> bool {
+        let shift = self.session_offset_secs - self.clock.utc_offset_secs;
+        self.clock.zone.is_none() && shift.rem_euclid(self.secs as i64) == 0
+    }
+
+    /// Return the bar boundary (start of the bar) for a given unix-us timestamp:
+    /// `floor((local - offset) / interval) * interval + offset`, in local seconds, taken
+    /// back to UTC.
     #[inline]
     pub fn bar_start(&self, timestamp_us: i64) -> i64 {
-        let secs = timestamp_us / 1_000_000;
-        let bar_secs = secs - (secs % self.0 as i64);
-        bar_secs * 1_000_000
+        let offset = self.clock.utc_offset_at(timestamp_us);
+        let local = timestamp_us.div_euclid(US_PER_SEC) + offset - self.session_offset_secs;
+        let start = local - local.rem_euclid(self.secs as i64) + self.session_offset_secs;
+        if self.clock.zone.is_some() {
+            // A DST change between the boundary and the tick gives the boundary another offset
+            let at_start = self.clock.utc_offset_at((start - offset) * US_PER_SEC);
+            return (start - at_start) * US_PER_SEC;
+        }
+        (start - offset) * US_PER_SEC
+    }
+
+    /// The boundary after `start`, a boundary. In a DST-following zone boundaries can be
+    /// up to an hour further apart than the interval.
+    pub fn next_start(&self, start: i64) -> i64 {
+        let mut t = start + self.secs as i64 * US_PER_SEC;
+        if self.clock.zone.is_none() {
+            return t;
+        }
+        loop {
+            let next = self.bar_start(t);
+            if next > start {
+                return next;
+            }
+            t += 3600 * US_PER_SEC;
+        }
+    }
+
+    /// The first bar boundary at or after `timestamp_us`.
+    pub fn boundary_at_or_after(&self, timestamp_us: i64) -> i64 {
+        let start = self.bar_start(timestamp_us);
+        if start < timestamp_us {
+            self.next_start(start)
+        } else {
+            start
+        }
     }
 }
 
-pub fn aggregate_bars(scid: &ScidFile, interval: BarInterval) -> Vec<Bar> {
-    if scid.num_records == 0 {
-        return Vec::new();
+/// Candle construction applied on top of the aggregated time bars.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BarType {
+    Standard,
+    HeikinAshi,
+}
+
+impl std::str::FromStr for BarType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "standard" => Ok(BarType::Standard),
+            "heikin_ashi" => Ok(BarType::HeikinAshi),
+            _ => Err(format!("Unknown bar type: {s}")),
+        }
     }
+}
 
-    let mut bars: Vec<Bar> = Vec::with_capacity(scid.num_records / 100);
-    let mut current_bar_start: i64 = i64::MIN;
-    let mut bar = Bar {
-        timestamp_us: 0,
-        open: 0.0,
-        high: f64::MIN,
-        low: f64::MAX,
-        close: 0.0,
-        volume: 0,
-        bid_volume: 0,
-        ask_volume: 0,
-        num_trades: 0,
+impl BarType {
+    /// Transform aggregated bars according to the bar type.
+    pub fn apply<'a>(&self, bars: &'a [Bar]) -> Cow<'a, [Bar]> {
+        match self {
+            BarType::Standard => Cow::Borrowed(bars),
+            BarType::HeikinAshi => Cow::Owned(to_heikin_ashi(bars)),
+        }
+    }
+}
+
+/// Default cap on the empty intervals filled per gap.
+pub const DEFAULT_MAX_GAP_BARS: usize = 10_000;
+
+/// Restricts the bar series to the end of the file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BarTail {
+    /// The most recent `n` bars
+    LastBars(usize),
+    /// Bars opening within this many days before the last valid tick
+    LookbackDays(f64),
+}
+
+/// How ticks are turned into the bar series handed to a strategy.
+#[derive(Clone, Debug)]
+pub struct BarOptions {
+    pub interval: BarInterval,
+    pub bar_type: BarType,
+    /// Insert flat, zero-volume bars for intervals that contain no ticks
+    pub fill_gaps: bool,
+    /// Leave gaps of more than this many empty intervals unfilled
+    pub max_gap_bars: usize,
+    /// Directory for the on-disk aggregated bar cache (None = always aggregate)
+    pub cache_dir: Option<PathBuf>,
+    /// Only build the end of the file (None = the whole file)
+    pub tail: Option<BarTail>,
+    /// Handling of ticks earlier than one before them
+    pub order: TickOrder,
+    /// How unbundled-trade records count towards volume and trade counts
+    pub unbundle: Unbundle,
+}
+
+/// Bars built from a SCID file.
+pub struct BarSeries {
+    pub bars: Vec<Bar>,
+    /// Invalid records skipped among those read
+    pub skipped: SkipStats,
+    /// Records the bars were built from
+    pub num_records: usize,
+    /// A tail window left out earlier data
+    pub truncated: bool,
+    /// Gaps too long to fill (see `BarOptions::max_gap_bars`)
+    pub unfilled_gaps: usize,
+}
+
+impl BarOptions {
+    pub fn new(interval: BarInterval) -> Self {
+        BarOptions {
+            interval,
+            bar_type: BarType::Standard,
+            fill_gaps: false,
+            max_gap_bars: DEFAULT_MAX_GAP_BARS,
+            cache_dir: None,
+            tail: None,
+            order: TickOrder::Skip,
+            unbundle: Unbundle::Ignore,
+        }
+    }
+
+    /// Aggregate real-price bars from `source` (read from the file at `path`, if any),
+    /// gap-filled if requested, along with counts of the records skipped as invalid.
+    /// `bar_type` is not applied here so callers can keep the real prices for fills.
+    ///
+    /// With a tail, aggregation starts from a bar boundary found by binary search near
+    /// the end of the file, so the first bar is complete and earlier ticks are not read
+    /// (except with a cache, whose full bar series is trimmed instead).
+    ///
+    /// An unsorted file is rejected under `TickOrder::Error`, and under `TickOrder::Sort`
+    /// aggregated in full in timestamp order, bypassing the cache. The cache only holds
+    /// bars built with `Unbundle::Ignore` on UTC-aligned intervals; anything else bypasses
+    /// it too.
+    /// The cache is keyed on the file, so `cache_dir` needs a `path`.
+    pub fn build(&self, source: &dyn DataSource, path: Option<&Path>) -> Result<BarSeries, String> {
+        if self.order == TickOrder::Error {
+            source.check_order()?;
+        }
+        let sort = self.order == TickOrder::Sort && !source.is_sorted();
+        let mut series = match (&self.cache_dir, self.tail) {
+            _ if sort => {
+                let (bars, skipped) = aggregate_bars_sorted(source, self.interval, self.unbundle);
+                self.trim_lookback(source, bars, skipped)
+            }
+            (Some(dir), _)
+                if self.unbundle == Unbundle::Ignore && self.interval.is_utc_aligned() =>
+            {
+                let path = path.ok_or("The bar cache needs a file-backed data source")?;
+                let (bars, skipped) =
+                    crate::cache::load_or_build(source, path, self.interval, dir)?;
+                self.trim_lookback(source, bars, skipped)
+            }
+            (_, Some(tail)) => tail_bars(source, self.interval, tail, self.unbundle),
+            (_, None) => {
+                let (bars, skipped) = aggregate_bars(source, self.interval, self.unbundle);
+                BarSeries {
+                    bars,
+                    skipped,
+                    num_records: source.num_ticks(),
+                    truncated: false,
+                    unfilled_gaps: 0,
+                }
+            }
+        };
+        if self.fill_gaps {
+            (series.bars, series.unfilled_gaps) =
+                fill_gaps(&series.bars, self.interval, self.max_gap_bars);
+        }
+        if let Some(BarTail::LastBars(n)) = self.tail {
+            if series.bars.len() > n {
+                series.bars.drain(..series.bars.len() - n);
+                series.truncated = true;
+            }
+        }
+        Ok(series)
+    }
+
+    /// Wrap bars aggregated from the whole file, dropping those before a `LookbackDays`
+    /// tail.
+    fn trim_lookback(
+        &self,
+        source: &dyn DataSource,
+        bars: Vec<Bar>,
+        skipped: SkipStats,
+    ) -> BarSeries {
+        let mut series = BarSeries {
+            bars,
+            skipped,
+            num_records: source.num_ticks(),
+            truncated: false,
+            unfilled_gaps: 0,
+        };
+        if let Some(BarTail::LookbackDays(days)) = self.tail {
+            if let Some(last) = last_valid_timestamp(source) {
+                let boundary = self.interval.boundary_at_or_after(last - days_us(days));
+                let keep_from = series.bars.partition_point(|b| b.timestamp_us < boundary);
+                series.bars.drain(..keep_from);
+                series.truncated = keep_from > 0;
+            }
+        }
+        series
+    }
+}
+
+fn days_us(days: f64) -> i64 {
+    (days * 86_400_000_000.0) as i64
+}
+
+/// Timestamp of the last record that isn't skipped as invalid.
+fn last_valid_timestamp(source: &dyn DataSource) -> Option<i64> {
+    (0..source.num_ticks())
+        .rev()
+        .map(|i| source.tick(i))
+        .find(|t| t.is_valid())
+        .map(|t| t.timestamp_us)
+}
+
+/// Aggregate only the tail of `source`. For `LastBars(n)` the window starts `n` bar
+/// intervals before the last tick and doubles until it holds at least `n` bars (empty
+/// intervals produce no bars) or reaches the start of the file; the caller trims the
+/// surplus.
+fn tail_bars(
+    source: &dyn DataSource,
+    interval: BarInterval,
+    tail: BarTail,
+    unbundle: Unbundle,
+) -> BarSeries {
+    let Some(last) = last_valid_timestamp(source) else {
+        let (bars, skipped) = aggregate_bars(source, interval, unbundle);
+        return BarSeries {
+            bars,
+            skipped,
+            num_records: source.num_ticks(),
+            truncated: false,
+            unfilled_gaps: 0,
+        };
+    };
+    let step_us = interval.secs as i64 * US_PER_SEC;
+    let from_boundary = |boundary: i64| {
+        let start = source.find_index_at_or_after(boundary);
+        let (bars, skipped) = aggregate_bars_from(source, interval, start, unbundle);
+        BarSeries {
+            bars,
+            skipped,
+            num_records: source.num_ticks() - start,
+            truncated: start > 0,
+            unfilled_gaps: 0,
+        }
     };
+    match tail {
+        BarTail::LookbackDays(days) => {
+            from_boundary(interval.boundary_at_or_after(last - days_us(days)))
+        }
+        BarTail::LastBars(n) => {
+            let mut window = n.max(1) as i64;
+            loop {
+                // Realigned, as boundaries in a DST-following zone are not evenly spaced
+                let boundary = interval.bar_start(
+                    interval
+                        .bar_start(last)
+                        .saturating_sub((window - 1).saturating_mul(step_us)),
+                );
+                let series = from_boundary(boundary);
+                if series.bars.len() >= n || !series.truncated {
+                    return series;
+                }
+                window = window.saturating_mul(2);
+            }
+        }
+    }
+}
+
+/// Aggregate valid ticks into bars. Invalid records (see `Tick::is_valid`) and ticks
+/// earlier than one before them are dropped and counted in the returned `SkipStats`.
+/// `unbundle` sets how the records of unbundled trades count towards volume.
+pub fn aggregate_bars(
+    source: &dyn DataSource,
+    interval: BarInterval,
+    unbundle: Unbundle,
+) -> (Vec<Bar>, SkipStats) {
+    aggregate_bars_from(source, interval, 0, unbundle)
+}
 
-    for i in 0..scid.num_records {
-        let tick: Tick = scid.tick(i);
-        if tick.price <= 0.0 {
-            continue;
+/// `aggregate_bars` over the records from index `start` onwards.
+pub fn aggregate_bars_from(
+    source: &dyn DataSource,
+    interval: BarInterval,
+    start: usize,
+    unbundle: Unbundle,
+) -> (Vec<Bar>, SkipStats) {
+    if start >= source.num_ticks() {
+        return (Vec::new(), SkipStats::default());
+    }
+
+    let mut bars: Vec<Bar> = Vec::with_capacity((source.num_ticks() - start) / 100);
+    let mut builder = BarBuilder::starting_at(interval, start).with_unbundle(unbundle);
+    for tick in source.iter_range(start, source.num_ticks()) {
+        if let Some(done) = builder.push(&tick) {
+            bars.push(done);
         }
-        let bs = interval.bar_start(tick.timestamp_us);
+    }
+    bars.extend(builder.finish());
+    (bars, builder.skipped)
+}
 
-        if bs != current_bar_start {
-            if current_bar_start != i64::MIN {
-                bars.push(bar);
+/// `aggregate_bars` for several intervals in a single scan of `source`, one bar series per
+/// interval in the order given. Each series closes a bar when a tick crosses that
+/// interval's boundary, so coarser bars line up with the finer ones.
+pub fn aggregate_bars_multi(
+    source: &dyn DataSource,
+    intervals: &[BarInterval],
+    unbundle: Unbundle,
+) -> (Vec<Vec<Bar>>, SkipStats) {
+    let mut series: Vec<Vec<Bar>> = intervals
+        .iter()
+        .map(|_| Vec::with_capacity(source.num_ticks() / 100))
+        .collect();
+    let mut builders: Vec<BarBuilder> = intervals
+        .iter()
+        .map(|&interval| BarBuilder::new(interval).with_unbundle(unbundle))
+        .collect();
+    for tick in source.iter() {
+        for (builder, bars) in builders.iter_mut().zip(&mut series) {
+            if let Some(done) = builder.push(&tick) {
+                bars.push(done);
             }
-            current_bar_start = bs;
-            bar = Bar {
-                timestamp_us: bs,
-                open: tick.price,
-                high: tick.price,
-                low: tick.price,
-                close: tick.price,
-                volume: tick.volume as u64,
-                bid_volume: tick.bid_volume as u64,
-                ask_volume: tick.ask_volume as u64,
-                num_trades: tick.num_trades as u64,
-            };
+        }
+    }
+    for (builder, bars) in builders.iter_mut().zip(&mut series) {
+        bars.extend(builder.finish());
+    }
+    let skipped = builders.first().map(|b| b.skipped).unwrap_or_default();
+    (series, skipped)
+}
+
+/// `aggregate_bars` reading the records in stable timestamp order, for files that are
+/// not sorted. Bars keep the record indices of their first and last ticks in that order.
+pub fn aggregate_bars_sorted(
+    source: &dyn DataSource,
+    interval: BarInterval,
+    unbundle: Unbundle,
+) -> (Vec<Bar>, SkipStats) {
+    let mut bars: Vec<Bar> = Vec::with_capacity(source.num_ticks() / 100);
+    let mut builder = BarBuilder::new(interval).with_unbundle(unbundle);
+    for i in source.sorted_indices() {
+        if let Some(done) = builder.push_record(&source.tick(i), i) {
+            bars.push(done);
+        }
+    }
+    bars.extend(builder.finish());
+    (bars, builder.skipped)
+}
+
+/// Incremental tick-to-bar aggregation for streamed ticks (e.g. a `ScidReaderIter`), with the
+/// same rules as `aggregate_bars`.
+pub struct BarBuilder {
+    interval: BarInterval,
+    /// Only ever holds a bar seeded from a real tick, so no placeholder can be emitted
+    current: Option<Bar>,
+    /// Start of the bar after `current`
+    current_end: i64,
+    /// SCID record index of the next tick pushed
+    next_index: usize,
+    /// Latest valid tick timestamp so far; earlier ticks are dropped as out of order
+    last_us: i64,
+    /// Invalid and out-of-order ticks dropped so far
+    pub skipped: SkipStats,
+    unbundler: Unbundler,
+}
+
+impl BarBuilder {
+    /// Builder for ticks streamed from the first record of a file.
+    pub fn new(interval: BarInterval) -> Self {
+        Self::starting_at(interval, 0)
+    }
+
+    /// Builder whose first pushed tick is SCID record `first_index`.
+    pub fn starting_at(interval: BarInterval, first_index: usize) -> Self {
+        BarBuilder {
+            interval,
+            current: None,
+            current_end: i64::MIN,
+            next_index: first_index,
+            last_us: i64::MIN,
+            skipped: SkipStats::default(),
+            unbundler: Unbundler::default(),
+        }
+    }
+
+    /// Count the records of unbundled trades per `mode` (default `Unbundle::Ignore`).
+    pub fn with_unbundle(mut self, mode: Unbundle) -> Self {
+        self.unbundler = Unbundler::new(mode);
+        self
+    }
+
+    /// Add the next tick; returns the previous bar once a tick opens a new one.
+    pub fn push(&mut self, tick: &Tick) -> Option<Bar> {
+        let index = self.next_index;
+        self.next_index += 1;
+        self.push_record(tick, index)
+    }
+
+    /// `push` for a tick read out of file order, from SCID record `index`.
+    pub fn push_record(&mut self, tick: &Tick, index: usize) -> Option<Bar> {
+        let tick = &self.unbundler.apply(tick);
+        if !self.skipped.accept(tick) {
+            return None;
+        }
+        if tick.timestamp_us < self.last_us {
+            self.skipped.out_of_order += 1;
+            return None;
+        }
+        self.last_us = tick.timestamp_us;
+        // Ticks are in order, so one before the next boundary belongs to the current bar
+        if let (Some(bar), true) = (self.current.as_mut(), tick.timestamp_us < self.current_end) {
+            bar.update(tick, index);
+            return None;
+        }
+        let start = self.interval.bar_start(tick.timestamp_us);
+        self.current_end = self.interval.next_start(start);
+        self.current.replace(Bar::from_tick(start, tick, index))
+    }
+
+    /// Take the bar still collecting ticks, once the ticks have run out.
+    pub fn finish(&mut self) -> Option<Bar> {
+        self.current.take()
+    }
+}
+
+/// Copy `bars` into columns, as handed to Python strategies.
+pub fn bar_columns(bars: &[Bar]) -> BarColumns {
+    bars.iter().collect()
+}
+
+/// Convert bars to Heikin-Ashi candles. Volume fields are carried over unchanged.
+///
+/// HA close = (O + H + L + C) / 4, HA open = (prev HA open + prev HA close) / 2 with the
+/// first bar seeded from its own (O + C) / 2, and HA high/low extend the real high/low to
+/// cover the HA open and close.
+pub fn to_heikin_ashi(bars: &[Bar]) -> Vec<Bar> {
+    let mut out: Vec<Bar> = Vec::with_capacity(bars.len());
+    for (i, bar) in bars.iter().enumerate() {
+        let ha_close = (bar.open + bar.high + bar.low + bar.close) / 4.0;
+        let ha_open = if i == 0 {
+            (bar.open + bar.close) / 2.0
         } else {
-            if tick.price > bar.high {
-                bar.high = tick.price;
+            (out[i - 1].open + out[i - 1].close) / 2.0
+        };
+        out.push(Bar {
+            open: ha_open,
+            high: bar.high.max(ha_open).max(ha_close),
+            low: bar.low.min(ha_open).min(ha_close),
+            close: ha_close,
+            ..*bar
+        });
+    }
+    out
+}
+
+/// Merge bars built from several files into one series in timestamp order. Where files
+/// overlap, the bar from the earliest-listed file is kept for each timestamp.
+pub fn merge_bar_vecs(vecs: Vec<Vec<Bar>>) -> Vec<Bar> {
+    merge_by_timestamp(vecs, |bar| bar.timestamp_us)
+}
+
+/// Insert a synthetic bar for every empty interval between consecutive bars. Fillers carry
+/// the previous close as open/high/low/close, have zero volume and trades, and are flagged
+/// `is_synthetic`. Gaps of more than `max_gap_bars` empty intervals (e.g. weekends at a
+/// seconds interval) are left unfilled; returns the filled bars and the number of such gaps.
+pub fn fill_gaps(bars: &[Bar], interval: BarInterval, max_gap_bars: usize) -> (Vec<Bar>, usize) {
+    let step_us = interval.secs as i64 * US_PER_SEC;
+    let mut out: Vec<Bar> = Vec::with_capacity(bars.len());
+    let mut unfilled = 0;
+    for bar in bars {
+        if let Some(prev) = out.last().copied() {
+            let missing = ((bar.timestamp_us - prev.timestamp_us) / step_us - 1).max(0) as usize;
+            if missing > max_gap_bars {
+                unfilled += 1;
+                out.push(*bar);
+                continue;
             }
-            if tick.price < bar.low {
-                bar.low = tick.price;
+            let mut ts = interval.next_start(prev.timestamp_us);
+            while ts < bar.timestamp_us {
+                out.push(Bar {
+                    timestamp_us: ts,
+                    open: prev.close,
+                    high: prev.close,
+                    low: prev.close,
+                    close: prev.close,
+                    volume: 0,
+                    bid_volume: 0,
+                    ask_volume: 0,
+                    num_trades: 0,
+                    tick_count: 0,
+                    delta: 0,
+                    delta_high: 0,
+                    delta_low: 0,
+                    is_synthetic: true,
+                    first_tick_index: prev.last_tick_index,
+                    last_tick_index: prev.last_tick_index,
+                });
+                ts = interval.next_start(ts);
             }
-            bar.close = tick.price;
-            bar.volume += tick.volume as u64;
-            bar.bid_volume += tick.bid_volume as u64;
-            bar.ask_volume += tick.ask_volume as u64;
-            bar.num_trades += tick.num_trades as u64;
         }
+        out.push(*bar);
+    }
+    (out, unfilled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scid::ScidFile;
+    use crate::session::{parse_iso8601, parse_timezone};
+
+    const START_US: i64 = 1_704_153_600_000_000;
+
+    fn tick(secs: i64, price: f64) -> Tick {
+        Tick {
+            timestamp_us: START_US + secs * US_PER_SEC,
+            open: 0.0,
+            price,
+            bid: price,
+            ask: price,
+            volume: 1,
+            bid_volume: 0,
+            ask_volume: 1,
+            num_trades: 1,
+        }
+    }
+
+    fn minute_bars(ticks: &[Tick]) -> (Vec<Bar>, SkipStats) {
+        let scid = ScidFile::from_ticks(ticks).unwrap();
+        aggregate_bars(&scid, BarInterval::new(60), Unbundle::Ignore)
+    }
+
+    #[test]
+    fn all_invalid_prices_give_no_bars() {
+        let (bars, skipped) = minute_bars(&[tick(0, 0.0), tick(1, -1.0), tick(61, 0.0)]);
+        assert!(bars.is_empty());
+        assert_eq!(skipped.non_positive_price, 3);
+    }
+
+    #[test]
+    fn a_single_tick_makes_one_bar() {
+        let (bars, _) = minute_bars(&[tick(30, 100.25)]);
+        assert_eq!(bars.len(), 1);
+        let bar = bars[0];
+        assert_eq!(bar.timestamp_us, START_US);
+        assert_eq!(
+            (bar.open, bar.high, bar.low, bar.close),
+            (100.25, 100.25, 100.25, 100.25)
+        );
+        assert_eq!((bar.tick_count, bar.volume), (1, 1));
+        assert_eq!((bar.first_tick_index, bar.last_tick_index), (0, 0));
+    }
+
+    #[test]
+    fn invalid_ticks_at_bar_boundaries_are_skipped() {
+        let ticks = [
+            tick(0, 100.0),
+            tick(59, -1.0),
+            tick(60, 0.0),
+            tick(61, 101.0),
+            tick(119, 102.0),
+            tick(120, 0.0),
+        ];
+        let (bars, skipped) = minute_bars(&ticks);
+        assert_eq!(skipped.non_positive_price, 3);
+        assert_eq!(bars.len(), 2);
+        let ohlc = |b: &Bar| (b.open, b.high, b.low, b.close);
+        assert_eq!(ohlc(&bars[0]), (100.0, 100.0, 100.0, 100.0));
+        assert_eq!(bars[0].tick_count, 1);
+        assert_eq!(bars[1].timestamp_us, START_US + 60 * US_PER_SEC);
+        assert_eq!(ohlc(&bars[1]), (101.0, 102.0, 101.0, 102.0));
+        assert_eq!((bars[1].first_tick_index, bars[1].last_tick_index), (3, 4));
+    }
+
+    #[test]
+    fn flt_max_placeholders_are_left_out_of_bars() {
+        // A raw FLT_MAX record, as Sierra Chart leaves at the tail of a live file
+        let placeholder = f32::MAX as f64 / 100.0;
+        let ticks = [tick(0, 100.0), tick(20, placeholder), tick(40, 99.5)];
+        let (bars, skipped) = minute_bars(&ticks);
+        assert_eq!(skipped.placeholder, 1);
+        assert_eq!(bars.len(), 1);
+        let bar = bars[0];
+        assert_eq!((bar.high, bar.low, bar.close), (100.0, 99.5, 99.5));
+        assert_eq!(bar.tick_count, 2);
+
+        // The same in memory, priced at FLT_MAX itself
+        let mut builder = BarBuilder::new(BarInterval::new(60));
+        builder.push(&tick(0, 100.0));
+        builder.push(&tick(20, f32::MAX as f64));
+        let bar = builder.finish().unwrap();
+        assert_eq!((bar.high, bar.low), (100.0, 100.0));
+        assert_eq!(builder.skipped.placeholder, 1);
+    }
+
+    #[test]
+    fn builder_with_no_valid_ticks_finishes_empty() {
+        let mut builder = BarBuilder::new(BarInterval::new(60));
+        assert!(builder.push(&tick(0, -5.0)).is_none());
+        assert!(builder.finish().is_none());
+        assert_eq!(builder.skipped.non_positive_price, 1);
+    }
+
+    #[test]
+    fn merged_bars_keep_the_first_listed_file_on_overlap() {
+        // Minute bars from three files, each priced with its file number
+        let bars = |file: f64, minutes: &[i64]| -> Vec<Bar> {
+            minutes
+                .iter()
+                .map(|&m| Bar::from_tick(START_US + m * 60 * US_PER_SEC, &tick(m * 60, file), 0))
+                .collect()
+        };
+        let merged = merge_bar_vecs(vec![
+            bars(1.0, &[2, 3, 4]),
+            bars(2.0, &[0, 1, 2, 3]),
+            bars(3.0, &[1, 4, 5]),
+        ]);
+        let got: Vec<(i64, f64)> = merged
+            .iter()
+            .map(|b| ((b.timestamp_us - START_US) / (60 * US_PER_SEC), b.close))
+            .collect();
+        assert_eq!(
+            got,
+            [(0, 2.0), (1, 2.0), (2, 1.0), (3, 1.0), (4, 1.0), (5, 3.0)]
+        );
+    }
+
+    fn utc(s: &str) -> i64 {
+        parse_iso8601(s).unwrap()
+    }
+
+    /// Daily bars from 17:00 Chicago time, built from a tick every 30 minutes over `from..to`.
+    fn cme_daily_starts(from: &str, to: &str) -> Vec<i64> {
+        let chicago = SessionClock::with_zone(parse_timezone("America/Chicago").unwrap());
+        let interval = BarInterval::new(86_400).with_session(17 * 3600, chicago);
+        let (from, to) = (utc(from), utc(to));
+        let ticks: Vec<Tick> = (0..)
+            .map(|i| from + i * 1_800 * US_PER_SEC)
+            .take_while(|&ts| ts < to)
+            .map(|ts| Tick {
+                timestamp_us: ts,
+                ..tick(0, 100.0)
+            })
+            .collect();
+        let scid = ScidFile::from_ticks(&ticks).unwrap();
+        let (bars, _) = aggregate_bars(&scid, interval, Unbundle::Ignore);
+        bars.iter().map(|b| b.timestamp_us).collect()
+    }
+
+    #[test]
+    fn fixed_session_offset_shifts_daily_bars() {
+        // 17:00 at a fixed UTC-6 offset is 23:00 UTC
+        let interval =
+            BarInterval::new(86_400).with_session(17 * 3600, SessionClock::new(-6 * 3600));
+        assert!(!interval.is_utc_aligned());
+        let start = utc("2024-01-02T23:00:00Z");
+        assert_eq!(interval.bar_start(start), start);
+        assert_eq!(interval.bar_start(utc("2024-01-03T22:59:59Z")), start);
+        assert_eq!(
+            interval.bar_start(utc("2024-01-02T22:59:59Z")),
+            utc("2024-01-01T23:00:00Z")
+        );
+        assert_eq!(interval.next_start(start), utc("2024-01-03T23:00:00Z"));
+        assert!(BarInterval::new(3600)
+            .with_session(3600, SessionClock::default())
+            .is_utc_aligned());
+    }
+
+    #[test]
+    fn a_23_hour_session_day_is_one_bar() {
+        // Clocks spring forward at 02:00 CST on Sunday 2024-03-10
+        let starts = cme_daily_starts("2024-03-09T00:00:00Z", "2024-03-11T12:00:00Z");
+        assert_eq!(
+            starts,
+            [
+                utc("2024-03-08T23:00:00Z"),
+                utc("2024-03-09T23:00:00Z"),
+                utc("2024-03-10T22:00:00Z"),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_25_hour_session_day_is_one_bar() {
+        // Clocks fall back at 02:00 CDT on Sunday 2024-11-03
+        let starts = cme_daily_starts("2024-11-02T00:00:00Z", "2024-11-04T12:00:00Z");
+        assert_eq!(
+            starts,
+            [
+                utc("2024-11-01T22:00:00Z"),
+                utc("2024-11-02T22:00:00Z"),
+                utc("2024-11-03T23:00:00Z"),
+            ]
+        );
+    }
+
+    #[test]
+    fn five_minute_bars_fold_their_one_minute_bars() {
+        // An hour of ticks 7 s apart with prices that wander up and down
+        let ticks: Vec<Tick> = (0..3600 / 7)
+            .map(|i| tick(i * 7, 100.0 + ((i * 37) % 23) as f64 * 0.25))
+            .collect();
+        let scid = ScidFile::from_ticks(&ticks).unwrap();
+        let intervals = [BarInterval::new(60), BarInterval::new(300)];
+        let (series, _) = aggregate_bars_multi(&scid, &intervals, Unbundle::Ignore);
+        let (minutes, fives) = (&series[0], &series[1]);
+        assert_eq!(minutes.len(), 60);
+        assert_eq!(fives.len(), minutes.len() / 5);
+
+        for (five, group) in fives.iter().zip(minutes.chunks(5)) {
+            assert_eq!(five.timestamp_us, group[0].timestamp_us);
+            assert_eq!(five.open, group[0].open);
+            assert_eq!(five.close, group[4].close);
+            let high = group.iter().map(|b| b.high).fold(f64::MIN, f64::max);
+            let low = group.iter().map(|b| b.low).fold(f64::MAX, f64::min);
+            assert_eq!((five.high, five.low), (high, low));
+            assert_eq!(five.volume, group.iter().map(|b| b.volume).sum::<u64>());
+            assert_eq!(
+                five.tick_count,
+                group.iter().map(|b| b.tick_count).sum::<u32>()
+            );
+        }
+    }
+
+    #[test]
+    fn fill_gaps_carries_the_close_over_short_gaps_only() {
+        // Two empty minutes after the first bar, then nine after the second
+        let (bars, _) = minute_bars(&[
+            tick(0, 100.0),
+            tick(30, 101.0),
+            tick(200, 102.0),
+            tick(800, 103.0),
+        ]);
+        assert_eq!(bars.len(), 3);
+        let (filled, unfilled) = fill_gaps(&bars, BarInterval::new(60), 2);
+        assert_eq!(unfilled, 1);
+        let starts: Vec<i64> = filled
+            .iter()
+            .map(|b| (b.timestamp_us - START_US) / US_PER_SEC)
+            .collect();
+        assert_eq!(starts, [0, 60, 120, 180, 780]);
+        for filler in &filled[1..3] {
+            assert!(filler.is_synthetic);
+            assert_eq!(
+                (filler.open, filler.high, filler.low, filler.close),
+                (101.0, 101.0, 101.0, 101.0)
+            );
+            assert_eq!((filler.volume, filler.tick_count), (0, 0));
+        }
+        assert!(!filled[3].is_synthetic && !filled[4].is_synthetic);
     }
-    // Push the last bar
-    if current_bar_start != i64::MIN {
-        bars.push(bar);
+
+    #[test]
+    fn heikin_ashi_matches_hand_values() {
+        // OHLC 100/104/98/102, 102/106/101.5/105 and 105/105.5/99/100
+        let prices = [
+            [100.0, 104.0, 98.0, 102.0],
+            [102.0, 106.0, 101.5, 105.0],
+            [105.0, 105.5, 99.0, 100.0],
+        ];
+        let ticks: Vec<Tick> = prices
+            .iter()
+            .enumerate()
+            .flat_map(|(i, bar)| {
+                let start = i as i64 * 60;
+                bar.iter()
+                    .enumerate()
+                    .map(move |(j, &p)| tick(start + j as i64, p))
+            })
+            .collect();
+        let (bars, _) = minute_bars(&ticks);
+        let ha: Vec<[f64; 4]> = to_heikin_ashi(&bars)
+            .iter()
+            .map(|b| [b.open, b.high, b.low, b.close])
+            .collect();
+        assert_eq!(
+            ha,
+            [
+                // Seeded open (100 + 102) / 2; close (100 + 104 + 98 + 102) / 4
+                [101.0, 104.0, 98.0, 101.0],
+                // Open (101 + 101) / 2 sits below the real low, so the low extends to it
+                [101.0, 106.0, 101.0, 103.625],
+                // Open (101 + 103.625) / 2; close (105 + 105.5 + 99 + 100) / 4
+                [102.3125, 105.5, 99.0, 102.375],
+            ]
+        );
+    }
+
+    #[test]
+    fn tick_count_counts_records_not_reported_trades() {
+        let bundled = Tick {
+            num_trades: 5,
+            ..tick(20, 100.5)
+        };
+        let (bars, _) = minute_bars(&[tick(10, 100.0), bundled, tick(50, 101.0), tick(70, 99.0)]);
+        assert_eq!(bars.len(), 2);
+        assert_eq!((bars[0].tick_count, bars[0].num_trades), (3, 7));
+        assert_eq!(bars[1].tick_count, 1);
     }
-    bars
 }
@@ -0,0 +1,251 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::bar::{aggregate_bars, aggregate_bars_from, Bar, BarInterval};
+use crate::datasource::DataSource;
+use crate::scid::{SkipStats, Unbundle};
+
+const MAGIC: &[u8; 4] = b"SBAR";
+const VERSION: u32 = 6;
+/// Magic, version, then eleven u64 header fields
+const HEADER_LEN: usize = 8 + 11 * 8;
+/// Timestamp, OHLC, five volume and count fields, three delta fields and the first/last
+/// tick indices, 8 bytes each
+const BAR_LEN: usize = 15 * 8;
+/// Trailing FNV-1a checksum of everything before it
+const CHECKSUM_LEN: usize = 8;
+
+/// What a cache file was built from.
+#[derive(Clone, Copy, Debug)]
+struct CacheHeader {
+    interval_secs: u64,
+    source_len: u64,
+    source_mtime_ns: u64,
+    /// Records in the source file when the cache was written
+    num_records: u64,
+    /// First record of the last bar, which may still have been filling up
+    tail_start: u64,
+    /// Skipped records over the whole source file
+    skipped: SkipStats,
+}
+
+struct CacheFile {
+    header: CacheHeader,
+    bars: Vec<Bar>,
+}
+
+/// Bars for `ticks` (read from `source`) from the cache in `cache_dir`, aggregating
+/// and writing the cache on a miss. If the source has only grown since the cache was
+/// written, the cached bars are extended from the last cached bar instead of being
+/// rebuilt. Unreadable or corrupted cache files are rebuilt.
+pub fn load_or_build(
+    ticks: &dyn DataSource,
+    source: &Path,
+    interval: BarInterval,
+    cache_dir: &Path,
+) -> Result<(Vec<Bar>, SkipStats), String> {
+    let meta = fs::metadata(source).map_err(|e| format!("stat {}: {e}", source.display()))?;
+    let source_len = meta.len();
+    let source_mtime_ns = meta
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_nanos() as u64);
+    let cache_path = cache_path(cache_dir, source, interval);
+
+    let cached = read_cache(&cache_path).filter(|c| c.header.interval_secs == interval.secs);
+    let (bars, skipped) = match cached {
+        Some(c)
+            if c.header.source_len == source_len
+                && c.header.source_mtime_ns == source_mtime_ns
+                && c.header.num_records as usize == ticks.num_ticks() =>
+        {
+            return Ok((c.bars, c.header.skipped));
+        }
+        Some(c)
+            if source_len > c.header.source_len
+                && ticks.num_ticks() > c.header.num_records as usize
+                && !c.bars.is_empty() =>
+        {
+            extend(ticks, interval, c)
+        }
+        _ => aggregate_bars(ticks, interval, Unbundle::Ignore),
+    };
+
+    let tail_start = bars.last().map_or(ticks.num_ticks(), |b| {
+        ticks.find_index_at_or_after(b.timestamp_us)
+    });
+    let header = CacheHeader {
+        interval_secs: interval.secs,
+        source_len,
+        source_mtime_ns,
+        num_records: ticks.num_ticks() as u64,
+        tail_start: tail_start as u64,
+        skipped,
+    };
+    write_cache(cache_dir, &cache_path, &header, &bars)?;
+    Ok((bars, skipped))
+}
+
+/// Re-aggregate from the start of the last cached bar and append to the cached bars.
+fn extend(
+    ticks: &dyn DataSource,
+    interval: BarInterval,
+    cached: CacheFile,
+) -> (Vec<Bar>, SkipStats) {
+    let CacheFile { header, mut bars } = cached;
+    let tail_start = header.tail_start as usize;
+    let old_tail = ticks.skip_stats_in(tail_start..header.num_records as usize);
+    let (new_bars, new_tail) = aggregate_bars_from(ticks, interval, tail_start, Unbundle::Ignore);
+    bars.pop();
+    bars.extend(new_bars);
+    (bars, header.skipped - old_tail + new_tail)
+}
+
+/// One cache file per source path and interval.
+fn cache_path(cache_dir: &Path, source: &Path, interval: BarInterval) -> PathBuf {
+    let canonical = fs::canonicalize(source).unwrap_or_else(|_| source.to_path_buf());
+    let key = fnv1a(canonical.to_string_lossy().as_bytes());
+    let stem = source
+        .file_stem()
+        .map_or_else(|| "bars".into(), |s| s.to_string_lossy());
+    cache_dir.join(format!("{stem}-{key:016x}-{}s.bars", interval.secs))
+}
+
+/// Read and verify a cache file. Any problem (missing, truncated, bad magic or
+/// checksum) yields None so the caller rebuilds.
+fn read_cache(path: &Path) -> Option<CacheFile> {
+    let data = fs::read(path).ok()?;
+    if data.len() < HEADER_LEN + CHECKSUM_LEN || &data[0..4] != MAGIC {
+        return None;
+    }
+    let (body, checksum) = data.split_at(data.len() - CHECKSUM_LEN);
+    if fnv1a(body) != u64::from_le_bytes(checksum.try_into().ok()?) {
+        return None;
+    }
+    if u32::from_le_bytes(data[4..8].try_into().ok()?) != VERSION {
+        return None;
+    }
+
+    let word = |i: usize| u64::from_le_bytes(body[8 + i * 8..16 + i * 8].try_into().unwrap());
+    let header = CacheHeader {
+        interval_secs: word(0),
+        source_len: word(1),
+        source_mtime_ns: word(2),
+        num_records: word(3),
+        tail_start: word(4),
+        skipped: SkipStats {
+            non_positive_price: word(5) as usize,
+            non_finite: word(6) as usize,
+            timestamp_out_of_range: word(7) as usize,
+            placeholder: word(8) as usize,
+            out_of_order: word(9) as usize,
+        },
+    };
+    let num_bars = word(10) as usize;
+    if body.len() != HEADER_LEN + num_bars.checked_mul(BAR_LEN)? {
+        return None;
+    }
+
+    let bars = body[HEADER_LEN..]
+        .chunks_exact(BAR_LEN)
+        .map(|rec| {
+            let field = |i: usize| -> [u8; 8] { rec[i * 8..i * 8 + 8].try_into().unwrap() };
+            Bar {
+                timestamp_us: i64::from_le_bytes(field(0)),
+                open: f64::from_le_bytes(field(1)),
+                high: f64::from_le_bytes(field(2)),
+                low: f64::from_le_bytes(field(3)),
+                close: f64::from_le_bytes(field(4)),
+                volume: u64::from_le_bytes(field(5)),
+                bid_volume: u64::from_le_bytes(field(6)),
+                ask_volume: u64::from_le_bytes(field(7)),
+                num_trades: u64::from_le_bytes(field(8)),
+                tick_count: u64::from_le_bytes(field(9)) as u32,
+                delta: i64::from_le_bytes(field(10)),
+                delta_high: i64::from_le_bytes(field(11)),
+                delta_low: i64::from_le_bytes(field(12)),
+                is_synthetic: false,
+                first_tick_index: u64::from_le_bytes(field(13)) as usize,
+                last_tick_index: u64::from_le_bytes(field(14)) as usize,
+            }
+        })
+        .collect();
+    Some(CacheFile { header, bars })
+}
+
+/// Write the cache to a temporary file and rename it into place, so a crash mid-write
+/// never leaves a partial cache behind.
+fn write_cache(
+    cache_dir: &Path,
+    path: &Path,
+    header: &CacheHeader,
+    bars: &[Bar],
+) -> Result<(), String> {
+    fs::create_dir_all(cache_dir)
+        .map_err(|e| format!("create cache dir {}: {e}", cache_dir.display()))?;
+
+    let mut buf = Vec::with_capacity(HEADER_LEN + bars.len() * BAR_LEN + CHECKSUM_LEN);
+    buf.extend_from_slice(MAGIC);
+    buf.extend_from_slice(&VERSION.to_le_bytes());
+    for word in [
+        header.interval_secs,
+        header.source_len,
+        header.source_mtime_ns,
+        header.num_records,
+        header.tail_start,
+        header.skipped.non_positive_price as u64,
+        header.skipped.non_finite as u64,
+        header.skipped.timestamp_out_of_range as u64,
+        header.skipped.placeholder as u64,
+        header.skipped.out_of_order as u64,
+        bars.len() as u64,
+    ] {
+        buf.extend_from_slice(&word.to_le_bytes());
+    }
+    for bar in bars {
+        buf.extend_from_slice(&bar.timestamp_us.to_le_bytes());
+        for price in [bar.open, bar.high, bar.low, bar.close] {
+            buf.extend_from_slice(&price.to_le_bytes());
+        }
+        let tick_count = bar.tick_count as u64;
+        for count in [
+            bar.volume,
+            bar.bid_volume,
+            bar.ask_volume,
+            bar.num_trades,
+            tick_count,
+        ] {
+            buf.extend_from_slice(&count.to_le_bytes());
+        }
+        for delta in [bar.delta, bar.delta_high, bar.delta_low] {
+            buf.extend_from_slice(&delta.to_le_bytes());
+        }
+        for index in [bar.first_tick_index, bar.last_tick_index] {
+            buf.extend_from_slice(&(index as u64).to_le_bytes());
+        }
+    }
+    let checksum = fnv1a(&buf);
+    buf.extend_from_slice(&checksum.to_le_bytes());
+
+    let tmp = path.with_extension("bars.tmp");
+    let write = || -> std::io::Result<()> {
+        let mut f = fs::File::create(&tmp)?;
+        f.write_all(&buf)?;
+        f.sync_all()?;
+        fs::rename(&tmp, path)
+    };
+    write().map_err(|e| format!("write bar cache {}: {e}", path.display()))
+}
+
+/// 64-bit FNV-1a. Stable across builds, unlike `std`'s default hasher.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &b in bytes {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
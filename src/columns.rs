@@ -0,0 +1,248 @@
+//! Column-oriented copies of ticks and bars, and the dicts of numpy arrays built from them.
+
+use numpy::PyArray1;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::bar::Bar;
+use crate::scid::Tick;
+
+/// Tick fields as parallel columns, one entry per tick. Timestamps are Unix seconds.
+#[derive(Clone, Debug, Default)]
+pub struct TickColumns {
+    pub timestamp: Vec<f64>,
+    pub open: Vec<f64>,
+    pub price: Vec<f64>,
+    pub bid: Vec<f64>,
+    pub ask: Vec<f64>,
+    pub volume: Vec<f64>,
+    pub bid_volume: Vec<f64>,
+    pub ask_volume: Vec<f64>,
+    pub num_trades: Vec<f64>,
+    /// `TradeType::code` of each record
+    pub trade_type: Vec<u8>,
+}
+
+impl TickColumns {
+    pub fn with_capacity(n: usize) -> Self {
+        TickColumns {
+            timestamp: Vec::with_capacity(n),
+            open: Vec::with_capacity(n),
+            price: Vec::with_capacity(n),
+            bid: Vec::with_capacity(n),
+            ask: Vec::with_capacity(n),
+            volume: Vec::with_capacity(n),
+            bid_volume: Vec::with_capacity(n),
+            ask_volume: Vec::with_capacity(n),
+            num_trades: Vec::with_capacity(n),
+            trade_type: Vec::with_capacity(n),
+        }
+    }
+
+    pub fn push(&mut self, tick: &Tick) {
+        self.timestamp.push(tick.timestamp_us as f64 / 1_000_000.0);
+        self.open.push(tick.open);
+        self.price.push(tick.price);
+        self.bid.push(tick.bid);
+        self.ask.push(tick.ask);
+        self.volume.push(tick.volume as f64);
+        self.bid_volume.push(tick.bid_volume as f64);
+        self.ask_volume.push(tick.ask_volume as f64);
+        self.num_trades.push(tick.num_trades as f64);
+        self.trade_type.push(tick.trade_type().code());
+    }
+
+    pub fn len(&self) -> usize {
+        self.timestamp.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.timestamp.is_empty()
+    }
+
+    /// A dict with one numpy array per column, keyed by field name.
+    pub fn into_pydict(self, py: Python<'_>) -> PyResult<Bound<'_, PyDict>> {
+        let d = PyDict::new(py);
+        d.set_item("timestamp", PyArray1::from_vec(py, self.timestamp))?;
+        d.set_item("open", PyArray1::from_vec(py, self.open))?;
+        d.set_item("price", PyArray1::from_vec(py, self.price))?;
+        d.set_item("bid", PyArray1::from_vec(py, self.bid))?;
+        d.set_item("ask", PyArray1::from_vec(py, self.ask))?;
+        d.set_item("volume", PyArray1::from_vec(py, self.volume))?;
+        d.set_item("bid_volume", PyArray1::from_vec(py, self.bid_volume))?;
+        d.set_item("ask_volume", PyArray1::from_vec(py, self.ask_volume))?;
+        d.set_item("num_trades", PyArray1::from_vec(py, self.num_trades))?;
+        d.set_item("trade_type", PyArray1::from_vec(py, self.trade_type))?;
+        Ok(d)
+    }
+}
+
+impl FromIterator<Tick> for TickColumns {
+    fn from_iter<I: IntoIterator<Item = Tick>>(ticks: I) -> Self {
+        let ticks = ticks.into_iter();
+        let mut columns = TickColumns::with_capacity(ticks.size_hint().0);
+        for tick in ticks {
+            columns.push(&tick);
+        }
+        columns
+    }
+}
+
+/// Bar fields as parallel columns, one entry per bar, plus the running sum of delta.
+/// Timestamps are Unix seconds.
+#[derive(Clone, Debug, Default)]
+pub struct BarColumns {
+    pub timestamp: Vec<f64>,
+    pub open: Vec<f64>,
+    pub high: Vec<f64>,
+    pub low: Vec<f64>,
+    pub close: Vec<f64>,
+    pub volume: Vec<f64>,
+    pub bid_volume: Vec<f64>,
+    pub ask_volume: Vec<f64>,
+    pub delta: Vec<f64>,
+    pub delta_high: Vec<f64>,
+    pub delta_low: Vec<f64>,
+    pub cum_delta: Vec<f64>,
+    pub tick_count: Vec<f64>,
+    pub is_synthetic: Vec<bool>,
+}
+
+impl BarColumns {
+    pub fn len(&self) -> usize {
+        self.timestamp.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.timestamp.is_empty()
+    }
+
+    /// A dict with one numpy array per column, keyed by field name, and `num_bars`.
+    pub fn into_pydict(self, py: Python<'_>) -> PyResult<Bound<'_, PyDict>> {
+        let n = self.len();
+        let d = PyDict::new(py);
+        d.set_item("timestamp", PyArray1::from_vec(py, self.timestamp))?;
+        d.set_item("open", PyArray1::from_vec(py, self.open))?;
+        d.set_item("high", PyArray1::from_vec(py, self.high))?;
+        d.set_item("low", PyArray1::from_vec(py, self.low))?;
+        d.set_item("close", PyArray1::from_vec(py, self.close))?;
+        d.set_item("volume", PyArray1::from_vec(py, self.volume))?;
+        d.set_item("bid_volume", PyArray1::from_vec(py, self.bid_volume))?;
+        d.set_item("ask_volume", PyArray1::from_vec(py, self.ask_volume))?;
+        d.set_item("delta", PyArray1::from_vec(py, self.delta))?;
+        d.set_item("delta_high", PyArray1::from_vec(py, self.delta_high))?;
+        d.set_item("delta_low", PyArray1::from_vec(py, self.delta_low))?;
+        d.set_item("cum_delta", PyArray1::from_vec(py, self.cum_delta))?;
+        d.set_item("tick_count", PyArray1::from_vec(py, self.tick_count))?;
+        d.set_item("is_synthetic", PyArray1::from_vec(py, self.is_synthetic))?;
+        d.set_item("num_bars", n)?;
+        Ok(d)
+    }
+}
+
+impl<'a> FromIterator<&'a Bar> for BarColumns {
+    fn from_iter<I: IntoIterator<Item = &'a Bar>>(bars: I) -> Self {
+        let mut columns = BarColumns::default();
+        let mut cum_delta = 0_i64;
+        for bar in bars {
+            columns
+                .timestamp
+                .push(bar.timestamp_us as f64 / 1_000_000.0);
+            columns.open.push(bar.open);
+            columns.high.push(bar.high);
+            columns.low.push(bar.low);
+            columns.close.push(bar.close);
+            columns.volume.push(bar.volume as f64);
+            columns.bid_volume.push(bar.bid_volume as f64);
+            columns.ask_volume.push(bar.ask_volume as f64);
+            cum_delta += bar.delta;
+            columns.delta.push(bar.delta as f64);
+            columns.delta_high.push(bar.delta_high as f64);
+            columns.delta_low.push(bar.delta_low as f64);
+            columns.cum_delta.push(cum_delta as f64);
+            columns.tick_count.push(bar.tick_count as f64);
+            columns.is_synthetic.push(bar.is_synthetic);
+        }
+        columns
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bar::{aggregate_bars, BarInterval};
+    use crate::scid::{ScidFile, Unbundle};
+
+    const START_US: i64 = 1_704_153_600_000_000;
+
+    fn tick(secs: i64, price: f64, bid_volume: u32, ask_volume: u32) -> Tick {
+        Tick {
+            timestamp_us: START_US + secs * 1_000_000,
+            open: 0.0,
+            price,
+            bid: price - 0.25,
+            ask: price,
+            volume: bid_volume + ask_volume,
+            bid_volume,
+            ask_volume,
+            num_trades: 1,
+        }
+    }
+
+    fn ticks() -> Vec<Tick> {
+        vec![
+            tick(0, 100.0, 0, 3),
+            tick(30, 100.5, 2, 0),
+            tick(60, 0.0, 1, 0),
+            tick(61, 99.75, 4, 0),
+            tick(62, 100.25, 0, 1),
+        ]
+    }
+
+    #[test]
+    fn tick_columns_hold_one_entry_per_tick() {
+        let columns: TickColumns = ticks().into_iter().collect();
+        assert_eq!(columns.len(), 5);
+        assert_eq!(columns.timestamp[1], (START_US / 1_000_000 + 30) as f64);
+        assert_eq!(columns.price, [100.0, 100.5, 0.0, 99.75, 100.25]);
+        assert_eq!(columns.bid[0], 99.75);
+        assert_eq!(columns.volume, [3.0, 2.0, 1.0, 4.0, 1.0]);
+        assert_eq!(columns.bid_volume, [0.0, 2.0, 1.0, 4.0, 0.0]);
+        assert_eq!(columns.ask_volume, [3.0, 0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(columns.num_trades, [1.0; 5]);
+        assert_eq!(columns.trade_type, [0; 5]);
+        assert!(TickColumns::default().is_empty());
+    }
+
+    #[test]
+    fn scid_columns_copy_the_requested_records() {
+        let scid = ScidFile::from_ticks(&ticks()).unwrap();
+        let columns = scid.columns(1..4);
+        assert_eq!(columns.price, [100.5, 0.0, 99.75]);
+        assert_eq!(
+            columns.timestamp,
+            [30.0, 60.0, 61.0].map(|s| (START_US / 1_000_000) as f64 + s)
+        );
+        // Clamped to the file
+        assert_eq!(scid.columns(3..100).len(), 2);
+    }
+
+    #[test]
+    fn bar_columns_accumulate_delta() {
+        let scid = ScidFile::from_ticks(&ticks()).unwrap();
+        let (bars, _) = aggregate_bars(&scid, BarInterval::new(60), Unbundle::Ignore);
+        let columns: BarColumns = bars.iter().collect();
+        assert_eq!(columns.len(), 2);
+        assert_eq!(columns.open, [100.0, 99.75]);
+        assert_eq!(columns.high, [100.5, 100.25]);
+        assert_eq!(columns.low, [100.0, 99.75]);
+        assert_eq!(columns.close, [100.5, 100.25]);
+        assert_eq!(columns.volume, [5.0, 5.0]);
+        assert_eq!(columns.delta, [1.0, -3.0]);
+        assert_eq!(columns.cum_delta, [1.0, -2.0]);
+        assert_eq!(columns.delta_high, [3.0, -3.0]);
+        assert_eq!(columns.delta_low, [1.0, -4.0]);
+        assert_eq!(columns.tick_count, [2.0, 2.0]);
+        assert_eq!(columns.is_synthetic, [false, false]);
+    }
+}
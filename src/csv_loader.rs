@@ -0,0 +1,135 @@
+//! Tick data from CSV files, for users without Sierra Chart data.
+
+use std::path::Path;
+
+use crate::scid::Tick;
+use crate::session::parse_iso8601;
+
+/// Read one tick per CSV row from the columns named `timestamp_col`, `price_col` and
+/// `volume_col` (other columns are ignored). Timestamps are Unix seconds (fractions
+/// allowed) or ISO-8601 date-times (UTC unless they carry an offset). CSV rows carry no
+/// quote or aggressor side, so bid and ask are set to the price and the bid/ask volumes
+/// to 0. Rows are returned in file order.
+pub fn load_csv_ticks<P: AsRef<Path>>(
+    path: P,
+    timestamp_col: &str,
+    price_col: &str,
+    volume_col: &str,
+) -> Result<Vec<Tick>, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_path(path.as_ref())
+        .map_err(|e| format!("open: {e}"))?;
+    let headers = reader
+        .headers()
+        .map_err(|e| format!("CSV header: {e}"))?
+        .clone();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|h| h == name)
+            .ok_or_else(|| format!("CSV has no column named {name:?}"))
+    };
+    let (ts_idx, price_idx, volume_idx) = (
+        column(timestamp_col)?,
+        column(price_col)?,
+        column(volume_col)?,
+    );
+
+    let mut ticks = Vec::new();
+    for (i, record) in reader.records().enumerate() {
+        // Line numbers as a spreadsheet shows them, counting the header
+        let line = i + 2;
+        let record = record.map_err(|e| format!("CSV line {line}: {e}"))?;
+        let get = |idx: usize| record.get(idx).unwrap_or("");
+        let timestamp_us =
+            parse_timestamp(get(ts_idx)).map_err(|e| format!("CSV line {line}: {e}"))?;
+        let price: f64 = get(price_idx)
+            .parse()
+            .map_err(|_| format!("CSV line {line}: invalid price {:?}", get(price_idx)))?;
+        let volume: f64 = get(volume_idx)
+            .parse()
+            .map_err(|_| format!("CSV line {line}: invalid volume {:?}", get(volume_idx)))?;
+        if !(volume.is_finite() && volume >= 0.0) {
+            return Err(format!(
+                "CSV line {line}: volume must be >= 0, got {volume}"
+            ));
+        }
+        ticks.push(Tick {
+            timestamp_us,
+            open: 0.0,
+            price,
+            bid: price,
+            ask: price,
+            volume: volume.round() as u32,
+            bid_volume: 0,
+            ask_volume: 0,
+            num_trades: 1,
+        });
+    }
+    Ok(ticks)
+}
+
+/// Unix seconds if the field is a number, otherwise an ISO-8601 date-time.
+fn parse_timestamp(field: &str) -> Result<i64, String> {
+    match field.parse::<f64>() {
+        Ok(secs) if secs.is_finite() => Ok((secs * 1_000_000.0).round() as i64),
+        Ok(_) => Err(format!("invalid timestamp {field:?}")),
+        Err(_) => parse_iso8601(field),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write `contents` to a file in the temp dir unique to this test and process.
+    fn fixture(name: &str, contents: &str) -> std::path::PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("csv_loader_{}_{name}.csv", std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn reads_unix_seconds_and_iso_timestamps() {
+        let path = fixture(
+            "mixed",
+            "symbol, time, last, size\n\
+             ES, 1704153600, 4800.25, 3\n\
+             ES, 1704153600.5, 4800.5, 1\n\
+             ES, 2024-01-02T00:00:01Z, 4800.75, 2\n\
+             ES, 2024-01-01T19:00:02-05:00, 4801, 4\n",
+        );
+        let ticks = load_csv_ticks(&path, "time", "last", "size").unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let start = 1_704_153_600_000_000;
+        let got: Vec<(i64, f64, u32)> = ticks
+            .iter()
+            .map(|t| (t.timestamp_us - start, t.price, t.volume))
+            .collect();
+        assert_eq!(
+            got,
+            [
+                (0, 4800.25, 3),
+                (500_000, 4800.5, 1),
+                (1_000_000, 4800.75, 2),
+                (2_000_000, 4801.0, 4)
+            ]
+        );
+        assert!(ticks.iter().all(|t| t.bid == t.price && t.ask == t.price));
+    }
+
+    #[test]
+    fn reports_missing_columns_and_bad_rows_by_line() {
+        let path = fixture(
+            "bad",
+            "time,price,volume\n1704153600,100,1\n1704153601,abc,1\n",
+        );
+        let missing = load_csv_ticks(&path, "time", "last", "volume").unwrap_err();
+        let bad = load_csv_ticks(&path, "time", "price", "volume").unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(missing, "CSV has no column named \"last\"");
+        assert_eq!(bad, "CSV line 3: invalid price \"abc\"");
+    }
+}
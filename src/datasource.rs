@@ -0,0 +1,246 @@
+//! Random-access tick sources the bar builder and engines read from, so data can come from
+//! somewhere other than a SCID file (a CSV load, a database query, ...).
+
+use std::ops::Range;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::scid::{ScidFile, SkipStats, Tick};
+
+/// Timestamp of the first synthetic tick: 2024-01-02 00:00:00 UTC
+const SYNTHETIC_START_US: i64 = 1_704_153_600_000_000;
+
+/// A fixed sequence of ticks, addressed by index. Implementors only need `num_ticks` and
+/// `tick`; the rest have defaults built on them that a source can override when it can do
+/// better. `Sync` so the engines can read it with the GIL released.
+pub trait DataSource: Sync {
+    fn num_ticks(&self) -> usize;
+
+    /// Tick `index`, which is below `num_ticks`.
+    fn tick(&self, index: usize) -> Tick;
+
+    fn iter(&self) -> Box<dyn Iterator<Item = Tick> + '_> {
+        self.iter_range(0, self.num_ticks())
+    }
+
+    /// `iter` over ticks `start..end`, clamped to the source.
+    fn iter_range(&self, start: usize, end: usize) -> Box<dyn Iterator<Item = Tick> + '_> {
+        let end = end.min(self.num_ticks());
+        Box::new((start.min(end)..end).map(move |i| self.tick(i)))
+    }
+
+    /// Count the ticks that would be skipped as invalid.
+    fn skip_stats(&self) -> SkipStats {
+        self.skip_stats_in(0..self.num_ticks())
+    }
+
+    /// Count the ticks in `range` that would be skipped as invalid.
+    fn skip_stats_in(&self, range: Range<usize>) -> SkipStats {
+        let mut stats = SkipStats::default();
+        for tick in self.iter_range(range.start, range.end) {
+            stats.accept(&tick);
+        }
+        stats
+    }
+
+    /// Index of the first tick timestamped at or after `timestamp_us`, or `num_ticks` if
+    /// there is none. Binary search; assumes time-ordered ticks.
+    fn find_index_at_or_after(&self, timestamp_us: i64) -> usize {
+        let (mut lo, mut hi) = (0, self.num_ticks());
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if self.tick(mid).timestamp_us < timestamp_us {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        lo
+    }
+
+    /// Whether the ticks are in non-decreasing timestamp order.
+    fn is_sorted(&self) -> bool {
+        (1..self.num_ticks()).all(|i| self.tick(i - 1).timestamp_us <= self.tick(i).timestamp_us)
+    }
+
+    /// Fail with the first offending tick unless the ticks are in timestamp order.
+    fn check_order(&self) -> Result<(), String> {
+        let mut count = 0;
+        let mut first = None;
+        let mut prev = i64::MIN;
+        for (i, tick) in self.iter().enumerate() {
+            if tick.timestamp_us < prev {
+                count += 1;
+                first.get_or_insert(i);
+            }
+            prev = tick.timestamp_us;
+        }
+        match first {
+            None => Ok(()),
+            Some(first) => Err(format!(
+                "{count} records are earlier than the record before them (first at index {first})"
+            )),
+        }
+    }
+
+    /// Tick indices in stable timestamp order.
+    fn sorted_indices(&self) -> Vec<usize> {
+        let mut indices: Vec<usize> = (0..self.num_ticks()).collect();
+        if !self.is_sorted() {
+            indices.sort_by_key(|&i| self.tick(i).timestamp_us);
+        }
+        indices
+    }
+}
+
+impl DataSource for ScidFile {
+    fn num_ticks(&self) -> usize {
+        self.num_records
+    }
+
+    #[inline]
+    fn tick(&self, index: usize) -> Tick {
+        ScidFile::tick(self, index)
+    }
+
+    fn iter_range(&self, start: usize, end: usize) -> Box<dyn Iterator<Item = Tick> + '_> {
+        Box::new(ScidFile::iter_range(self, start, end))
+    }
+
+    fn skip_stats_in(&self, range: Range<usize>) -> SkipStats {
+        ScidFile::skip_stats_in(self, range)
+    }
+
+    fn find_index_at_or_after(&self, timestamp_us: i64) -> usize {
+        ScidFile::find_index_at_or_after(self, timestamp_us)
+    }
+
+    fn is_sorted(&self) -> bool {
+        ScidFile::is_sorted(self)
+    }
+
+    fn check_order(&self) -> Result<(), String> {
+        ScidFile::check_order(self)
+    }
+
+    fn sorted_indices(&self) -> Vec<usize> {
+        ScidFile::sorted_indices(self)
+    }
+}
+
+impl<T: DataSource + ?Sized> DataSource for Box<T> {
+    fn num_ticks(&self) -> usize {
+        (**self).num_ticks()
+    }
+
+    #[inline]
+    fn tick(&self, index: usize) -> Tick {
+        (**self).tick(index)
+    }
+
+    fn iter_range(&self, start: usize, end: usize) -> Box<dyn Iterator<Item = Tick> + '_> {
+        (**self).iter_range(start, end)
+    }
+
+    fn skip_stats_in(&self, range: Range<usize>) -> SkipStats {
+        (**self).skip_stats_in(range)
+    }
+
+    fn find_index_at_or_after(&self, timestamp_us: i64) -> usize {
+        (**self).find_index_at_or_after(timestamp_us)
+    }
+
+    fn is_sorted(&self) -> bool {
+        (**self).is_sorted()
+    }
+
+    fn check_order(&self) -> Result<(), String> {
+        (**self).check_order()
+    }
+
+    fn sorted_indices(&self) -> Vec<usize> {
+        (**self).sorted_indices()
+    }
+}
+
+/// Ticks already in memory, such as those from `csv_loader::load_csv_ticks`.
+impl DataSource for Vec<Tick> {
+    fn num_ticks(&self) -> usize {
+        self.len()
+    }
+
+    #[inline]
+    fn tick(&self, index: usize) -> Tick {
+        self[index]
+    }
+
+    fn iter_range(&self, start: usize, end: usize) -> Box<dyn Iterator<Item = Tick> + '_> {
+        let end = end.min(self.len());
+        Box::new(self[start.min(end)..end].iter().copied())
+    }
+}
+
+/// A tick series held in memory, for strategy tests that don't need a data file.
+#[derive(Clone, Debug, Default)]
+pub struct InMemoryDataSource {
+    pub ticks: Vec<Tick>,
+}
+
+impl InMemoryDataSource {
+    pub fn from_ticks(ticks: Vec<Tick>) -> Self {
+        InMemoryDataSource { ticks }
+    }
+
+    /// `n` ticks one second apart from 2024-01-02 00:00 UTC, following a geometric random
+    /// walk from `start_price` whose per-tick log returns have standard deviation
+    /// `volatility`. Bid and ask equal the price; each tick trades 1 to 10 contracts at
+    /// the ask on an uptick and at the bid otherwise. The same `seed` gives the same ticks.
+    pub fn from_synthetic(n: usize, start_price: f64, volatility: f64, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut price = start_price;
+        let mut ticks = Vec::with_capacity(n);
+        for i in 0..n {
+            // Box-Muller: a standard normal draw from two uniform ones
+            let (u1, u2): (f64, f64) = (1.0 - rng.gen::<f64>(), rng.gen());
+            let z = (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos();
+            let prev = price;
+            if i > 0 {
+                price *= (volatility * z).exp();
+            }
+            let volume = rng.gen_range(1..=10);
+            let uptick = price > prev;
+            ticks.push(Tick {
+                timestamp_us: SYNTHETIC_START_US + i as i64 * 1_000_000,
+                open: 0.0,
+                price,
+                bid: price,
+                ask: price,
+                volume,
+                bid_volume: if uptick { 0 } else { volume },
+                ask_volume: if uptick { volume } else { 0 },
+                num_trades: 1,
+            });
+        }
+        InMemoryDataSource { ticks }
+    }
+}
+
+impl DataSource for InMemoryDataSource {
+    fn num_ticks(&self) -> usize {
+        self.ticks.len()
+    }
+
+    #[inline]
+    fn tick(&self, index: usize) -> Tick {
+        self.ticks[index]
+    }
+
+    fn iter_range(&self, start: usize, end: usize) -> Box<dyn Iterator<Item = Tick> + '_> {
+        self.ticks.iter_range(start, end)
+    }
+
+    fn skip_stats_in(&self, range: Range<usize>) -> SkipStats {
+        self.ticks.skip_stats_in(range)
+    }
+}
@@ -0,0 +1,233 @@
+use memmap2::Mmap;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::Path;
+
+const HEADER_MAGIC: &[u8; 4] = b"SCDD";
+/// Minimum header: magic, header size, record size, version
+const MIN_HEADER_SIZE: usize = 16;
+const RECORD_SIZE: usize = 24;
+/// Microseconds between 1899-12-30 and 1970-01-01 (Unix epoch).
+const EPOCH_OFFSET_US: i64 = 2_209_161_600_000_000;
+/// Record flag marking the last update of an atomic batch
+const FLAG_END_OF_BATCH: u8 = 0x01;
+
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct RawDepthRecord {
+    pub sc_datetime: i64,
+    pub command: u8,
+    pub flags: u8,
+    pub num_orders: u16,
+    pub price: f32,
+    pub quantity: u32,
+    pub reserved: u32,
+}
+
+/// Book update carried by a depth record.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DepthCommand {
+    None,
+    ClearBook,
+    AddBid,
+    AddAsk,
+    ModifyBid,
+    ModifyAsk,
+    DeleteBid,
+    DeleteAsk,
+}
+
+impl DepthCommand {
+    fn from_u8(c: u8) -> Self {
+        match c {
+            1 => DepthCommand::ClearBook,
+            2 => DepthCommand::AddBid,
+            3 => DepthCommand::AddAsk,
+            4 => DepthCommand::ModifyBid,
+            5 => DepthCommand::ModifyAsk,
+            6 => DepthCommand::DeleteBid,
+            7 => DepthCommand::DeleteAsk,
+            _ => DepthCommand::None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct DepthRecord {
+    /// Unix timestamp in microseconds
+    pub timestamp_us: i64,
+    pub command: DepthCommand,
+    pub flags: u8,
+    pub num_orders: u16,
+    pub price: f64,
+    pub quantity: u32,
+}
+
+/// Top-of-book snapshots, one per batch of depth updates. Price and size columns are
+/// indexed `[level][snapshot]`; levels missing from the book are NaN.
+#[derive(Clone, Debug, Default)]
+pub struct DepthSnapshots {
+    pub timestamps_us: Vec<i64>,
+    pub bid_price: Vec<Vec<f64>>,
+    pub bid_size: Vec<Vec<f64>>,
+    pub ask_price: Vec<Vec<f64>>,
+    pub ask_size: Vec<Vec<f64>>,
+}
+
+impl DepthSnapshots {
+    pub fn levels(&self) -> usize {
+        self.bid_price.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.timestamps_us.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.timestamps_us.is_empty()
+    }
+
+    /// Index of the latest snapshot at or before `timestamp_us`, if any.
+    pub fn index_at(&self, timestamp_us: i64) -> Option<usize> {
+        self.timestamps_us
+            .partition_point(|&ts| ts <= timestamp_us)
+            .checked_sub(1)
+    }
+}
+
+/// Memory-mapped Sierra Chart market depth (`.depth`) file.
+pub struct DepthFile {
+    _mmap: Mmap,
+    ptr: *const u8,
+    header_size: usize,
+    pub num_records: usize,
+}
+
+// Safety: the mmap is read-only and lives as long as DepthFile
+unsafe impl Send for DepthFile {}
+unsafe impl Sync for DepthFile {}
+
+impl DepthFile {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let file = File::open(path.as_ref()).map_err(|e| format!("open: {e}"))?;
+        let mmap = unsafe { Mmap::map(&file) }.map_err(|e| format!("mmap: {e}"))?;
+
+        if mmap.len() < MIN_HEADER_SIZE {
+            return Err("File too small for depth header".into());
+        }
+        if &mmap[0..4] != HEADER_MAGIC {
+            return Err("Invalid depth file magic bytes".into());
+        }
+        let header_size = u32::from_le_bytes(mmap[4..8].try_into().unwrap()) as usize;
+        let record_size = u32::from_le_bytes(mmap[8..12].try_into().unwrap()) as usize;
+        if header_size < MIN_HEADER_SIZE || header_size > mmap.len() {
+            return Err(format!("Invalid depth header size {header_size}"));
+        }
+        if record_size != RECORD_SIZE {
+            return Err(format!(
+                "Unsupported depth record size {record_size}, expected {RECORD_SIZE}"
+            ));
+        }
+
+        // A trailing partial record (file still being written) is ignored
+        let num_records = (mmap.len() - header_size) / RECORD_SIZE;
+        let ptr = mmap.as_ptr();
+
+        Ok(DepthFile {
+            _mmap: mmap,
+            ptr,
+            header_size,
+            num_records,
+        })
+    }
+
+    #[inline]
+    pub fn raw_record(&self, index: usize) -> &RawDepthRecord {
+        debug_assert!(index < self.num_records);
+        unsafe {
+            let offset = self.header_size + index * RECORD_SIZE;
+            &*(self.ptr.add(offset) as *const RawDepthRecord)
+        }
+    }
+
+    #[inline]
+    pub fn record(&self, index: usize) -> DepthRecord {
+        let r = self.raw_record(index);
+        let sc_dt = r.sc_datetime;
+        let price = r.price;
+        let quantity = r.quantity;
+        let num_orders = r.num_orders;
+        DepthRecord {
+            timestamp_us: sc_dt - EPOCH_OFFSET_US,
+            command: DepthCommand::from_u8(r.command),
+            flags: r.flags,
+            num_orders,
+            // Same price scaling as SCID records
+            price: price as f64 / 100.0,
+            quantity,
+        }
+    }
+
+    /// Replay the book and snapshot the top `levels` on each side at the end of every
+    /// update batch (the end-of-batch flag, or a change of timestamp).
+    pub fn snapshots(&self, levels: usize) -> DepthSnapshots {
+        let mut out = DepthSnapshots {
+            bid_price: vec![Vec::new(); levels],
+            bid_size: vec![Vec::new(); levels],
+            ask_price: vec![Vec::new(); levels],
+            ask_size: vec![Vec::new(); levels],
+            ..Default::default()
+        };
+        // Keyed by the f32 bit pattern, which orders the same as the value for positive
+        // prices and avoids float keys
+        let mut bids: BTreeMap<u32, (f64, u32)> = BTreeMap::new();
+        let mut asks: BTreeMap<u32, (f64, u32)> = BTreeMap::new();
+
+        for i in 0..self.num_records {
+            let rec = self.record(i);
+            let key = self.raw_record(i).price.to_bits();
+            match rec.command {
+                DepthCommand::ClearBook => {
+                    bids.clear();
+                    asks.clear();
+                }
+                DepthCommand::AddBid | DepthCommand::ModifyBid => {
+                    bids.insert(key, (rec.price, rec.quantity));
+                }
+                DepthCommand::AddAsk | DepthCommand::ModifyAsk => {
+                    asks.insert(key, (rec.price, rec.quantity));
+                }
+                DepthCommand::DeleteBid => {
+                    bids.remove(&key);
+                }
+                DepthCommand::DeleteAsk => {
+                    asks.remove(&key);
+                }
+                DepthCommand::None => {}
+            }
+
+            let batch_end = rec.flags & FLAG_END_OF_BATCH != 0
+                || i + 1 == self.num_records
+                || self.record(i + 1).timestamp_us != rec.timestamp_us;
+            if !batch_end {
+                continue;
+            }
+            out.timestamps_us.push(rec.timestamp_us);
+            let mut best_bids = bids.values().rev();
+            let mut best_asks = asks.values();
+            for level in 0..levels {
+                let (bp, bs) = best_bids
+                    .next()
+                    .map_or((f64::NAN, f64::NAN), |&(p, q)| (p, q as f64));
+                let (ap, asz) = best_asks
+                    .next()
+                    .map_or((f64::NAN, f64::NAN), |&(p, q)| (p, q as f64));
+                out.bid_price[level].push(bp);
+                out.bid_size[level].push(bs);
+                out.ask_price[level].push(ap);
+                out.ask_size[level].push(asz);
+            }
+        }
+        out
+    }
+}
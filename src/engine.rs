@@ -1,164 +1,1314 @@
-use numpy::PyArray1;
+use numpy::{PyArray1, PyReadonlyArray1, PyUntypedArray, PyUntypedArrayMethods};
 use pyo3::prelude::*;
-use pyo3::types::PyDict;
+use pyo3::types::{PyDict, PyTuple};
+use rand::seq::SliceRandom;
+use rayon::prelude::*;
+use serde::Serialize;
 
-use crate::bar::{aggregate_bars, BarInterval};
-use crate::metrics::{compute_metrics, BacktestMetrics};
-use crate::position::PositionTracker;
-use crate::scid::ScidFile;
+use crate::bar::{bar_columns, Bar, BarOptions, BarSeries};
+use crate::columns::TickColumns;
+use crate::datasource::DataSource;
+use crate::depth::DepthSnapshots;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::metrics::{
+    calc_daily_sharpe, calc_max_drawdown, calc_rolling_sharpe, compute_metrics,
+    compute_metrics_by_tag, compute_monthly_stats, downsample_min_max, percentile,
+    score_against_benchmark, BacktestMetrics, MetricsOptions, MonthlyStats,
+};
+use crate::order::Order;
+use crate::position::{
+    EquitySampling, FillContext, PositionTracker, PyramidMode, Side, SlippageModel, Trade, HOLD,
+};
+use crate::scid::{ScidFile, SkipStats, Tick, TickOrder, Unbundle, Unbundler};
+use crate::session::SessionClock;
+use crate::signals::filter_signals;
+use crate::strategy::Strategy;
+
+/// When a bar engine signal is executed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum FillMode {
+    /// Fill at the close of the bar that produced the signal
+    #[default]
+    CurrentClose,
+    /// Fill at the open of the following bar
+    NextOpen,
+}
+
+impl std::str::FromStr for FillMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "current_close" => Ok(FillMode::CurrentClose),
+            "next_open" => Ok(FillMode::NextOpen),
+            _ => Err(format!(
+                "Unknown fill mode: {s}. Use \"current_close\" or \"next_open\""
+            )),
+        }
+    }
+}
+
+/// What delayed fills (`FillMode::NextOpen` or an execution delay) do with the last bar's
+/// signal, which has no later bar to fill at. Signals from earlier bars that would fill
+/// past the end are dropped either way.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum FinalSignal {
+    /// Ignore it
+    #[default]
+    Drop,
+    /// Execute it at the last bar's close
+    FillAtClose,
+}
+
+impl std::str::FromStr for FinalSignal {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "drop" => Ok(FinalSignal::Drop),
+            "fill_at_close" => Ok(FinalSignal::FillAtClose),
+            _ => Err(format!(
+                "Unknown final signal policy: {s}. Use \"drop\" or \"fill_at_close\""
+            )),
+        }
+    }
+}
+
+/// Which sides a strategy may trade. Signals for a disallowed side are treated as flat.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Direction {
+    #[default]
+    Both,
+    LongOnly,
+    ShortOnly,
+}
+
+impl std::str::FromStr for Direction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "both" => Ok(Direction::Both),
+            "long_only" => Ok(Direction::LongOnly),
+            "short_only" => Ok(Direction::ShortOnly),
+            _ => Err(format!(
+                "Unknown direction: {s}. Use \"both\", \"long_only\" or \"short_only\""
+            )),
+        }
+    }
+}
+
+impl Direction {
+    /// Map a signal for a side this direction doesn't allow to flat.
+    pub fn clamp(self, signal: i32) -> i32 {
+        match (self, signal) {
+            (Direction::LongOnly, -1) | (Direction::ShortOnly, 1) => 0,
+            _ => signal,
+        }
+    }
+}
+
+/// Simulation and metric settings shared by the bar and tick engines.
+#[derive(Clone, Debug)]
+pub struct BacktestConfig {
+    /// Dollars per contract per side
+    pub commission_per_side: f64,
+    /// Dollar value per 1.0 point move (ES=50, NQ=20)
+    pub point_value: f64,
+    pub metrics: MetricsOptions,
+    /// Contracts opened on each entry
+    pub quantity: f64,
+    /// Sides the strategy may open
+    pub direction: Direction,
+    pub slippage_model: SlippageModel,
+    /// Minimum price increment, the unit of tick-based slippage
+    pub tick_size: f64,
+    /// Scale-out targets as (points from entry, fraction of the quantity open when the
+    /// first target fills)
+    pub target_ladder: Vec<(f64, f64)>,
+    /// Stop-loss distance in points from the entry price
+    pub stop_loss_points: Option<f64>,
+    /// Force-close positions held longer than this many seconds
+    pub max_holding_secs: Option<i64>,
+    /// Ignore entry signals for this many seconds after a stop-loss exit
+    pub cooldown_secs: Option<i64>,
+    /// Start the cooldown after every exit, not just stop-loss exits
+    pub cooldown_after_any_exit: bool,
+    /// Local time of day (seconds after midnight) at which positions are flattened
+    pub flat_time_secs: Option<i64>,
+    /// Wall clock used for `flat_time_secs`
+    pub session_clock: SessionClock,
+    /// Dollars per contract charged for each day boundary a position is held across
+    pub daily_holding_cost: f64,
+    /// UTC hour at which the holding-cost day rolls over
+    pub rollover_hour: u32,
+    /// Fraction of notional value charged per day boundary a position is held across
+    pub daily_funding_rate: f64,
+    /// Fail if more than this fraction of records are invalid (None = never fail)
+    pub max_skipped_fraction: Option<f64>,
+    /// Leading bars the strategy sees but that are never traded or measured (bar engine
+    /// only)
+    pub warmup_bars: usize,
+    /// Re-run the strategy on every prefix of the bars and fail if any signal changes
+    /// (bar engine only)
+    pub strict_no_lookahead: bool,
+    /// Bars after an entry before a signal exit or reversal is acted on (bar engine only)
+    pub min_hold_bars: usize,
+    /// Consecutive bars a new signal must persist before it is acted on (bar engine only)
+    pub confirm_bars: usize,
+    /// Also hold a static position of this side from the first to the last traded bar
+    /// as a buy-and-hold benchmark (bar engine only)
+    pub benchmark_side: Option<Side>,
+    /// When signals fill (bar engine only)
+    pub fill_mode: FillMode,
+    /// Fill each signal at the open this many bars later (bar engine only; 0 = per
+    /// `fill_mode`, and `FillMode::NextOpen` means at least 1)
+    pub execution_delay_bars: usize,
+    /// Handling of the last bar's signal when fills are delayed to a later open
+    pub final_signal: FinalSignal,
+    /// Lots a position may pyramid to; a repeated entry signal adds one (1 = no pyramiding)
+    pub max_positions: usize,
+    /// Whether pyramided adds stay separate lots or average into one
+    pub pyramid_mode: PyramidMode,
+    /// Which equity points are kept on the equity curve
+    pub equity_sampling: EquitySampling,
+    /// How unbundled-trade records count towards the tick engine's volumes (bar engines
+    /// use `BarOptions::unbundle`)
+    pub unbundle: Unbundle,
+}
+
+impl Default for BacktestConfig {
+    fn default() -> Self {
+        BacktestConfig {
+            commission_per_side: 0.0,
+            point_value: 50.0,
+            metrics: MetricsOptions::default(),
+            quantity: 1.0,
+            direction: Direction::default(),
+            slippage_model: SlippageModel::default(),
+            tick_size: 0.25,
+            target_ladder: Vec::new(),
+            stop_loss_points: None,
+            max_holding_secs: None,
+            cooldown_secs: None,
+            cooldown_after_any_exit: false,
+            flat_time_secs: None,
+            session_clock: SessionClock::default(),
+            daily_holding_cost: 0.0,
+            rollover_hour: 0,
+            daily_funding_rate: 0.0,
+            max_skipped_fraction: None,
+            warmup_bars: 0,
+            strict_no_lookahead: false,
+            min_hold_bars: 0,
+            confirm_bars: 1,
+            benchmark_side: None,
+            fill_mode: FillMode::default(),
+            execution_delay_bars: 0,
+            final_signal: FinalSignal::default(),
+            max_positions: 1,
+            pyramid_mode: PyramidMode::default(),
+            equity_sampling: EquitySampling::default(),
+            unbundle: Unbundle::Ignore,
+        }
+    }
+}
+
+impl BacktestConfig {
+    /// Bars between a signal and its fill at the open (0 = fill at the signal bar's close).
+    pub fn fill_delay(&self) -> usize {
+        match self.fill_mode {
+            FillMode::CurrentClose => self.execution_delay_bars,
+            FillMode::NextOpen => self.execution_delay_bars.max(1),
+        }
+    }
+
+    /// Build a position tracker with this configuration applied.
+    pub fn tracker(&self) -> PyResult<PositionTracker> {
+        if self.metrics.initial_capital <= 0.0 {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "initial_capital must be positive",
+            ));
+        }
+        if self.metrics.rolling_window < 2 {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "rolling_window must be at least 2",
+            ));
+        }
+        if self.metrics.annual_factor <= 0.0 {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "sharpe_annual_factor must be positive",
+            ));
+        }
+        if self.quantity <= 0.0 {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "quantity must be positive",
+            ));
+        }
+        if self.max_positions == 0 {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "pyramiding must be at least 1",
+            ));
+        }
+        let mut tracker = PositionTracker::new_with_config(
+            self.commission_per_side,
+            self.point_value,
+            self.max_positions,
+        );
+        tracker.pyramid_mode = self.pyramid_mode;
+        tracker.equity_sampling = self.equity_sampling;
+        if self.rollover_hour > 23 {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "rollover_hour must be in 0..=23",
+            ));
+        }
+        tracker.quantity = self.quantity;
+        tracker.initial_capital = self.metrics.initial_capital;
+        if self.tick_size <= 0.0 {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "tick_size must be positive",
+            ));
+        }
+        tracker.slippage_model = self.slippage_model;
+        tracker.tick_size = self.tick_size;
+        if self.stop_loss_points.is_some_and(|stop| stop <= 0.0) {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "stop_loss must be positive",
+            ));
+        }
+        tracker.stop_loss_points = self.stop_loss_points;
+        tracker.max_holding_secs = self.max_holding_secs;
+        if self.cooldown_secs.is_some_and(|secs| secs < 0) {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "cooldown_secs must not be negative",
+            ));
+        }
+        tracker.cooldown_secs = self.cooldown_secs;
+        tracker.cooldown_after_any_exit = self.cooldown_after_any_exit;
+        tracker.flat_time_secs = self.flat_time_secs;
+        tracker.session_clock = self.session_clock;
+        tracker.daily_holding_cost = self.daily_holding_cost;
+        tracker.rollover_hour = self.rollover_hour;
+        tracker.daily_funding_rate = self.daily_funding_rate;
+        tracker
+            .set_target_ladder(self.target_ladder.clone())
+            .map_err(pyo3::exceptions::PyValueError::new_err)?;
+        Ok(tracker)
+    }
+}
+
+/// Raise ValueError if more than `limit` of `num_records` were skipped (None = no limit).
+pub fn check_skipped(skipped: &SkipStats, num_records: usize, limit: Option<f64>) -> PyResult<()> {
+    match limit {
+        Some(max_fraction) => skipped
+            .check(num_records, max_fraction)
+            .map_err(pyo3::exceptions::PyValueError::new_err),
+        None => Ok(()),
+    }
+}
 
 /// Run a bar-based backtest. The Python callback receives dict-of-arrays for all bars
 /// up to the current index and returns a signal (1=long, -1=short, 0=flat).
+///
+/// `bar_options.bar_type` only changes the bars the strategy sees; fills always use the
+/// real prices. `path` is the file `source` was read from, needed for the bar cache.
 pub fn run_bar_backtest(
     py: Python<'_>,
-    path: &str,
-    interval: &str,
+    source: &dyn DataSource,
+    path: Option<&Path>,
+    bar_options: &BarOptions,
     callback: &Bound<'_, PyAny>,
-    commission: f64,
-    point_value: f64,
+    config: &BacktestConfig,
 ) -> PyResult<BacktestResults> {
-    let scid = ScidFile::open(path).map_err(|e| pyo3::exceptions::PyIOError::new_err(e))?;
-    let bar_interval =
-        BarInterval::from_str(interval).map_err(|e| pyo3::exceptions::PyValueError::new_err(e))?;
+    let BarSeries {
+        bars,
+        skipped,
+        truncated,
+        ..
+    } = source_bars(py, source, path, bar_options, config)?;
+    let strategy_bars = bar_options.bar_type.apply(&bars);
+
+    // Call the strategy once with all bars — strategy returns signal array
+    let result = callback.call1((bar_dict(py, &strategy_bars)?,))?;
+    let (signals, tags) = extract_signals(&result)?;
+    check_signal_count(signals.len(), bars.len())?;
+    check_warmup(config.warmup_bars, bars.len())?;
+    if config.strict_no_lookahead {
+        check_lookahead(py, callback, &strategy_bars, &signals)?;
+    }
+
+    let mut tracker = config.tracker()?;
+    simulate_bars(&mut tracker, &bars, &signals, tags.as_deref(), config);
+    let mut results = finish(tracker, config, skipped);
+    results.bars_truncated = truncated;
+    if let Some(side) = config.benchmark_side {
+        add_buy_and_hold(&mut results, &bars[config.warmup_bars..], side, config);
+    }
+    Ok(results)
+}
 
-    let bars = aggregate_bars(&scid, bar_interval);
+/// Run a bar backtest with a Rust `strategy`, called once per bar as it closes, without
+/// touching the Python interpreter, so it can run in parallel sweeps and plain Rust
+/// tests. Bars are built from `source` as in `run_bar_backtest`; no unfilled-gap warning
+/// is raised.
+pub fn run_rust_backtest(
+    source: &dyn DataSource,
+    path: Option<&Path>,
+    bar_options: &BarOptions,
+    strategy: &mut dyn Strategy,
+    config: &BacktestConfig,
+) -> PyResult<BacktestResults> {
+    let BarSeries {
+        bars,
+        skipped,
+        num_records,
+        truncated,
+        ..
+    } = bar_options
+        .build(source, path)
+        .map_err(pyo3::exceptions::PyIOError::new_err)?;
+    check_skipped(&skipped, num_records, config.max_skipped_fraction)?;
     if bars.is_empty() {
         return Err(pyo3::exceptions::PyValueError::new_err("No bars generated"));
     }
+    check_warmup(config.warmup_bars, bars.len())?;
+    let strategy_bars = bar_options.bar_type.apply(&bars);
+    let signals: Vec<i32> = (0..bars.len())
+        .map(|i| strategy.on_bar(&strategy_bars[..=i], i))
+        .collect();
 
-    // Pre-allocate arrays
-    let n = bars.len();
-    let mut timestamps = Vec::with_capacity(n);
-    let mut opens = Vec::with_capacity(n);
-    let mut highs = Vec::with_capacity(n);
-    let mut lows = Vec::with_capacity(n);
-    let mut closes = Vec::with_capacity(n);
-    let mut volumes = Vec::with_capacity(n);
-    let mut bid_vols = Vec::with_capacity(n);
-    let mut ask_vols = Vec::with_capacity(n);
-
-    for bar in &bars {
-        timestamps.push(bar.timestamp_us as f64 / 1_000_000.0); // Unix seconds
-        opens.push(bar.open);
-        highs.push(bar.high);
-        lows.push(bar.low);
-        closes.push(bar.close);
-        volumes.push(bar.volume as f64);
-        bid_vols.push(bar.bid_volume as f64);
-        ask_vols.push(bar.ask_volume as f64);
-    }
-
-    // Convert to numpy arrays
-    let ts_arr = PyArray1::from_vec(py, timestamps);
-    let open_arr = PyArray1::from_vec(py, opens);
-    let high_arr = PyArray1::from_vec(py, highs);
-    let low_arr = PyArray1::from_vec(py, lows);
-    let close_arr = PyArray1::from_vec(py, closes);
-    let vol_arr = PyArray1::from_vec(py, volumes);
-    let bid_arr = PyArray1::from_vec(py, bid_vols);
-    let ask_arr = PyArray1::from_vec(py, ask_vols);
-
-    // Build a dict of arrays
-    let bar_data = PyDict::new(py);
-    bar_data.set_item("timestamp", ts_arr)?;
-    bar_data.set_item("open", open_arr)?;
-    bar_data.set_item("high", high_arr)?;
-    bar_data.set_item("low", low_arr)?;
-    bar_data.set_item("close", close_arr)?;
-    bar_data.set_item("volume", vol_arr)?;
-    bar_data.set_item("bid_volume", bid_arr)?;
-    bar_data.set_item("ask_volume", ask_arr)?;
-    bar_data.set_item("num_bars", n)?;
+    let mut tracker = config.tracker()?;
+    simulate_bars(
+        &mut tracker,
+        &bars,
+        &SignalArray::List(signals),
+        None,
+        config,
+    );
+    let mut results = finish(tracker, config, skipped);
+    results.bars_truncated = truncated;
+    if let Some(side) = config.benchmark_side {
+        add_buy_and_hold(&mut results, &bars[config.warmup_bars..], side, config);
+    }
+    Ok(results)
+}
 
-    // Call the strategy once with all bars — strategy returns signal array
-    let result = callback.call1((bar_data,))?;
-    let signals: Vec<i32> = result.extract()?;
+/// Fill in the buy-and-hold benchmark equity and metrics for holding `config.quantity`
+/// contracts of `side` over `bars`, less one entry commission and without slippage, and
+/// score the strategy's beta, alpha, information and Treynor ratios against it.
+fn add_buy_and_hold(
+    results: &mut BacktestResults,
+    bars: &[Bar],
+    side: Side,
+    config: &BacktestConfig,
+) {
+    let sign = if side == Side::Short { -1.0 } else { 1.0 };
+    let capital = config.metrics.initial_capital - config.commission_per_side * config.quantity;
+    let equity: Vec<f64> = buy_and_hold_equity(
+        bars,
+        &results.equity_timestamps,
+        sign * config.point_value * config.quantity,
+    )
+    .into_iter()
+    .map(|pnl| capital + pnl)
+    .collect();
+    let metrics = &mut results.metrics;
+    let initial_capital = config.metrics.initial_capital;
+    metrics.benchmark_total_pnl = Some(equity.last().map_or(0.0, |eq| eq - initial_capital));
+    metrics.benchmark_sharpe = Some(calc_daily_sharpe(
+        &equity,
+        &results.equity_timestamps,
+        initial_capital,
+        config.metrics.annual_factor,
+    ));
+    metrics.benchmark_max_drawdown = Some(calc_max_drawdown(&equity).0);
+    score_against_benchmark(
+        metrics,
+        &results.equity_curve,
+        &equity,
+        &results.equity_timestamps,
+        &config.metrics,
+    );
+    results.benchmark_pnl = Some(equity);
+}
 
-    if signals.len() != n {
-        return Err(pyo3::exceptions::PyValueError::new_err(format!(
-            "Signal array length {} != bar count {}",
-            signals.len(),
-            n
-        )));
+/// Run a bar-based backtest calling the strategy once per bar, as each bar closes. The
+/// callback receives the bars up to and including the current one (only the last
+/// `lookback` of them, if set) and returns a single signal for that bar, so it cannot
+/// see future bars.
+pub fn run_incremental_bar_backtest(
+    py: Python<'_>,
+    path: &str,
+    bar_options: &BarOptions,
+    callback: &Bound<'_, PyAny>,
+    lookback: Option<usize>,
+    config: &BacktestConfig,
+) -> PyResult<BacktestResults> {
+    if lookback == Some(0) {
+        return Err(pyo3::exceptions::PyValueError::new_err(
+            "lookback must be positive",
+        ));
     }
+    let BarSeries { bars, skipped, .. } = build_bars(py, path, bar_options, config)?;
+    let strategy_bars = bar_options.bar_type.apply(&bars);
 
-    // Simulate
-    let mut tracker = PositionTracker::new(commission, point_value);
+    let mut signals = Vec::with_capacity(bars.len());
+    for i in 0..bars.len() {
+        let start = lookback.map_or(0, |n| (i + 1).saturating_sub(n));
+        let window = bar_dict(py, &strategy_bars[start..=i])?;
+        let signal: i32 = callback.call1((window,))?.extract().map_err(|_| {
+            pyo3::exceptions::PyTypeError::new_err(format!(
+                "Incremental callback must return a single int signal (bar {i})"
+            ))
+        })?;
+        signals.push(signal);
+    }
+
+    let mut tracker = config.tracker()?;
+    simulate_bars(
+        &mut tracker,
+        &bars,
+        &SignalArray::List(signals),
+        None,
+        config,
+    );
+    Ok(finish(tracker, config, skipped))
+}
+
+/// Run an event-driven bar backtest. After each bar closes the callback receives the bars
+/// so far, with `position` (signed open contracts) and `entry_price` (NaN when flat)
+/// added, and returns a list of orders for the next bar:
+/// `("market", side, qty)`, `("limit", side, qty, price)` or `("stop", side, qty, price)`
+/// with side "buy" or "sell". Orders fill against that bar per `Order::fill_price`, in
+/// the order given; unfilled orders are cancelled. Any open position is closed at the
+/// last bar's close.
+pub fn run_event_backtest(
+    py: Python<'_>,
+    path: &str,
+    bar_options: &BarOptions,
+    callback: &Bound<'_, PyAny>,
+    config: &BacktestConfig,
+) -> PyResult<BacktestResults> {
+    let BarSeries { bars, skipped, .. } = build_bars(py, path, bar_options, config)?;
+    let strategy_bars = bar_options.bar_type.apply(&bars);
+
+    let mut tracker = config.tracker()?;
+    let mut pending: Vec<Order> = Vec::new();
     for (i, bar) in bars.iter().enumerate() {
-        tracker.process_signal(signals[i], bar.close, bar.timestamp_us);
+        let ctx = bar_context(bar);
+        tracker.bar_index = i;
+        for order in pending.drain(..) {
+            if let Some(price) = order.fill_price(bar) {
+                tracker.fill_order(
+                    order.side(),
+                    order.qty() as f64,
+                    price,
+                    bar.timestamp_us,
+                    &ctx,
+                );
+            }
+        }
+        tracker.mark(bar.close, bar.timestamp_us);
+
+        if i + 1 < bars.len() {
+            let bar_data = bar_dict(py, &strategy_bars[..=i])?;
+            let position = match tracker.side {
+                Side::Long => tracker.open_quantity,
+                Side::Short => -tracker.open_quantity,
+                Side::Flat => 0.0,
+            };
+            bar_data.set_item("position", position)?;
+            bar_data.set_item(
+                "entry_price",
+                match tracker.side {
+                    Side::Flat => f64::NAN,
+                    _ => tracker.entry_price,
+                },
+            )?;
+            pending = extract_orders(&callback.call1((bar_data,))?)?;
+        }
+    }
+    let last = &bars[bars.len() - 1];
+    tracker.close_position(last.close, last.timestamp_us, &bar_context(last));
+    Ok(finish(tracker, config, skipped))
+}
+
+/// Parse the list of order tuples returned by an event strategy.
+fn extract_orders(obj: &Bound<'_, PyAny>) -> PyResult<Vec<Order>> {
+    if obj.is_none() {
+        return Ok(Vec::new());
+    }
+    let mut orders = Vec::new();
+    for item in obj.try_iter()? {
+        let item = item?;
+        let tuple = item.cast::<PyTuple>().map_err(|_| {
+            pyo3::exceptions::PyTypeError::new_err(format!(
+                "Orders must be tuples like (\"limit\", \"buy\", qty, price), got {}",
+                item.get_type()
+            ))
+        })?;
+        if !(3..=4).contains(&tuple.len()) {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "Order tuple must have 3 or 4 items, got {}",
+                tuple.len()
+            )));
+        }
+        let kind: String = tuple.get_item(0)?.extract()?;
+        let side: String = tuple.get_item(1)?.extract()?;
+        let qty: u32 = tuple.get_item(2)?.extract()?;
+        let price: Option<f64> = match tuple.len() {
+            4 => Some(tuple.get_item(3)?.extract()?),
+            _ => None,
+        };
+        orders.push(
+            Order::from_parts(&kind, &side, qty, price)
+                .map_err(pyo3::exceptions::PyValueError::new_err)?,
+        );
     }
-    // Close any open position at end
-    let last = bars.last().unwrap();
-    tracker.close_position(last.close, last.timestamp_us);
+    Ok(orders)
+}
+
+/// Walk-forward analysis. The bars are split into an in-sample window of
+/// `in_sample_bars` followed by an out-of-sample window of `out_sample_bars`, rolling
+/// forward by `out_sample_bars` so in-sample windows overlap. For each window
+/// `optimize_callback(in_sample)` returns parameters and
+/// `evaluate_callback(out_of_sample, params)` returns signals for the out-of-sample bars.
+///
+/// The out-of-sample segments run through one tracker, flattened at the end of each
+/// segment, so the combined result is the stitched out-of-sample track record. A final
+/// partial out-of-sample window is evaluated if it has at least `min_oos_bars` bars
+/// (default: a full window).
+#[allow(clippy::too_many_arguments)]
+pub fn run_walk_forward(
+    py: Python<'_>,
+    path: &str,
+    bar_options: &BarOptions,
+    optimize_callback: &Bound<'_, PyAny>,
+    evaluate_callback: &Bound<'_, PyAny>,
+    in_sample_bars: usize,
+    out_sample_bars: usize,
+    min_oos_bars: Option<usize>,
+    config: &BacktestConfig,
+) -> PyResult<WalkForwardResults> {
+    if in_sample_bars == 0 || out_sample_bars == 0 {
+        return Err(pyo3::exceptions::PyValueError::new_err(
+            "in_sample_bars and out_sample_bars must be positive",
+        ));
+    }
+    let min_oos_bars = min_oos_bars.unwrap_or(out_sample_bars);
+    if min_oos_bars == 0 || min_oos_bars > out_sample_bars {
+        return Err(pyo3::exceptions::PyValueError::new_err(
+            "min_oos_bars must be in 1..=out_sample_bars",
+        ));
+    }
+    let BarSeries { bars, skipped, .. } = build_bars(py, path, bar_options, config)?;
+    let strategy_bars = bar_options.bar_type.apply(&bars);
 
-    let metrics = compute_metrics(&tracker.trades, &tracker.equity_curve);
+    let mut tracker = config.tracker()?;
+    let mut windows = Vec::new();
+    let mut start = 0;
+    while start + in_sample_bars + min_oos_bars <= bars.len() {
+        let oos_start = start + in_sample_bars;
+        let oos = oos_start..(oos_start + out_sample_bars).min(bars.len());
+        let in_sample = bar_dict(py, &strategy_bars[start..oos_start])?;
+        let params = optimize_callback.call1((in_sample,))?;
 
-    Ok(BacktestResults {
-        metrics,
-        trades: tracker.trades,
-        equity_curve: tracker.equity_curve,
+        let out_of_sample = bar_dict(py, &strategy_bars[oos.clone()])?;
+        let result = evaluate_callback.call1((out_of_sample, params))?;
+        let (signals, tags) = extract_signals(&result)?;
+        check_signal_count(signals.len(), oos.len())?;
+
+        let first_trade = tracker.trades.len();
+        let first_point = tracker.equity_curve.len();
+        let baseline = tracker.running_pnl;
+        // simulate_bars closes the position at the segment's last bar
+        simulate_bars(
+            &mut tracker,
+            &bars[oos.clone()],
+            &signals,
+            tags.as_deref(),
+            config,
+        );
+        // A forced exit in this segment must not block entries in the next one
+        tracker.blocked_side = Side::Flat;
+        windows.push(WalkForwardWindow {
+            in_sample_start_us: bars[start].timestamp_us,
+            out_sample_start_us: bars[oos.start].timestamp_us,
+            out_sample_end_us: bars[oos.end - 1].timestamp_us,
+            num_bars: oos.len(),
+            results: build_results(
+                tracker.trades[first_trade..].to_vec(),
+                tracker.equity_curve[first_point..]
+                    .iter()
+                    .map(|eq| eq - baseline)
+                    .collect(),
+                tracker.equity_timestamps[first_point..].to_vec(),
+                config,
+                skipped,
+            ),
+        });
+        start += out_sample_bars;
+    }
+    if windows.is_empty() {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "{} bars is too few for one {in_sample_bars} + {min_oos_bars} bar window",
+            bars.len()
+        )));
+    }
+    Ok(WalkForwardResults {
+        combined: finish(tracker, config, skipped),
+        windows,
     })
 }
 
-/// Run a tick-based backtest. Sends batches of ticks to the callback.
-pub fn run_tick_backtest(
+/// Rolling-window backtest: the strategy is run independently on windows of
+/// `window_bars` bars starting every `step_bars` bars, each with a fresh tracker, and one
+/// result is returned per window. Only full windows are run.
+pub fn run_rolling_backtest(
     py: Python<'_>,
     path: &str,
-    batch_size: usize,
+    bar_options: &BarOptions,
+    callback: &Bound<'_, PyAny>,
+    window_bars: usize,
+    step_bars: usize,
+    config: &BacktestConfig,
+) -> PyResult<Vec<BacktestResults>> {
+    if window_bars == 0 || step_bars == 0 {
+        return Err(pyo3::exceptions::PyValueError::new_err(
+            "window_bars and step_bars must be positive",
+        ));
+    }
+    let BarSeries { bars, skipped, .. } = build_bars(py, path, bar_options, config)?;
+    if window_bars > bars.len() {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "{} bars is too few for one {window_bars} bar window",
+            bars.len()
+        )));
+    }
+    check_warmup(config.warmup_bars, window_bars)?;
+    let strategy_bars = bar_options.bar_type.apply(&bars);
+
+    let mut windows = Vec::new();
+    for start in (0..=bars.len() - window_bars).step_by(step_bars) {
+        let window = start..start + window_bars;
+        let result = callback.call1((bar_dict(py, &strategy_bars[window.clone()])?,))?;
+        let (signals, tags) = extract_signals(&result)?;
+        check_signal_count(signals.len(), window_bars)?;
+
+        let mut tracker = config.tracker()?;
+        simulate_bars(
+            &mut tracker,
+            &bars[window],
+            &signals,
+            tags.as_deref(),
+            config,
+        );
+        windows.push(finish(tracker, config, skipped));
+    }
+    Ok(windows)
+}
+
+/// Parameter sweep. For each dict in `param_grid`, `callback_factory(params)` builds a
+/// strategy (a callable, or an object with `on_bars`) that is called once with all bars.
+/// The bars are built once and the strategies called in turn under the GIL; the
+/// simulations then run in parallel on the rayon pool with the GIL released. Returns
+/// `(params, results)` pairs sorted by `sharpe_ratio`, best first.
+pub fn run_param_sweep<'py>(
+    py: Python<'py>,
+    path: &str,
+    bar_options: &BarOptions,
+    callback_factory: &Bound<'py, PyAny>,
+    param_grid: Vec<Bound<'py, PyDict>>,
+    config: &BacktestConfig,
+) -> PyResult<Vec<(Bound<'py, PyDict>, BacktestResults)>> {
+    let BarSeries { bars, skipped, .. } = build_bars(py, path, bar_options, config)?;
+    check_warmup(config.warmup_bars, bars.len())?;
+    let strategy_bars = bar_options.bar_type.apply(&bars);
+
+    let mut runs = Vec::with_capacity(param_grid.len());
+    for params in &param_grid {
+        let strategy = callback_factory.call1((params,))?;
+        let callback = match strategy.getattr("on_bars") {
+            Ok(on_bars) => on_bars,
+            Err(_) => strategy,
+        };
+        let result = callback.call1((bar_dict(py, &strategy_bars)?,))?;
+        let (signals, tags) = extract_signals(&result)?;
+        check_signal_count(signals.len(), bars.len())?;
+        let signals: Vec<i32> = (0..signals.len()).map(|i| signals.get(i)).collect();
+        runs.push((signals, tags));
+    }
+
+    let results = py.detach(|| {
+        runs.into_par_iter()
+            .map(|(signals, tags)| {
+                let mut tracker = config.tracker()?;
+                simulate_bars(
+                    &mut tracker,
+                    &bars,
+                    &SignalArray::List(signals),
+                    tags.as_deref(),
+                    config,
+                );
+                Ok(finish(tracker, config, skipped))
+            })
+            .collect::<PyResult<Vec<BacktestResults>>>()
+    })?;
+
+    let mut sweep: Vec<_> = param_grid.into_iter().zip(results).collect();
+    sweep.sort_by(|(_, a), (_, b)| {
+        let sharpe = |r: &BacktestResults| match r.metrics.sharpe_ratio {
+            s if s.is_nan() => f64::NEG_INFINITY,
+            s => s,
+        };
+        sharpe(b).total_cmp(&sharpe(a))
+    });
+    Ok(sweep)
+}
+
+/// Open the SCID file at `path` without holding the GIL.
+pub fn open_scid(py: Python<'_>, path: &str) -> PyResult<ScidFile> {
+    py.detach(|| ScidFile::open(path))
+        .map_err(pyo3::exceptions::PyIOError::new_err)
+}
+
+/// Open `path`, aggregate it per `bar_options` and apply the skipped-record check.
+fn build_bars(
+    py: Python<'_>,
+    path: &str,
+    bar_options: &BarOptions,
+    config: &BacktestConfig,
+) -> PyResult<BarSeries> {
+    let scid = open_scid(py, path)?;
+    source_bars(py, &scid, Some(Path::new(path)), bar_options, config)
+}
+
+/// `build_bars` over an already open `source`, read from the file at `path` if any.
+fn source_bars(
+    py: Python<'_>,
+    source: &dyn DataSource,
+    path: Option<&Path>,
+    bar_options: &BarOptions,
+    config: &BacktestConfig,
+) -> PyResult<BarSeries> {
+    // The scan and aggregation don't touch Python objects, so other threads can run
+    let series = py
+        .detach(|| bar_options.build(source, path))
+        .map_err(pyo3::exceptions::PyIOError::new_err)?;
+    check_skipped(
+        &series.skipped,
+        series.num_records,
+        config.max_skipped_fraction,
+    )?;
+    warn_unfilled_gaps(py, &series, bar_options.max_gap_bars)?;
+    if series.bars.is_empty() {
+        return Err(pyo3::exceptions::PyValueError::new_err("No bars generated"));
+    }
+    Ok(series)
+}
+
+/// Emit a UserWarning when gaps were too long for `fill_gaps` to fill.
+pub fn warn_unfilled_gaps(py: Python<'_>, series: &BarSeries, max_gap_bars: usize) -> PyResult<()> {
+    if series.unfilled_gaps == 0 {
+        return Ok(());
+    }
+    let msg = format!(
+        "{} gaps longer than max_gap_bars={max_gap_bars} bars were left unfilled",
+        series.unfilled_gaps
+    );
+    let msg = std::ffi::CString::new(msg).expect("message has no NUL bytes");
+    PyErr::warn(
+        py,
+        &py.get_type::<pyo3::exceptions::PyUserWarning>(),
+        &msg,
+        1,
+    )
+}
+
+/// The dict of numpy arrays a bar strategy receives.
+fn bar_dict<'py>(py: Python<'py>, bars: &[Bar]) -> PyResult<Bound<'py, PyDict>> {
+    py.detach(|| bar_columns(bars)).into_pydict(py)
+}
+
+fn check_warmup(warmup_bars: usize, bars: usize) -> PyResult<()> {
+    if warmup_bars >= bars {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "warmup_bars {warmup_bars} leaves no bars to trade out of {bars}"
+        )));
+    }
+    Ok(())
+}
+
+/// Call the strategy again with `bars[..=i]` for every `i` and check that the last signal
+/// matches the batch signal at `i`. A mismatch means the batch signal depended on later
+/// bars. Calls the strategy once per bar on a growing slice, so it is O(n^2).
+fn check_lookahead(
+    py: Python<'_>,
+    callback: &Bound<'_, PyAny>,
+    bars: &[Bar],
+    signals: &SignalArray<'_>,
+) -> PyResult<()> {
+    for i in 0..bars.len() {
+        let result = callback.call1((bar_dict(py, &bars[..=i])?,))?;
+        let (prefix, _) = extract_signals(&result)?;
+        check_signal_count(prefix.len(), i + 1)?;
+        let (batch, incremental) = (signals.get(i), prefix.get(i));
+        if batch != incremental {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "Look-ahead detected at bar {i} (timestamp {}): signal is {batch} with all \
+                 bars but {incremental} with bars up to this one",
+                bars[i].timestamp_us as f64 / 1_000_000.0
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn check_signal_count(signals: usize, bars: usize) -> PyResult<()> {
+    if signals != bars {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "Signal array length {signals} != bar count {bars}"
+        )));
+    }
+    Ok(())
+}
+
+/// Feed one signal per bar through the tracker per `config.fill_delay()`, then close any
+/// open position at the last bar's close. The first `config.warmup_bars` bars are
+/// skipped, and signals pass through `filter_signals` when `config.min_hold_bars` or
+/// `config.confirm_bars` is set. `bars` must not be empty.
+fn simulate_bars(
+    tracker: &mut PositionTracker,
+    bars: &[Bar],
+    signals: &SignalArray<'_>,
+    tags: Option<&[i32]>,
+    config: &BacktestConfig,
+) {
+    let n = bars.len();
+    let warmup = config.warmup_bars;
+    let tag_at = |i: usize| tags.map_or(0, |tags| tags[i]);
+    let signals = effective_signals(signals, n, config);
+    let signal_at = |i: usize| signals[i];
+    // Warm-up bars are not simulated at all, so they add no equity points to the metrics
+    match config.fill_delay() {
+        0 => {
+            for (i, bar) in bars.iter().enumerate().skip(warmup) {
+                tracker.bar_index = i;
+                tracker.signal_tag = tag_at(i);
+                tracker.process_signal(
+                    signal_at(i),
+                    bar.close,
+                    bar.timestamp_us,
+                    &bar_context(bar),
+                );
+            }
+        }
+        delay => {
+            // Bar i executes the signal from bar i - delay at its open; the first bars have
+            // nothing to fill
+            for (i, bar) in bars.iter().enumerate().skip(warmup) {
+                let signal = match i.checked_sub(delay) {
+                    Some(from) => signal_at(from),
+                    None => 0,
+                };
+                tracker.bar_index = i;
+                tracker.signal_tag = tag_at(i.saturating_sub(delay));
+                tracker.process_signal(signal, bar.open, bar.timestamp_us, &bar_context(bar));
+            }
+            if config.final_signal == FinalSignal::FillAtClose {
+                let last = &bars[n - 1];
+                tracker.signal_tag = tag_at(n - 1);
+                tracker.process_signal(
+                    signal_at(n - 1),
+                    last.close,
+                    last.timestamp_us,
+                    &bar_context(last),
+                );
+            }
+        }
+    }
+    // Close any open position at end, whatever the minimum hold
+    let last = &bars[n - 1];
+    tracker.bar_index = n - 1;
+    tracker.close_position(last.close, last.timestamp_us, &bar_context(last));
+}
+
+/// The signals the engine acts on: flat through the warm-up, clamped to the allowed
+/// direction, then debounced and hold-filtered if configured.
+fn effective_signals(signals: &SignalArray<'_>, n: usize, config: &BacktestConfig) -> Vec<i32> {
+    let raw: Vec<i32> = (0..n)
+        .map(|i| {
+            if i < config.warmup_bars {
+                0
+            } else {
+                config.direction.clamp(signals.get(i))
+            }
+        })
+        .collect();
+    if config.min_hold_bars > 0 || config.confirm_bars > 1 {
+        filter_signals(&raw, config.min_hold_bars, config.confirm_bars)
+    } else {
+        raw
+    }
+}
+
+/// Run a bar backtest with tick-accurate fills. The strategy is called once with all bars,
+/// as in `run_bar_backtest`, but the signal of bar i fills at the first tick of bar
+/// i + delay (`fill_delay`, at least 1) instead of a bar price, and bars holding a position
+/// are replayed tick by tick whenever a stop, target or time exit could trigger within
+/// them. Bars with nothing to do cost no tick reads. Equity is marked at each bar's close.
+/// Bars are replayed from their SCID record ranges, so `TickOrder::Sort` is rejected: a
+/// sorted bar's ticks need not be contiguous in the file.
+pub fn run_hybrid_backtest(
+    py: Python<'_>,
+    path: &str,
+    bar_options: &BarOptions,
+    callback: &Bound<'_, PyAny>,
+    config: &BacktestConfig,
+) -> PyResult<BacktestResults> {
+    if bar_options.order == TickOrder::Sort {
+        return Err(pyo3::exceptions::PyValueError::new_err(
+            "The hybrid engine replays ticks in file order and does not support sorting",
+        ));
+    }
+    let BarSeries {
+        bars,
+        skipped,
+        truncated,
+        ..
+    } = build_bars(py, path, bar_options, config)?;
+    let strategy_bars = bar_options.bar_type.apply(&bars);
+
+    let result = callback.call1((bar_dict(py, &strategy_bars)?,))?;
+    let (signals, tags) = extract_signals(&result)?;
+    check_signal_count(signals.len(), bars.len())?;
+    check_warmup(config.warmup_bars, bars.len())?;
+
+    let scid = open_scid(py, path)?;
+    let mut tracker = config.tracker()?;
+    simulate_hybrid(
+        &mut tracker,
+        &scid,
+        &bars,
+        &signals,
+        tags.as_deref(),
+        config,
+    );
+    let mut results = finish(tracker, config, skipped);
+    results.bars_truncated = truncated;
+    Ok(results)
+}
+
+/// Drive the tracker through `bars`, reading a bar's ticks (via its tick index range)
+/// only when its signal would change the position or an engine exit could trigger in it.
+fn simulate_hybrid(
+    tracker: &mut PositionTracker,
+    scid: &ScidFile,
+    bars: &[Bar],
+    signals: &SignalArray<'_>,
+    tags: Option<&[i32]>,
+    config: &BacktestConfig,
+) {
+    let n = bars.len();
+    let tag_at = |i: usize| tags.map_or(0, |tags| tags[i]);
+    let signals = effective_signals(signals, n, config);
+    let delay = config.fill_delay().max(1);
+    for (i, bar) in bars.iter().enumerate().skip(config.warmup_bars) {
+        tracker.bar_index = i;
+        tracker.signal_tag = tag_at(i.saturating_sub(delay));
+        let signal = i.checked_sub(delay).map_or(0, |from| signals[from]);
+        if bar.is_synthetic {
+            tracker.mark(bar.close, bar.timestamp_us);
+            continue;
+        }
+        let desired = match signal {
+            1 => Side::Long,
+            -1 => Side::Short,
+            _ => Side::Flat,
+        };
+        let mut pending_signal = signal != HOLD
+            && (desired != tracker.side || (desired != Side::Flat && tracker.can_pyramid()));
+        let close_us = bar_close_us(scid, bar);
+        if pending_signal || tracker.may_exit_within(bar.high, bar.low, close_us) {
+            for index in bar.first_tick_index..=bar.last_tick_index {
+                let tick = scid.tick(index);
+                // Out-of-order ticks were left out of the bar
+                if !tick.is_valid() || tick.timestamp_us < bar.timestamp_us {
+                    continue;
+                }
+                let ctx = tick_context(&tick);
+                if pending_signal {
+                    tracker.apply_signal(signal, tick.price, tick.timestamp_us, &ctx);
+                    pending_signal = false;
+                } else {
+                    tracker.check_exits(tick.price, tick.timestamp_us, &ctx);
+                }
+            }
+        }
+        // No valid tick to fill on
+        if pending_signal {
+            tracker.apply_signal(signal, bar.close, close_us, &bar_context(bar));
+        }
+        tracker.mark(bar.close, close_us);
+    }
+    let last = &bars[n - 1];
+    let last_us = bar_close_us(scid, last);
+    if config.final_signal == FinalSignal::FillAtClose {
+        tracker.signal_tag = tag_at(n - 1);
+        tracker.process_signal(signals[n - 1], last.close, last_us, &bar_context(last));
+    }
+    tracker.bar_index = n - 1;
+    tracker.close_position(last.close, last_us, &bar_context(last));
+}
+
+/// Time of a bar's last tick, or its open for a synthetic bar.
+fn bar_close_us(scid: &ScidFile, bar: &Bar) -> i64 {
+    if bar.is_synthetic {
+        bar.timestamp_us
+    } else {
+        scid.tick(bar.last_tick_index).timestamp_us
+    }
+}
+
+/// Run a bar backtest and score it against holding `config.quantity` contracts of the
+/// benchmark file over the same period, filling in the information ratio, beta, alpha
+/// and Treynor ratio.
+/// The benchmark is aggregated with the same bar options and sampled at each equity
+/// point's timestamp (last benchmark close at or before it).
+pub fn run_backtest_vs_benchmark(
+    py: Python<'_>,
+    path: &str,
+    bar_options: &BarOptions,
     callback: &Bound<'_, PyAny>,
-    commission: f64,
-    point_value: f64,
+    benchmark_path: &str,
+    benchmark_point_value: f64,
+    config: &BacktestConfig,
+) -> PyResult<BacktestResults> {
+    let scid = open_scid(py, path)?;
+    let mut results = run_bar_backtest(
+        py,
+        &scid,
+        Some(Path::new(path)),
+        bar_options,
+        callback,
+        config,
+    )?;
+
+    let benchmark = open_scid(py, benchmark_path)?;
+    let benchmark_bars = bar_options
+        .build(&benchmark, Some(Path::new(benchmark_path)))
+        .map_err(pyo3::exceptions::PyIOError::new_err)?
+        .bars;
+    if benchmark_bars.is_empty() {
+        return Err(pyo3::exceptions::PyValueError::new_err(
+            "No benchmark bars generated",
+        ));
+    }
+
+    let benchmark_equity = buy_and_hold_equity(
+        &benchmark_bars,
+        &results.equity_timestamps,
+        benchmark_point_value * config.quantity,
+    );
+    score_against_benchmark(
+        &mut results.metrics,
+        &results.equity_curve,
+        &benchmark_equity,
+        &results.equity_timestamps,
+        &config.metrics,
+    );
+    Ok(results)
+}
+
+/// Dollar equity of holding a position in `bars` from the first bar, sampled at each of
+/// the (ascending) `timestamps_us`.
+fn buy_and_hold_equity(bars: &[Bar], timestamps_us: &[i64], dollars_per_point: f64) -> Vec<f64> {
+    let first_close = bars[0].close;
+    let mut idx = 0;
+    timestamps_us
+        .iter()
+        .map(|&ts| {
+            while idx + 1 < bars.len() && bars[idx + 1].timestamp_us <= ts {
+                idx += 1;
+            }
+            let close = if bars[idx].timestamp_us <= ts {
+                bars[idx].close
+            } else {
+                first_close
+            };
+            (close - first_close) * dollars_per_point
+        })
+        .collect()
+}
+
+/// Percentile bands from reshuffling the order of a backtest's trades.
+pub struct MonteCarloBands {
+    /// 5th/50th/95th percentile equity after each trade, starting at the first equity point
+    pub p5_curve: Vec<f64>,
+    pub p50_curve: Vec<f64>,
+    pub p95_curve: Vec<f64>,
+    /// 5th/95th percentile of the per-simulation maximum drawdown
+    pub p5_max_dd: f64,
+    pub p95_max_dd: f64,
+    /// 5th percentile of the final equity
+    pub p5_final_equity: f64,
+}
+
+/// Shuffle the trade PnLs `n_simulations` times, rebuild the equity path for each ordering
+/// (starting from the first point of `equity_curve`, or 0), and take percentiles across
+/// simulations at every trade and of the max drawdown and final equity.
+pub fn run_monte_carlo(
+    trades: &[Trade],
+    n_simulations: usize,
+    equity_curve: &[f64],
+) -> MonteCarloBands {
+    let start = equity_curve.first().copied().unwrap_or(0.0);
+    let steps = trades.len() + 1;
+    if n_simulations == 0 {
+        return MonteCarloBands {
+            p5_curve: Vec::new(),
+            p50_curve: Vec::new(),
+            p95_curve: Vec::new(),
+            p5_max_dd: f64::NAN,
+            p95_max_dd: f64::NAN,
+            p5_final_equity: f64::NAN,
+        };
+    }
+
+    let mut rng = rand::thread_rng();
+    let mut pnls: Vec<f64> = trades.iter().map(|t| t.pnl).collect();
+    // [step][simulation]
+    let mut paths = vec![Vec::with_capacity(n_simulations); steps];
+    let mut max_dds = Vec::with_capacity(n_simulations);
+    let mut finals = Vec::with_capacity(n_simulations);
+
+    let mut curve = Vec::with_capacity(steps);
+    for _ in 0..n_simulations {
+        pnls.shuffle(&mut rng);
+        curve.clear();
+        curve.push(start);
+        let mut equity = start;
+        for &pnl in &pnls {
+            equity += pnl;
+            curve.push(equity);
+        }
+        for (step, &eq) in curve.iter().enumerate() {
+            paths[step].push(eq);
+        }
+        max_dds.push(calc_max_drawdown(&curve).0);
+        finals.push(equity);
+    }
+
+    let mut p5_curve = Vec::with_capacity(steps);
+    let mut p50_curve = Vec::with_capacity(steps);
+    let mut p95_curve = Vec::with_capacity(steps);
+    for values in &mut paths {
+        values.sort_by(f64::total_cmp);
+        p5_curve.push(percentile(values, 0.05));
+        p50_curve.push(percentile(values, 0.50));
+        p95_curve.push(percentile(values, 0.95));
+    }
+    max_dds.sort_by(f64::total_cmp);
+    finals.sort_by(f64::total_cmp);
+
+    MonteCarloBands {
+        p5_curve,
+        p50_curve,
+        p95_curve,
+        p5_max_dd: percentile(&max_dds, 0.05),
+        p95_max_dd: percentile(&max_dds, 0.95),
+        p5_final_equity: percentile(&finals, 0.05),
+    }
+}
+
+/// Run a tick-based backtest over `source`. Sends batches of ticks to the callback.
+///
+/// With `depth` set, each batch also carries the latest depth snapshot at or before every
+/// tick as `bid_price_1`, `bid_size_1`, `ask_price_1`, `ask_size_1`, ... (NaN before the
+/// first snapshot).
+///
+/// With `state` set, the callback is called as `callback(tick_data, state)` and must
+/// return `(signals, new_state)` or `(signals, tags, new_state)`; the new state is passed
+/// to the next batch.
+pub fn run_tick_backtest<'py>(
+    py: Python<'py>,
+    source: &dyn DataSource,
+    batch_size: usize,
+    callback: &Bound<'py, PyAny>,
+    config: &BacktestConfig,
+    mut state: Option<Bound<'py, PyAny>>,
+    depth: Option<&DepthSnapshots>,
 ) -> PyResult<BacktestResults> {
-    let scid = ScidFile::open(path).map_err(|e| pyo3::exceptions::PyIOError::new_err(e))?;
+    let skipped = py.detach(|| source.skip_stats());
+    check_skipped(&skipped, source.num_ticks(), config.max_skipped_fraction)?;
 
-    let mut tracker = PositionTracker::new(commission, point_value);
-    let total = scid.num_records;
+    let mut tracker = config.tracker()?;
+    let total = source.num_ticks();
     let mut offset = 0usize;
+    let mut last_valid = None;
+    let mut unbundler = Unbundler::new(config.unbundle);
 
     while offset < total {
         let end = (offset + batch_size).min(total);
-        let batch_len = end - offset;
-
-        let mut timestamps = Vec::with_capacity(batch_len);
-        let mut prices = Vec::with_capacity(batch_len);
-        let mut bids = Vec::with_capacity(batch_len);
-        let mut asks = Vec::with_capacity(batch_len);
-        let mut volumes = Vec::with_capacity(batch_len);
-        let mut bid_vols = Vec::with_capacity(batch_len);
-        let mut ask_vols = Vec::with_capacity(batch_len);
-
-        for i in offset..end {
-            let tick = scid.tick(i);
-            if tick.price <= 0.0 {
-                continue;
+
+        let mut depth_rows = Vec::new();
+        // Kept for the fills, so slippage sees the same unbundled volumes as the strategy
+        let (ticks, columns): (Vec<Tick>, TickColumns) = py.detach(|| {
+            let ticks: Vec<Tick> = source
+                .iter_range(offset, end)
+                .map(|tick| unbundler.apply(&tick))
+                .filter(Tick::is_valid)
+                .collect();
+            if let Some(depth) = depth {
+                depth_rows.extend(
+                    ticks
+                        .as_slice()
+                        .iter()
+                        .map(|t| depth.index_at(t.timestamp_us)),
+                );
             }
-            timestamps.push(tick.timestamp_us as f64 / 1_000_000.0);
-            prices.push(tick.price);
-            bids.push(tick.bid);
-            asks.push(tick.ask);
-            volumes.push(tick.volume as f64);
-            bid_vols.push(tick.bid_volume as f64);
-            ask_vols.push(tick.ask_volume as f64);
-        }
+            let columns = ticks.as_slice().iter().copied().collect();
+            (ticks, columns)
+        });
 
-        let actual_len = timestamps.len();
+        let actual_len = columns.len();
         if actual_len == 0 {
             offset = end;
             continue;
         }
 
-        let tick_data = PyDict::new(py);
-        tick_data.set_item("timestamp", PyArray1::from_vec(py, timestamps))?;
-        tick_data.set_item("price", PyArray1::from_vec(py, prices))?;
-        tick_data.set_item("bid", PyArray1::from_vec(py, bids))?;
-        tick_data.set_item("ask", PyArray1::from_vec(py, asks))?;
-        tick_data.set_item("volume", PyArray1::from_vec(py, volumes))?;
-        tick_data.set_item("bid_volume", PyArray1::from_vec(py, bid_vols))?;
-        tick_data.set_item("ask_volume", PyArray1::from_vec(py, ask_vols))?;
+        let tick_data = columns.into_pydict(py)?;
         tick_data.set_item("num_ticks", actual_len)?;
+        if let Some(depth) = depth {
+            set_depth_columns(py, &tick_data, depth, &depth_rows)?;
+        }
+        // Position as of the start of the batch
+        let position = match tracker.side {
+            Side::Long => 1,
+            Side::Short => -1,
+            Side::Flat => 0,
+        };
+        tick_data.set_item("position", position)?;
+        tick_data.set_item(
+            "entry_price",
+            if position == 0 {
+                f64::NAN
+            } else {
+                tracker.entry_price
+            },
+        )?;
 
-        let result = callback.call1((tick_data,))?;
-        let signals: Vec<i32> = result.extract()?;
+        let result = match &state {
+            Some(current) => {
+                let (result, next) = split_state(&callback.call1((tick_data, current))?)?;
+                state = Some(next);
+                result
+            }
+            None => callback.call1((tick_data,))?,
+        };
+        let (signals, tags) = extract_signals(&result)?;
 
         if signals.len() != actual_len {
             return Err(pyo3::exceptions::PyValueError::new_err(format!(
@@ -169,36 +1319,614 @@ pub fn run_tick_backtest(
         }
 
         // Process signals
-        let mut tick_idx = 0;
-        for i in offset..end {
-            let tick = scid.tick(i);
-            if tick.price <= 0.0 {
-                continue;
+        for (tick_idx, tick) in ticks.as_slice().iter().enumerate() {
+            if let Some(tags) = &tags {
+                tracker.signal_tag = tags[tick_idx];
             }
-            tracker.process_signal(signals[tick_idx], tick.price, tick.timestamp_us);
-            tick_idx += 1;
+            tracker.process_signal(
+                config.direction.clamp(signals.get(tick_idx)),
+                tick.price,
+                tick.timestamp_us,
+                &tick_context(tick),
+            );
         }
+        last_valid = ticks.last().copied().or(last_valid);
 
         offset = end;
     }
 
     // Close any open position
-    if total > 0 {
-        let last = scid.tick(total - 1);
-        tracker.close_position(last.price, last.timestamp_us);
+    if let Some(last) = last_valid {
+        tracker.close_position(last.price, last.timestamp_us, &tick_context(&last));
+    }
+
+    Ok(finish(tracker, config, skipped))
+}
+
+fn bar_context(bar: &Bar) -> FillContext {
+    FillContext {
+        volume: bar.volume as f64,
+        high: bar.high,
+        low: bar.low,
+    }
+}
+
+/// In tick mode the bid/ask spread stands in for the bar range.
+fn tick_context(tick: &Tick) -> FillContext {
+    FillContext {
+        volume: tick.volume as f64,
+        high: tick.ask,
+        low: tick.bid,
     }
+}
+
+/// Compute metrics for a finished simulation and package the results. A sampled equity
+/// curve can miss the worst point, so the drawdown the tracker followed over every point
+/// replaces the one measured on the curve; the other curve-based metrics (drawdown
+/// duration, Ulcer Index, daily Sharpe) use the sampled curve.
+fn finish(
+    tracker: PositionTracker,
+    config: &BacktestConfig,
+    skipped: SkipStats,
+) -> BacktestResults {
+    let sampled = tracker.equity_sampling != EquitySampling::Every;
+    let (max_drawdown, max_drawdown_pct) = (tracker.max_drawdown, tracker.max_drawdown_pct);
+    let mut results = build_results(
+        tracker.trades,
+        tracker.equity_curve,
+        tracker.equity_timestamps,
+        config,
+        skipped,
+    );
+    if sampled {
+        results.metrics.max_drawdown = max_drawdown;
+        results.metrics.max_drawdown_pct = max_drawdown_pct;
+    }
+    results
+}
 
-    let metrics = compute_metrics(&tracker.trades, &tracker.equity_curve);
+fn build_results(
+    trades: Vec<Trade>,
+    equity_curve: Vec<f64>,
+    equity_timestamps: Vec<i64>,
+    config: &BacktestConfig,
+    skipped: SkipStats,
+) -> BacktestResults {
+    let metrics = compute_metrics(&trades, &equity_curve, &equity_timestamps, &config.metrics);
+    let metrics_by_tag = compute_metrics_by_tag(&trades, &config.metrics);
+    let monthly_stats = compute_monthly_stats(&trades, config.metrics.annual_factor);
+    let rolling_sharpe = calc_rolling_sharpe(
+        &equity_curve,
+        config.metrics.rolling_window,
+        config.metrics.annual_factor,
+    );
 
-    Ok(BacktestResults {
+    BacktestResults {
         metrics,
-        trades: tracker.trades,
-        equity_curve: tracker.equity_curve,
-    })
+        metrics_by_tag,
+        monthly_stats,
+        trades,
+        equity_curve,
+        equity_timestamps,
+        rolling_sharpe,
+        skipped,
+        bars_truncated: false,
+        benchmark_pnl: None,
+    }
+}
+
+/// Signal array returned by a strategy. Numpy arrays are read in place through a
+/// readonly view rather than copied; plain Python sequences are extracted into a Vec.
+pub enum SignalArray<'py> {
+    I8(PyReadonlyArray1<'py, i8>),
+    I16(PyReadonlyArray1<'py, i16>),
+    I32(PyReadonlyArray1<'py, i32>),
+    I64(PyReadonlyArray1<'py, i64>),
+    U8(PyReadonlyArray1<'py, u8>),
+    U16(PyReadonlyArray1<'py, u16>),
+    U32(PyReadonlyArray1<'py, u32>),
+    U64(PyReadonlyArray1<'py, u64>),
+    /// Float arrays, already checked to contain only -1, 0, 1 and NaN
+    F32(PyReadonlyArray1<'py, f32>),
+    F64(PyReadonlyArray1<'py, f64>),
+    List(Vec<i32>),
+}
+
+impl SignalArray<'_> {
+    pub fn len(&self) -> usize {
+        match self {
+            SignalArray::I8(a) => a.len(),
+            SignalArray::I16(a) => a.len(),
+            SignalArray::I32(a) => a.len(),
+            SignalArray::I64(a) => a.len(),
+            SignalArray::U8(a) => a.len(),
+            SignalArray::U16(a) => a.len(),
+            SignalArray::U32(a) => a.len(),
+            SignalArray::U64(a) => a.len(),
+            SignalArray::F32(a) => a.len(),
+            SignalArray::F64(a) => a.len(),
+            SignalArray::List(v) => v.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Signal at `i`. Integers outside the i32 range read as flat.
+    #[inline]
+    pub fn get(&self, i: usize) -> i32 {
+        match self {
+            SignalArray::I8(a) => a.as_array()[i] as i32,
+            SignalArray::I16(a) => a.as_array()[i] as i32,
+            SignalArray::I32(a) => a.as_array()[i],
+            SignalArray::I64(a) => i32::try_from(a.as_array()[i]).unwrap_or(0),
+            SignalArray::U8(a) => a.as_array()[i] as i32,
+            SignalArray::U16(a) => a.as_array()[i] as i32,
+            SignalArray::U32(a) => i32::try_from(a.as_array()[i]).unwrap_or(0),
+            SignalArray::U64(a) => i32::try_from(a.as_array()[i]).unwrap_or(0),
+            SignalArray::F32(a) => float_signal(a.as_array()[i] as f64),
+            SignalArray::F64(a) => float_signal(a.as_array()[i]),
+            SignalArray::List(v) => v[i],
+        }
+    }
+}
+
+/// A float signal, already checked: NaN holds.
+#[inline]
+fn float_signal(v: f64) -> i32 {
+    if v.is_nan() {
+        HOLD
+    } else {
+        v as i32
+    }
+}
+
+/// Check that a float signal array only holds -1, 0, 1 and NaN.
+fn check_float_signals<T: Copy + Into<f64>>(
+    values: impl Iterator<Item = T>,
+    dtype: &str,
+) -> PyResult<()> {
+    for (i, v) in values.enumerate() {
+        let v: f64 = v.into();
+        if v != -1.0 && v != 0.0 && v != 1.0 && !v.is_nan() {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "Signal array of dtype {dtype} has value {v} at index {i}; \
+                 float signals may only contain -1, 0, 1 and NaN"
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn extract_signal_array<'py>(obj: &Bound<'py, PyAny>) -> PyResult<SignalArray<'py>> {
+    let Ok(arr) = obj.cast::<PyUntypedArray>() else {
+        return obj
+            .extract::<Vec<i32>>()
+            .map(SignalArray::List)
+            .map_err(|_| {
+                pyo3::exceptions::PyTypeError::new_err(format!(
+                    "Signals must be a 1-D numpy array or a list of ints, got {}",
+                    obj.get_type()
+                ))
+            });
+    };
+    let dtype = arr.dtype().to_string();
+    if arr.ndim() != 1 {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "Signal array must be 1-D, got {} dimensions",
+            arr.ndim()
+        )));
+    }
+
+    macro_rules! try_int {
+        ($t:ty, $variant:ident) => {
+            if let Ok(a) = obj.extract::<PyReadonlyArray1<'py, $t>>() {
+                return Ok(SignalArray::$variant(a));
+            }
+        };
+    }
+    try_int!(i8, I8);
+    try_int!(i16, I16);
+    try_int!(i32, I32);
+    try_int!(i64, I64);
+    try_int!(u8, U8);
+    try_int!(u16, U16);
+    try_int!(u32, U32);
+    try_int!(u64, U64);
+    if let Ok(a) = obj.extract::<PyReadonlyArray1<'py, f32>>() {
+        check_float_signals(a.as_array().iter().copied(), &dtype)?;
+        return Ok(SignalArray::F32(a));
+    }
+    if let Ok(a) = obj.extract::<PyReadonlyArray1<'py, f64>>() {
+        check_float_signals(a.as_array().iter().copied(), &dtype)?;
+        return Ok(SignalArray::F64(a));
+    }
+    Err(pyo3::exceptions::PyTypeError::new_err(format!(
+        "Signal array has dtype {dtype}; expected an integer dtype \
+         (int8/16/32/64, uint8/16/32/64) or float32/float64 holding only -1, 0, 1"
+    )))
+}
+
+/// Add `{bid,ask}_{price,size}_{level}` columns to `dict`, one value per entry of `rows`
+/// (a snapshot index, or None for NaN).
+pub fn set_depth_columns(
+    py: Python<'_>,
+    dict: &Bound<'_, PyDict>,
+    depth: &DepthSnapshots,
+    rows: &[Option<usize>],
+) -> PyResult<()> {
+    let columns = [
+        ("bid_price", &depth.bid_price),
+        ("bid_size", &depth.bid_size),
+        ("ask_price", &depth.ask_price),
+        ("ask_size", &depth.ask_size),
+    ];
+    for (name, levels) in columns {
+        for (level, values) in levels.iter().enumerate() {
+            let column: Vec<f64> = rows
+                .iter()
+                .map(|row| row.map_or(f64::NAN, |r| values[r]))
+                .collect();
+            dict.set_item(
+                format!("{name}_{}", level + 1),
+                PyArray1::from_vec(py, column),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Split a stateful callback's `(signals, state)` or `(signals, tags, state)` return
+/// into the signal part (as `extract_signals` expects it) and the new state.
+fn split_state<'py>(
+    result: &Bound<'py, PyAny>,
+) -> PyResult<(Bound<'py, PyAny>, Bound<'py, PyAny>)> {
+    let tuple = result.cast::<PyTuple>().map_err(|_| {
+        pyo3::exceptions::PyTypeError::new_err(
+            "Stateful tick callback must return (signals, state) or (signals, tags, state)",
+        )
+    })?;
+    match tuple.len() {
+        2 => Ok((tuple.get_item(0)?, tuple.get_item(1)?)),
+        3 => {
+            let signals = PyTuple::new(result.py(), [tuple.get_item(0)?, tuple.get_item(1)?])?;
+            Ok((signals.into_any(), tuple.get_item(2)?))
+        }
+        n => Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "Stateful tick callback returned a {n}-tuple; expected (signals, state) or \
+             (signals, tags, state)"
+        ))),
+    }
+}
+
+/// Extract the strategy's return value: either a signal array, or a `(signals, tags)`
+/// tuple where `tags` is a parallel array of integer setup labels.
+fn extract_signals<'py>(
+    result: &Bound<'py, PyAny>,
+) -> PyResult<(SignalArray<'py>, Option<Vec<i32>>)> {
+    if let Ok(tuple) = result.cast::<PyTuple>() {
+        if tuple.len() == 2 {
+            let signals = extract_signal_array(&tuple.get_item(0)?)?;
+            let tags: Vec<i32> = tuple.get_item(1)?.extract()?;
+            if tags.len() != signals.len() {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "Tag array length {} != signal array length {}",
+                    tags.len(),
+                    signals.len()
+                )));
+            }
+            return Ok((signals, Some(tags)));
+        }
+    }
+    Ok((extract_signal_array(result)?, None))
 }
 
 pub struct BacktestResults {
     pub metrics: BacktestMetrics,
-    pub trades: Vec<crate::position::Trade>,
+    /// Metrics per strategy tag (trades grouped by the tag active at entry)
+    pub metrics_by_tag: BTreeMap<i32, BacktestMetrics>,
+    /// Per calendar month (UTC, by trade exit) PnL and risk-adjusted ratios
+    pub monthly_stats: BTreeMap<(i32, u32), MonthlyStats>,
+    pub trades: Vec<Trade>,
+    /// Mark-to-market account equity, starting from the initial capital and including any
+    /// open position, at each processed bar or tick
     pub equity_curve: Vec<f64>,
+    /// Timestamp (Unix microseconds) of each equity curve point
+    pub equity_timestamps: Vec<i64>,
+    /// Trailing-window Sharpe aligned with the equity curve
+    pub rolling_sharpe: Vec<f64>,
+    /// Records dropped as invalid before simulation
+    pub skipped: SkipStats,
+    /// The bars were a tail window of the file (see `BarOptions::tail`)
+    pub bars_truncated: bool,
+    /// Buy-and-hold benchmark equity aligned with `equity_curve` (see
+    /// `BacktestConfig::benchmark_side`)
+    pub benchmark_pnl: Option<Vec<f64>>,
+}
+
+impl BacktestResults {
+    /// Metrics, trades and the timestamped equity curve as a JSON object. Non-finite
+    /// numbers (e.g. a NaN ratio) are written as null.
+    pub fn to_json(&self) -> String {
+        #[derive(Serialize)]
+        struct Json<'a> {
+            metrics: &'a BacktestMetrics,
+            trades: &'a [Trade],
+            equity_curve: &'a [f64],
+            equity_timestamps: &'a [i64],
+        }
+        serde_json::to_string(&Json {
+            metrics: &self.metrics,
+            trades: &self.trades,
+            equity_curve: &self.equity_curve,
+            equity_timestamps: &self.equity_timestamps,
+        })
+        .expect("results serialize to JSON")
+    }
+
+    /// Thin the equity curve and the series aligned with it to at most `max_points`
+    /// points (see `downsample_min_max`). Metrics and trades are unchanged.
+    pub fn downsample_equity(&mut self, max_points: usize) {
+        let keep = downsample_min_max(&self.equity_curve, max_points);
+        if keep.len() == self.equity_curve.len() {
+            return;
+        }
+        fn pick<T: Copy>(values: &[T], keep: &[usize]) -> Vec<T> {
+            keep.iter().map(|&i| values[i]).collect()
+        }
+        self.equity_curve = pick(&self.equity_curve, &keep);
+        self.equity_timestamps = pick(&self.equity_timestamps, &keep);
+        self.rolling_sharpe = pick(&self.rolling_sharpe, &keep);
+        if let Some(benchmark) = &mut self.benchmark_pnl {
+            *benchmark = pick(benchmark, &keep);
+        }
+    }
+}
+
+/// One out-of-sample segment of a walk-forward run.
+pub struct WalkForwardWindow {
+    /// Open timestamp (Unix microseconds) of the first in-sample bar
+    pub in_sample_start_us: i64,
+    /// Open timestamps of the first and last out-of-sample bars
+    pub out_sample_start_us: i64,
+    pub out_sample_end_us: i64,
+    /// Out-of-sample bars in the window (fewer than requested for a final partial window)
+    pub num_bars: usize,
+    /// The segment's trades, with its equity curve starting from `initial_capital`
+    pub results: BacktestResults,
+}
+
+pub struct WalkForwardResults {
+    /// All out-of-sample segments stitched together
+    pub combined: BacktestResults,
+    pub windows: Vec<WalkForwardWindow>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE_US: i64 = 60_000_000;
+
+    /// A one-minute bar with its high and low at the open and close.
+    fn bar(i: usize, open: f64, close: f64) -> Bar {
+        Bar {
+            timestamp_us: 1_704_153_600_000_000 + i as i64 * MINUTE_US,
+            open,
+            high: open.max(close),
+            low: open.min(close),
+            close,
+            volume: 10,
+            bid_volume: 5,
+            ask_volume: 5,
+            num_trades: 10,
+            tick_count: 10,
+            delta: 0,
+            delta_high: 0,
+            delta_low: 0,
+            is_synthetic: false,
+            first_tick_index: i,
+            last_tick_index: i,
+        }
+    }
+
+    fn bars() -> Vec<Bar> {
+        vec![
+            bar(0, 100.0, 100.5),
+            bar(1, 101.0, 101.5),
+            bar(2, 102.0, 102.5),
+            bar(3, 103.0, 103.5),
+        ]
+    }
+
+    fn simulate(signals: Vec<i32>, config: &BacktestConfig) -> Vec<Trade> {
+        let mut tracker = config.tracker().unwrap();
+        simulate_bars(
+            &mut tracker,
+            &bars(),
+            &SignalArray::List(signals),
+            None,
+            config,
+        );
+        tracker.trades
+    }
+
+    #[test]
+    fn next_open_fills_at_the_following_bar_open() {
+        let config = BacktestConfig {
+            fill_mode: FillMode::NextOpen,
+            ..BacktestConfig::default()
+        };
+        let trades = simulate(vec![1, 0, 0, 0], &config);
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].entry_price, 101.0);
+        assert_eq!(trades[0].entry_time_us, bars()[1].timestamp_us);
+        assert_eq!(trades[0].exit_price, 102.0);
+    }
+
+    #[test]
+    fn current_close_fills_at_the_signal_bar_close() {
+        let trades = simulate(vec![1, 0, 0, 0], &BacktestConfig::default());
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].entry_price, 100.5);
+        assert_eq!(trades[0].exit_price, 101.5);
+    }
+
+    #[test]
+    fn final_bar_signal_is_dropped_by_default() {
+        let config = BacktestConfig {
+            fill_mode: FillMode::NextOpen,
+            final_signal: FinalSignal::Drop,
+            ..BacktestConfig::default()
+        };
+        assert!(simulate(vec![0, 0, 0, 1], &config).is_empty());
+    }
+
+    #[test]
+    fn final_bar_signal_fills_at_its_close() {
+        let config = BacktestConfig {
+            fill_mode: FillMode::NextOpen,
+            final_signal: FinalSignal::FillAtClose,
+            ..BacktestConfig::default()
+        };
+        let trades = simulate(vec![0, 0, 0, 1], &config);
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].side, Side::Long);
+        assert_eq!(trades[0].entry_price, 103.5);
+        assert_eq!(trades[0].exit_price, 103.5);
+        assert_eq!(trades[0].entry_time_us, bars()[3].timestamp_us);
+    }
+
+    #[test]
+    fn downsampled_equity_keeps_its_series_aligned() {
+        let config = BacktestConfig {
+            metrics: MetricsOptions {
+                rolling_window: 5,
+                ..MetricsOptions::default()
+            },
+            ..BacktestConfig::default()
+        };
+        let mut tracker = config.tracker().unwrap();
+        let bars: Vec<Bar> = (0..500)
+            .map(|i| {
+                let price = 100.0 + (i as f64 / 20.0).sin() * 5.0;
+                bar(i, price, price)
+            })
+            .collect();
+        let signals = SignalArray::List(vec![1; bars.len()]);
+        simulate_bars(&mut tracker, &bars, &signals, None, &config);
+        let mut results = finish(tracker, &config, SkipStats::default());
+        let full = (
+            results.equity_curve.clone(),
+            results.equity_timestamps.clone(),
+        );
+        results.downsample_equity(50);
+        assert!(results.equity_curve.len() <= 50);
+        assert_eq!(results.equity_timestamps.len(), results.equity_curve.len());
+        assert_eq!(results.rolling_sharpe.len(), results.equity_curve.len());
+        for (eq, ts) in results.equity_curve.iter().zip(&results.equity_timestamps) {
+            let i = full.1.iter().position(|t| t == ts).unwrap();
+            assert_eq!(full.0[i], *eq);
+        }
+        let min = |v: &[f64]| v.iter().copied().fold(f64::MAX, f64::min);
+        assert_eq!(min(&results.equity_curve), min(&full.0));
+    }
+
+    #[test]
+    fn nan_float_signals_hold() {
+        assert_eq!(float_signal(f64::NAN), HOLD);
+        assert_eq!(float_signal(-1.0), -1);
+        let trades = simulate(vec![1, HOLD, HOLD, 0], &BacktestConfig::default());
+        assert_eq!(trades.len(), 1);
+        assert_eq!(
+            (trades[0].entry_price, trades[0].exit_price),
+            (100.5, 103.5)
+        );
+    }
+
+    #[test]
+    fn hybrid_fills_at_the_close_of_a_bar_without_valid_ticks() {
+        // One tick per bar at its close price, except bar 1's, which has no price
+        let ticks: Vec<Tick> = bars()
+            .iter()
+            .enumerate()
+            .map(|(i, bar)| {
+                let price = if i == 1 { 0.0 } else { bar.close };
+                Tick {
+                    timestamp_us: bar.timestamp_us + 30_000_000,
+                    open: 0.0,
+                    price,
+                    bid: price,
+                    ask: price,
+                    volume: 1,
+                    bid_volume: 0,
+                    ask_volume: 1,
+                    num_trades: 1,
+                }
+            })
+            .collect();
+        let scid = ScidFile::from_ticks(&ticks).unwrap();
+        let config = BacktestConfig::default();
+        let mut tracker = config.tracker().unwrap();
+        simulate_hybrid(
+            &mut tracker,
+            &scid,
+            &bars(),
+            &SignalArray::List(vec![1, 0, 0, 0]),
+            None,
+            &config,
+        );
+        let trades = tracker.trades;
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].entry_price, 101.5);
+        assert_eq!(trades[0].entry_time_us, ticks[1].timestamp_us);
+        assert_eq!(trades[0].exit_price, 102.5);
+    }
+
+    #[test]
+    fn results_round_trip_through_json() {
+        let config = BacktestConfig::default();
+        let mut tracker = config.tracker().unwrap();
+        simulate_bars(
+            &mut tracker,
+            &bars(),
+            &SignalArray::List(vec![1, -1, 0, 0]),
+            None,
+            &config,
+        );
+        let results = finish(tracker, &config, SkipStats::default());
+        assert_eq!(results.trades.len(), 2);
+
+        let json: serde_json::Value = serde_json::from_str(&results.to_json()).unwrap();
+        let trades = json["trades"].as_array().unwrap();
+        assert_eq!(trades.len(), results.trades.len());
+        for (value, trade) in trades.iter().zip(&results.trades) {
+            assert_eq!(value["entry_time_us"], trade.entry_time_us);
+            assert_eq!(value["exit_time_us"], trade.exit_time_us);
+            assert_eq!(value["side"], format!("{:?}", trade.side).to_lowercase());
+            assert_eq!(value["entry_price"], trade.entry_price);
+            assert_eq!(value["exit_price"], trade.exit_price);
+            assert_eq!(value["pnl"], trade.pnl);
+        }
+
+        let metrics = &json["metrics"];
+        let m = &results.metrics;
+        assert_eq!(metrics["total_pnl"], m.total_pnl);
+        assert_eq!(metrics["num_trades"], m.num_trades);
+        assert_eq!(metrics["win_rate"], m.win_rate);
+        assert_eq!(metrics["max_drawdown"], m.max_drawdown);
+        // No benchmark, so beta is NaN, written as null
+        assert!(m.beta.is_nan());
+        assert!(metrics["beta"].is_null());
+
+        assert_eq!(
+            json["equity_curve"],
+            serde_json::json!(results.equity_curve)
+        );
+        assert_eq!(
+            json["equity_timestamps"],
+            serde_json::json!(results.equity_timestamps)
+        );
+    }
 }
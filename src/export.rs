@@ -0,0 +1,72 @@
+//! Writing backtest output to files for use outside Python.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::position::{Side, Trade};
+use crate::session::iso8601_utc;
+
+/// Write one CSV row per trade under the header
+/// `entry_time,exit_time,side,entry_price,exit_price,pnl`, with ISO-8601 UTC times.
+/// No trades gives a file with just the header.
+pub fn write_trades_csv<P: AsRef<Path>>(path: P, trades: &[Trade]) -> Result<(), String> {
+    let file = File::create(path.as_ref()).map_err(|e| format!("create: {e}"))?;
+    let mut w = BufWriter::new(file);
+    let write_err = |e: std::io::Error| format!("write: {e}");
+    writeln!(w, "entry_time,exit_time,side,entry_price,exit_price,pnl").map_err(write_err)?;
+    for t in trades {
+        let side = match t.side {
+            Side::Long => "long",
+            Side::Short => "short",
+            Side::Flat => "flat",
+        };
+        writeln!(
+            w,
+            "{},{},{side},{},{},{}",
+            iso8601_utc(t.entry_time_us),
+            iso8601_utc(t.exit_time_us),
+            t.entry_price,
+            t.exit_price,
+            t.pnl
+        )
+        .map_err(write_err)?;
+    }
+    w.flush().map_err(write_err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::{FillContext, PositionTracker};
+
+    #[test]
+    fn trades_csv_has_a_header_and_a_row_per_trade() {
+        let mut tracker = PositionTracker::new(0.0, 50.0);
+        // Long, then short reversed into a final long: three trades from 2024-01-02 00:00 UTC
+        for (i, &(signal, price)) in [(1, 100.0), (0, 101.5), (-1, 102.0), (1, 101.0), (0, 99.25)]
+            .iter()
+            .enumerate()
+        {
+            let ts = 1_704_153_600_000_000 + i as i64 * 60_000_000;
+            tracker.process_signal(signal, price, ts, &FillContext::default());
+        }
+        assert_eq!(tracker.trades.len(), 3);
+
+        let path = std::env::temp_dir().join(format!("trades_{}.csv", std::process::id()));
+        write_trades_csv(&path, &tracker.trades).unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(rows.len(), tracker.trades.len() + 1);
+        assert_eq!(
+            rows[0],
+            "entry_time,exit_time,side,entry_price,exit_price,pnl"
+        );
+        assert_eq!(
+            rows[1],
+            "2024-01-02T00:00:00Z,2024-01-02T00:01:00Z,long,100,101.5,75"
+        );
+        assert!(rows[2].contains(",short,102,101,50"), "{}", rows[2]);
+    }
+}
@@ -1,155 +1,1867 @@
-mod bar;
-mod engine;
-mod metrics;
-mod position;
-mod scid;
+pub mod bar;
+pub mod cache;
+pub mod columns;
+pub mod csv_loader;
+pub mod datasource;
+pub mod depth;
+pub mod engine;
+pub mod export;
+pub mod metrics;
+pub mod order;
+pub mod parquet_loader;
+pub mod position;
+pub mod replay;
+pub mod scid;
+pub mod session;
+pub mod signals;
+pub mod strategy;
+
+use std::path::{Path, PathBuf};
 
 use numpy::PyArray1;
 use pyo3::prelude::*;
-use pyo3::types::PyDict;
-
-use bar::{aggregate_bars, BarInterval};
-use scid::ScidFile;
-
-/// Load raw ticks from an SCID file. Returns a dict of numpy arrays.
-#[pyfunction]
-fn load_scid(py: Python<'_>, path: &str) -> PyResult<Py<PyDict>> {
-    let scid = ScidFile::open(path).map_err(|e| pyo3::exceptions::PyIOError::new_err(e))?;
-    let n = scid.num_records;
-
-    let mut timestamps = Vec::with_capacity(n);
-    let mut prices = Vec::with_capacity(n);
-    let mut bids = Vec::with_capacity(n);
-    let mut asks = Vec::with_capacity(n);
-    let mut volumes = Vec::with_capacity(n);
-    let mut bid_vols = Vec::with_capacity(n);
-    let mut ask_vols = Vec::with_capacity(n);
-    let mut num_trades = Vec::with_capacity(n);
-
-    for i in 0..n {
-        let tick = scid.tick(i);
-        timestamps.push(tick.timestamp_us as f64 / 1_000_000.0);
-        prices.push(tick.price);
-        bids.push(tick.bid);
-        asks.push(tick.ask);
-        volumes.push(tick.volume as f64);
-        bid_vols.push(tick.bid_volume as f64);
-        ask_vols.push(tick.ask_volume as f64);
-        num_trades.push(tick.num_trades as f64);
+use pyo3::types::{PyBool, PyDict, PyList, PyTuple};
+use rayon::prelude::*;
+
+use bar::{BarInterval, BarOptions, BarType};
+use columns::TickColumns;
+use datasource::DataSource;
+use position::SlippageModel;
+use scid::{ScidFile, SkipStats, Tick, TickOrder};
+
+/// Load raw ticks from an SCID file. Returns a dict of numpy arrays; price is the record
+/// close and open the record open (0 for plain trade records). trade_type decodes the
+/// open: 0 a single trade, 1 and 2 the first and last sub-trades of an unbundled trade,
+/// 3 an aggregated record.
+/// All records are returned except out-of-order ones; skipped_records counts those the
+/// backtests would drop.
+/// strict: raise ValueError if more than max_skipped_fraction of records are invalid
+/// order: records earlier than one before them (e.g. from concatenated downloads) are
+///     dropped with "skip", raise ValueError with "error", or are returned in timestamp
+///     order with "sort"
+/// max_decompressed_size: gzip or zstd compressed files are decompressed into memory;
+///     raise IOError rather than decompress more than this many bytes
+/// start, end: only the records timestamped in [start, end), in Unix seconds (None = no
+///     bound), located by binary search in a time-ordered file and by a scan otherwise
+#[pyfunction]
+#[pyo3(signature = (path, strict=false, max_skipped_fraction=0.01, order="skip", max_decompressed_size=scid::DEFAULT_MAX_DECOMPRESSED_SIZE, start=None, end=None))]
+#[allow(clippy::too_many_arguments)]
+fn load_scid(
+    py: Python<'_>,
+    path: &str,
+    strict: bool,
+    max_skipped_fraction: f64,
+    order: &str,
+    max_decompressed_size: usize,
+    start: Option<f64>,
+    end: Option<f64>,
+) -> PyResult<Py<PyDict>> {
+    let limit = skip_limit(strict, max_skipped_fraction)?;
+    let order = tick_order(order)?;
+    let window = window_us(start, end)?;
+    let source = py
+        .detach(|| {
+            ScidFile::open_with_limit(path, max_decompressed_size)
+                .map(|scid| tick_window(scid, window))
+        })
+        .map_err(pyo3::exceptions::PyIOError::new_err)?;
+    scid_dict(py, &source, limit, order)
+}
+
+/// Load only the raw ticks timestamped in [start_unix_secs, end_unix_secs), located by
+/// binary search so the rest of the file is never read. Same columns as load_scid.
+#[pyfunction]
+#[pyo3(signature = (path, start_unix_secs, end_unix_secs, strict=false, max_skipped_fraction=0.01))]
+fn load_scid_range(
+    py: Python<'_>,
+    path: &str,
+    start_unix_secs: f64,
+    end_unix_secs: f64,
+    strict: bool,
+    max_skipped_fraction: f64,
+) -> PyResult<Py<PyDict>> {
+    let limit = skip_limit(strict, max_skipped_fraction)?;
+    let (start_us, end_us) = range_us(start_unix_secs, end_unix_secs)?;
+    let scid = ScidFile::open_range(path, start_us, end_us)
+        .map_err(pyo3::exceptions::PyIOError::new_err)?;
+    scid_dict(py, &scid, limit, TickOrder::Skip)
+}
+
+/// Load the raw ticks of several SCID files (e.g. one per day or month) as one series,
+/// in the order given. Same columns as load_scid; raises ValueError if a file starts
+/// before the previous one ends.
+#[pyfunction]
+#[pyo3(signature = (paths, strict=false, max_skipped_fraction=0.01, order="skip"))]
+fn load_scid_multi(
+    py: Python<'_>,
+    paths: Vec<PathBuf>,
+    strict: bool,
+    max_skipped_fraction: f64,
+    order: &str,
+) -> PyResult<Py<PyDict>> {
+    let limit = skip_limit(strict, max_skipped_fraction)?;
+    let order = tick_order(order)?;
+    let scid = open_multi(py, &paths)?;
+    scid_dict(py, &scid, limit, order)
+}
+
+/// Open `paths` and concatenate them into one in-memory SCID file.
+fn open_multi(py: Python<'_>, paths: &[PathBuf]) -> PyResult<ScidFile> {
+    let files = py
+        .detach(|| {
+            paths
+                .iter()
+                .map(ScidFile::open)
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .map_err(pyo3::exceptions::PyIOError::new_err)?;
+    py.detach(|| ScidFile::concat(files))
+        .map_err(pyo3::exceptions::PyValueError::new_err)
+}
+
+/// The optional `start` and `end` arguments as a [start, end) range in microseconds, or
+/// None when neither is given.
+fn window_us(start: Option<f64>, end: Option<f64>) -> PyResult<Option<(i64, i64)>> {
+    if start.is_none() && end.is_none() {
+        return Ok(None);
+    }
+    range_us(start.unwrap_or(f64::MIN), end.unwrap_or(f64::MAX)).map(Some)
+}
+
+/// The ticks of `scid` in `window` (None = all): a binary-searched view of a time-ordered
+/// file, otherwise a copy of the ticks in range, in file order.
+fn tick_window(scid: ScidFile, window: Option<(i64, i64)>) -> Box<dyn DataSource + Send> {
+    match window {
+        None => Box::new(scid),
+        Some((start, end)) if scid.is_sorted() => Box::new(scid.narrow_to(start, end)),
+        Some((start, end)) => Box::new(
+            scid.iter()
+                .filter(|t| (start..end).contains(&t.timestamp_us))
+                .collect::<Vec<Tick>>(),
+        ),
+    }
+}
+
+/// Convert a [start, end) range in Unix seconds to microseconds.
+fn range_us(start_unix_secs: f64, end_unix_secs: f64) -> PyResult<(i64, i64)> {
+    if !(start_unix_secs.is_finite() && end_unix_secs.is_finite()) {
+        return Err(pyo3::exceptions::PyValueError::new_err(
+            "range bounds must be finite",
+        ));
+    }
+    if start_unix_secs > end_unix_secs {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "range start {start_unix_secs} is after end {end_unix_secs}"
+        )));
     }
+    let to_us = |secs: f64| (secs * 1_000_000.0).round() as i64;
+    Ok((to_us(start_unix_secs), to_us(end_unix_secs)))
+}
+
+fn tick_order(order: &str) -> PyResult<TickOrder> {
+    order
+        .parse::<TickOrder>()
+        .map_err(pyo3::exceptions::PyValueError::new_err)
+}
+
+fn scid_dict(
+    py: Python<'_>,
+    scid: &dyn DataSource,
+    limit: Option<f64>,
+    order: TickOrder,
+) -> PyResult<Py<PyDict>> {
+    let n = scid.num_ticks();
+    // Nothing below touches Python objects until the arrays are built
+    if order == TickOrder::Error {
+        py.detach(|| scid.check_order())
+            .map_err(pyo3::exceptions::PyValueError::new_err)?;
+    }
+    let sorted = py
+        .detach(|| (order == TickOrder::Sort && !scid.is_sorted()).then(|| scid.sorted_indices()));
+    let mut skipped = py.detach(|| scid.skip_stats());
+
+    let columns = py.detach(|| {
+        let indices: Box<dyn Iterator<Item = usize>> = match sorted {
+            Some(sorted) => Box::new(sorted.into_iter()),
+            None => Box::new(0..n),
+        };
+        let mut columns = TickColumns::with_capacity(n);
+        let mut last_us = i64::MIN;
+        for i in indices {
+            let tick = scid.tick(i);
+            if tick.is_valid() {
+                if tick.timestamp_us < last_us {
+                    skipped.out_of_order += 1;
+                    continue;
+                }
+                last_us = tick.timestamp_us;
+            }
+            columns.push(&tick);
+        }
+        columns
+    });
+    engine::check_skipped(&skipped, n, limit)?;
+
+    let d = columns.into_pydict(py)?;
+    d.set_item("num_records", n)?;
+    d.set_item("skipped_records", skip_stats_to_dict(py, &skipped)?)?;
+
+    Ok(d.into())
+}
+
+/// Validate the strict-mode options and return the skipped-fraction limit, if any.
+fn skip_limit(strict: bool, max_skipped_fraction: f64) -> PyResult<Option<f64>> {
+    if !(0.0..=1.0).contains(&max_skipped_fraction) {
+        return Err(pyo3::exceptions::PyValueError::new_err(
+            "max_skipped_fraction must be in [0, 1]",
+        ));
+    }
+    Ok(strict.then_some(max_skipped_fraction))
+}
+
+/// Validate the downsample_equity option.
+fn equity_points(downsample_equity: Option<usize>) -> PyResult<Option<usize>> {
+    match downsample_equity {
+        Some(n) if n < 4 => Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "downsample_equity must be at least 4, got {n}"
+        ))),
+        _ => Ok(downsample_equity),
+    }
+}
+
+fn skip_stats_to_dict<'py>(py: Python<'py>, s: &SkipStats) -> PyResult<Bound<'py, PyDict>> {
+    let d = PyDict::new(py);
+    d.set_item("non_positive_price", s.non_positive_price)?;
+    d.set_item("non_finite", s.non_finite)?;
+    d.set_item("placeholder", s.placeholder)?;
+    d.set_item("timestamp_out_of_range", s.timestamp_out_of_range)?;
+    d.set_item("out_of_order", s.out_of_order)?;
+    d.set_item("total", s.total())?;
+    Ok(d)
+}
+
+/// Load a Sierra Chart market depth (.depth) file as top-of-book snapshots, one per
+/// batch of book updates. Returns a dict with timestamp and bid_price_N, bid_size_N,
+/// ask_price_N, ask_size_N for N in 1..=levels (NaN where the book is thinner).
+#[pyfunction]
+#[pyo3(signature = (path, levels=1))]
+fn load_depth(py: Python<'_>, path: &str, levels: usize) -> PyResult<Py<PyDict>> {
+    let snapshots = load_depth_snapshots(path, levels)?;
+    let n = snapshots.len();
+    let timestamps: Vec<f64> = snapshots
+        .timestamps_us
+        .iter()
+        .map(|&ts| ts as f64 / 1_000_000.0)
+        .collect();
 
     let d = PyDict::new(py);
     d.set_item("timestamp", PyArray1::from_vec(py, timestamps))?;
-    d.set_item("price", PyArray1::from_vec(py, prices))?;
-    d.set_item("bid", PyArray1::from_vec(py, bids))?;
-    d.set_item("ask", PyArray1::from_vec(py, asks))?;
-    d.set_item("volume", PyArray1::from_vec(py, volumes))?;
-    d.set_item("bid_volume", PyArray1::from_vec(py, bid_vols))?;
-    d.set_item("ask_volume", PyArray1::from_vec(py, ask_vols))?;
-    d.set_item("num_trades", PyArray1::from_vec(py, num_trades))?;
-    d.set_item("num_records", n)?;
+    let rows: Vec<Option<usize>> = (0..n).map(Some).collect();
+    engine::set_depth_columns(py, &d, &snapshots, &rows)?;
+    d.set_item("num_snapshots", n)?;
+    Ok(d.into())
+}
+
+fn load_depth_snapshots(path: &str, levels: usize) -> PyResult<depth::DepthSnapshots> {
+    if levels == 0 {
+        return Err(pyo3::exceptions::PyValueError::new_err(
+            "depth levels must be at least 1",
+        ));
+    }
+    let file = depth::DepthFile::open(path).map_err(pyo3::exceptions::PyIOError::new_err)?;
+    Ok(file.snapshots(levels))
+}
+
+/// Check an SCID file for out-of-order and duplicated timestamps and time gaps longer
+/// than gap_threshold_secs. Returns a dict; the file is not modified.
+#[pyfunction]
+#[pyo3(signature = (path, gap_threshold_secs=3600.0))]
+fn validate_scid(py: Python<'_>, path: &str, gap_threshold_secs: f64) -> PyResult<Py<PyDict>> {
+    let report = py
+        .detach(|| -> Result<_, String> {
+            let scid = ScidFile::open(path)?;
+            Ok(scid.validate((gap_threshold_secs * 1_000_000.0) as i64))
+        })
+        .map_err(pyo3::exceptions::PyIOError::new_err)?;
 
+    let (gap_indices, gap_secs): (Vec<u64>, Vec<f64>) = report
+        .gaps
+        .iter()
+        .map(|&(i, us)| (i as u64, us as f64 / 1_000_000.0))
+        .unzip();
+
+    let d = PyDict::new(py);
+    d.set_item("num_records", report.num_records)?;
+    d.set_item("non_monotonic_count", report.non_monotonic_count)?;
+    d.set_item(
+        "first_non_monotonic_index",
+        report.first_non_monotonic_index,
+    )?;
+    d.set_item("duplicate_count", report.duplicate_count)?;
+    let secs = |us: Option<i64>| us.map(|us| us as f64 / 1_000_000.0);
+    d.set_item("min_timestamp", secs(report.min_timestamp_us))?;
+    d.set_item("max_timestamp", secs(report.max_timestamp_us))?;
+    d.set_item("num_gaps", report.gaps.len())?;
+    d.set_item("gap_indices", PyArray1::from_vec(py, gap_indices))?;
+    d.set_item("gap_secs", PyArray1::from_vec(py, gap_secs))?;
     Ok(d.into())
 }
 
-/// Load SCID data aggregated into bars. Returns dict of numpy arrays.
+/// Check an SCID file's layout (magic bytes, header and record sizes, whole records) and
+/// its records (timestamps in order, positive prices). Returns a list of every problem
+/// found, empty for a clean file. Named apart from `validate_scid`, which reports
+/// timestamp order and gaps.
 #[pyfunction]
-fn load_bars(py: Python<'_>, path: &str, interval: &str) -> PyResult<Py<PyDict>> {
-    let scid = ScidFile::open(path).map_err(|e| pyo3::exceptions::PyIOError::new_err(e))?;
-    let bar_interval =
-        BarInterval::from_str(interval).map_err(|e| pyo3::exceptions::PyValueError::new_err(e))?;
-    let bars = aggregate_bars(&scid, bar_interval);
+fn check_scid_integrity(py: Python<'_>, path: &str) -> PyResult<Vec<String>> {
+    py.detach(|| scid::check_integrity(path))
+        .map_err(pyo3::exceptions::PyIOError::new_err)
+}
+
+/// Header fields and the detected record DateTime layout of an SCID file:
+/// datetime_layout is "microseconds" (SCDateTimeMS int64) or "days" (older files' double
+/// days since 1899-12-30); both are read as Unix time.
+#[pyfunction]
+fn scid_info(py: Python<'_>, path: &str) -> PyResult<Py<PyDict>> {
+    let scid = ScidFile::open(path).map_err(pyo3::exceptions::PyIOError::new_err)?;
+    let d = PyDict::new(py);
+    d.set_item("num_records", scid.num_records)?;
+    d.set_item("version", scid.version)?;
+    d.set_item("datetime_layout", scid.datetime_layout.as_str())?;
+    Ok(d.into())
+}
 
-    let n = bars.len();
-    let mut timestamps = Vec::with_capacity(n);
-    let mut opens = Vec::with_capacity(n);
-    let mut highs = Vec::with_capacity(n);
-    let mut lows = Vec::with_capacity(n);
-    let mut closes = Vec::with_capacity(n);
-    let mut volumes = Vec::with_capacity(n);
-    let mut bid_vols = Vec::with_capacity(n);
-    let mut ask_vols = Vec::with_capacity(n);
+/// Read the header of an SCID file: version, header_size, record_size and
+/// utc_start_index. Only the header is read and its sizes are not checked, so files that
+/// load_scid rejects can still be inspected.
+#[pyfunction]
+fn scid_header(py: Python<'_>, path: &str) -> PyResult<Py<PyDict>> {
+    let header = scid::ScidHeader::read(path).map_err(pyo3::exceptions::PyIOError::new_err)?;
+    let d = PyDict::new(py);
+    d.set_item("version", header.version)?;
+    d.set_item("header_size", header.header_size)?;
+    d.set_item("record_size", header.record_size)?;
+    d.set_item("utc_start_index", header.utc_start_index)?;
+    Ok(d.into())
+}
 
-    for bar in &bars {
-        timestamps.push(bar.timestamp_us as f64 / 1_000_000.0);
-        opens.push(bar.open);
-        highs.push(bar.high);
-        lows.push(bar.low);
-        closes.push(bar.close);
-        volumes.push(bar.volume as f64);
-        bid_vols.push(bar.bid_volume as f64);
-        ask_vols.push(bar.ask_volume as f64);
+/// Replay a SCID file as an iterator of tick dicts, or of completed bar dicts when
+/// interval is given, sleeping between items by the original gaps divided by speed
+/// (0 = as fast as possible). Sleeps release the GIL and are interruptible with Ctrl-C.
+/// start_time: Unix seconds to start from (first record at or after it)
+#[pyfunction(name = "replay")]
+#[pyo3(signature = (path, speed=1.0, interval=None, start_time=None))]
+fn replay_scid(
+    path: &str,
+    speed: f64,
+    interval: Option<&str>,
+    start_time: Option<f64>,
+) -> PyResult<replay::Replay> {
+    let scid = ScidFile::open(path).map_err(pyo3::exceptions::PyIOError::new_err)?;
+    let interval = interval
+        .map(str::parse::<bar::BarInterval>)
+        .transpose()
+        .map_err(pyo3::exceptions::PyValueError::new_err)?;
+    let start_us = start_time.map(|secs| (secs * 1_000_000.0).round() as i64);
+    replay::Replay::new(scid, speed, interval, start_us)
+        .map_err(pyo3::exceptions::PyValueError::new_err)
+}
+
+/// Write tick arrays to a SCID file. Timestamps are Unix seconds (as returned by load_scid).
+/// Optional columns default to: bid/ask = price, bid_volume/ask_volume = 0, num_trades = 1,
+/// open = 0.
+/// append: extend an existing file instead of replacing it (new ticks must not predate it)
+#[pyfunction]
+#[pyo3(signature = (path, timestamp, price, volume, bid=None, ask=None, bid_volume=None, ask_volume=None, num_trades=None, append=false, open=None))]
+#[allow(clippy::too_many_arguments)]
+fn write_scid(
+    path: &str,
+    timestamp: Vec<f64>,
+    price: Vec<f64>,
+    volume: Vec<f64>,
+    bid: Option<Vec<f64>>,
+    ask: Option<Vec<f64>>,
+    bid_volume: Option<Vec<f64>>,
+    ask_volume: Option<Vec<f64>>,
+    num_trades: Option<Vec<f64>>,
+    append: bool,
+    open: Option<Vec<f64>>,
+) -> PyResult<()> {
+    let n = timestamp.len();
+    let columns = [
+        ("price", Some(&price)),
+        ("volume", Some(&volume)),
+        ("bid", bid.as_ref()),
+        ("ask", ask.as_ref()),
+        ("bid_volume", bid_volume.as_ref()),
+        ("ask_volume", ask_volume.as_ref()),
+        ("num_trades", num_trades.as_ref()),
+        ("open", open.as_ref()),
+    ];
+    for (name, col) in columns {
+        if let Some(col) = col {
+            if col.len() != n {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "{name} length {} != timestamp length {n}",
+                    col.len()
+                )));
+            }
+        }
+    }
+
+    let ticks: Vec<Tick> = (0..n)
+        .map(|i| Tick {
+            timestamp_us: (timestamp[i] * 1_000_000.0).round() as i64,
+            open: open.as_ref().map_or(0.0, |v| v[i]),
+            price: price[i],
+            bid: bid.as_ref().map_or(price[i], |v| v[i]),
+            ask: ask.as_ref().map_or(price[i], |v| v[i]),
+            volume: volume[i] as u32,
+            bid_volume: bid_volume.as_ref().map_or(0, |v| v[i] as u32),
+            ask_volume: ask_volume.as_ref().map_or(0, |v| v[i] as u32),
+            num_trades: num_trades.as_ref().map_or(1, |v| v[i] as u32),
+        })
+        .collect();
+
+    let written = if append {
+        scid::append_scid(path, &ticks)
+    } else {
+        scid::write_scid(path, &ticks)
+    };
+    written.map_err(pyo3::exceptions::PyIOError::new_err)
+}
+
+/// Parse the Python-facing bar arguments.
+fn bar_options(
+    interval: &str,
+    bar_type: &str,
+    fill_gaps: bool,
+    cache_dir: Option<PathBuf>,
+) -> PyResult<BarOptions> {
+    let interval = interval
+        .parse::<BarInterval>()
+        .map_err(pyo3::exceptions::PyValueError::new_err)?;
+    let mut opts = BarOptions::new(interval);
+    opts.bar_type = bar_type
+        .parse::<BarType>()
+        .map_err(pyo3::exceptions::PyValueError::new_err)?;
+    opts.fill_gaps = fill_gaps;
+    opts.cache_dir = cache_dir;
+    Ok(opts)
+}
+
+/// Parse the mutually exclusive tail-window arguments.
+fn bar_tail(
+    last_n_bars: Option<usize>,
+    lookback_days: Option<f64>,
+) -> PyResult<Option<bar::BarTail>> {
+    match (last_n_bars, lookback_days) {
+        (Some(_), Some(_)) => Err(pyo3::exceptions::PyValueError::new_err(
+            "Pass only one of last_n_bars and lookback_days",
+        )),
+        (Some(0), None) => Err(pyo3::exceptions::PyValueError::new_err(
+            "last_n_bars must be positive",
+        )),
+        (Some(n), None) => Ok(Some(bar::BarTail::LastBars(n))),
+        (None, Some(days)) if !(days.is_finite() && days > 0.0) => Err(
+            pyo3::exceptions::PyValueError::new_err("lookback_days must be positive"),
+        ),
+        (None, Some(days)) => Ok(Some(bar::BarTail::LookbackDays(days))),
+        (None, None) => Ok(None),
+    }
+}
+
+/// Build the session clock from the `tz_offset_minutes` and `timezone` arguments.
+fn session_clock(
+    tz_offset_minutes: i64,
+    timezone: Option<&str>,
+) -> PyResult<session::SessionClock> {
+    match timezone {
+        Some(_) if tz_offset_minutes != 0 => Err(pyo3::exceptions::PyValueError::new_err(
+            "Pass only one of tz_offset_minutes and timezone",
+        )),
+        Some(name) => session::parse_timezone(name)
+            .map(session::SessionClock::with_zone)
+            .map_err(pyo3::exceptions::PyValueError::new_err),
+        None => Ok(session::SessionClock::new(tz_offset_minutes * 60)),
+    }
+}
+
+/// Align the bars of `opts` per the `session_offset_secs` and `session_timezone` arguments.
+fn bar_session(
+    opts: &mut BarOptions,
+    session_offset_secs: i64,
+    session_timezone: Option<&str>,
+) -> PyResult<()> {
+    let clock = match session_timezone {
+        Some(name) => session::parse_timezone(name)
+            .map(session::SessionClock::with_zone)
+            .map_err(pyo3::exceptions::PyValueError::new_err)?,
+        None => session::SessionClock::default(),
+    };
+    opts.interval = opts.interval.with_session(session_offset_secs, clock);
+    Ok(())
+}
+
+/// Parse the `benchmark` argument: a bool or a side of 1 (long) or -1 (short).
+fn benchmark_side(spec: Option<&Bound<'_, PyAny>>) -> PyResult<Option<position::Side>> {
+    let Some(spec) = spec else {
+        return Ok(None);
+    };
+    if let Ok(flag) = spec.cast::<PyBool>() {
+        return Ok(flag.is_true().then_some(position::Side::Long));
+    }
+    match spec.extract::<i64>() {
+        Ok(1) => Ok(Some(position::Side::Long)),
+        Ok(-1) => Ok(Some(position::Side::Short)),
+        _ => Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "benchmark must be True, False, 1 or -1, got {spec}"
+        ))),
+    }
+}
+
+/// Parse the `slippage` argument: a number of ticks or a (model, *params) tuple.
+fn slippage_model(spec: Option<&Bound<'_, PyAny>>) -> PyResult<SlippageModel> {
+    let Some(spec) = spec else {
+        return Ok(SlippageModel::default());
+    };
+    if let Ok(ticks) = spec.extract::<f64>() {
+        return SlippageModel::from_parts("fixed", &[ticks])
+            .map_err(pyo3::exceptions::PyValueError::new_err);
+    }
+    let tuple = spec.cast::<PyTuple>().map_err(|_| {
+        pyo3::exceptions::PyTypeError::new_err("slippage must be a number or a tuple")
+    })?;
+    if tuple.is_empty() {
+        return Err(pyo3::exceptions::PyValueError::new_err(
+            "slippage tuple must start with a model name",
+        ));
+    }
+    let name: String = tuple.get_item(0)?.extract()?;
+    let params = tuple
+        .iter()
+        .skip(1)
+        .map(|p| p.extract::<f64>())
+        .collect::<PyResult<Vec<f64>>>()?;
+    SlippageModel::from_parts(&name, &params).map_err(pyo3::exceptions::PyValueError::new_err)
+}
+
+/// Load SCID data aggregated into bars. Returns dict of numpy arrays, including order-flow
+/// columns delta (ask - bid volume), delta_high/delta_low (intrabar running-delta extremes)
+/// and cum_delta (running sum of delta across bars), plus tick_count (records aggregated
+/// into each bar, as opposed to the exchange's trade count).
+/// bar_type: "standard" or "heikin_ashi"
+/// fill_gaps: insert flat zero-volume bars (previous close) for intervals with no ticks,
+///     flagged in the is_synthetic column
+/// max_gap_bars: gaps of more empty intervals than this are left unfilled, with a warning
+/// strict: raise ValueError if more than max_skipped_fraction of records are invalid
+/// cache_dir: directory for cached aggregated bars, reused while the file is unchanged
+///     and extended when it has grown
+/// last_n_bars: only the most recent last_n_bars bars
+/// lookback_days: only bars opening within this many days of the last tick
+///     (bars_truncated in the result is True when either option dropped earlier data)
+/// order: ticks earlier than one before them are dropped and counted in
+///     skipped_records["out_of_order"] with "skip", raise with "error", or are
+///     aggregated in timestamp order with "sort" (the bar cache is then not used)
+/// max_decompressed_size: as for load_scid
+/// unbundle: how the records of an unbundled trade (a first through a last sub-trade
+///     record, see load_scid's trade_type) count towards volume and num_trades: "ignore"
+///     counts every record, "first" or "last" only that record, "sum" the summed volume
+///     as one trade. Prices use every record; the bar cache is only used with "ignore"
+/// start, end: only aggregate the records in [start, end), as for load_scid; the bar
+///     cache is not used then
+/// session_offset_secs: start bars this many seconds after midnight instead of at the
+///     interval's multiples of UTC midnight, e.g. 82800 for daily bars from 17:00 CST
+/// session_timezone: IANA zone name whose local midnight session_offset_secs counts
+///     from, following daylight saving time (e.g. "America/Chicago" with 61200 for
+///     17:00 to 17:00 CME days). The bar cache is only used for UTC-aligned bars
+#[pyfunction]
+#[pyo3(signature = (path, interval, bar_type="standard", fill_gaps=false, strict=false, max_skipped_fraction=0.01, cache_dir=None, last_n_bars=None, lookback_days=None, max_gap_bars=bar::DEFAULT_MAX_GAP_BARS, order="skip", max_decompressed_size=scid::DEFAULT_MAX_DECOMPRESSED_SIZE, unbundle="ignore", session_offset_secs=0, session_timezone=None, start=None, end=None))]
+#[allow(clippy::too_many_arguments)]
+fn load_bars(
+    py: Python<'_>,
+    path: &str,
+    interval: &str,
+    bar_type: &str,
+    fill_gaps: bool,
+    strict: bool,
+    max_skipped_fraction: f64,
+    cache_dir: Option<PathBuf>,
+    last_n_bars: Option<usize>,
+    lookback_days: Option<f64>,
+    max_gap_bars: usize,
+    order: &str,
+    max_decompressed_size: usize,
+    unbundle: &str,
+    session_offset_secs: i64,
+    session_timezone: Option<&str>,
+    start: Option<f64>,
+    end: Option<f64>,
+) -> PyResult<Py<PyDict>> {
+    let limit = skip_limit(strict, max_skipped_fraction)?;
+    let window = window_us(start, end)?;
+    let mut opts = bar_options(interval, bar_type, fill_gaps, cache_dir)?;
+    opts.tail = bar_tail(last_n_bars, lookback_days)?;
+    opts.max_gap_bars = max_gap_bars;
+    opts.order = tick_order(order)?;
+    opts.unbundle = unbundle
+        .parse()
+        .map_err(pyo3::exceptions::PyValueError::new_err)?;
+    bar_session(&mut opts, session_offset_secs, session_timezone)?;
+    if window.is_some() {
+        // The cache holds bars of the whole file
+        opts.cache_dir = None;
     }
+    let open = || {
+        ScidFile::open_with_limit(path, max_decompressed_size).map(|scid| tick_window(scid, window))
+    };
+    bars_dict(py, Some(path), open, &opts, limit)
+}
+
+/// Load bars built only from the ticks timestamped in [start_unix_secs,
+/// end_unix_secs), located by binary search. Same columns as load_bars; no bar cache.
+#[pyfunction]
+#[pyo3(signature = (path, interval, start_unix_secs, end_unix_secs, bar_type="standard", fill_gaps=false, strict=false, max_skipped_fraction=0.01, max_gap_bars=bar::DEFAULT_MAX_GAP_BARS))]
+#[allow(clippy::too_many_arguments)]
+fn load_bars_range(
+    py: Python<'_>,
+    path: &str,
+    interval: &str,
+    start_unix_secs: f64,
+    end_unix_secs: f64,
+    bar_type: &str,
+    fill_gaps: bool,
+    strict: bool,
+    max_skipped_fraction: f64,
+    max_gap_bars: usize,
+) -> PyResult<Py<PyDict>> {
+    let limit = skip_limit(strict, max_skipped_fraction)?;
+    let (start_us, end_us) = range_us(start_unix_secs, end_unix_secs)?;
+    let mut opts = bar_options(interval, bar_type, fill_gaps, None)?;
+    opts.max_gap_bars = max_gap_bars;
+    let open = || ScidFile::open_range(path, start_us, end_us);
+    bars_dict(py, Some(path), open, &opts, limit)
+}
+
+/// Load bars built from several SCID files (e.g. one per day or month) as one series, in
+/// the order given. Same columns as load_bars; no bar cache. Raises ValueError if a file
+/// starts before the previous one ends.
+#[pyfunction]
+#[pyo3(signature = (paths, interval, bar_type="standard", fill_gaps=false, strict=false, max_skipped_fraction=0.01, max_gap_bars=bar::DEFAULT_MAX_GAP_BARS, order="skip"))]
+#[allow(clippy::too_many_arguments)]
+fn load_bars_multi(
+    py: Python<'_>,
+    paths: Vec<PathBuf>,
+    interval: &str,
+    bar_type: &str,
+    fill_gaps: bool,
+    strict: bool,
+    max_skipped_fraction: f64,
+    max_gap_bars: usize,
+    order: &str,
+) -> PyResult<Py<PyDict>> {
+    let limit = skip_limit(strict, max_skipped_fraction)?;
+    let mut opts = bar_options(interval, bar_type, fill_gaps, None)?;
+    opts.max_gap_bars = max_gap_bars;
+    opts.order = tick_order(order)?;
+    let scid = open_multi(py, &paths)?;
+    bars_dict(py, None, || Ok(scid), &opts, limit)
+}
+
+/// Load bars of several intervals (e.g. ["1m", "5m", "15m"]) from one scan of a SCID
+/// file. Returns a dict keyed by interval string, each value with the load_bars columns
+/// and skipped_records. Bars of every interval start on the same boundaries, so each
+/// coarser bar closes together with a finer one. No bar cache. (Not `load_bars_multi`,
+/// which loads bars across several files.)
+#[pyfunction]
+#[pyo3(signature = (path, intervals, bar_type="standard", fill_gaps=false, strict=false, max_skipped_fraction=0.01, max_gap_bars=bar::DEFAULT_MAX_GAP_BARS, unbundle="ignore"))]
+#[allow(clippy::too_many_arguments)]
+fn load_bars_intervals(
+    py: Python<'_>,
+    path: &str,
+    intervals: Vec<String>,
+    bar_type: &str,
+    fill_gaps: bool,
+    strict: bool,
+    max_skipped_fraction: f64,
+    max_gap_bars: usize,
+    unbundle: &str,
+) -> PyResult<Py<PyDict>> {
+    let limit = skip_limit(strict, max_skipped_fraction)?;
+    let parsed = intervals
+        .iter()
+        .map(|s| s.parse::<BarInterval>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(pyo3::exceptions::PyValueError::new_err)?;
+    let bar_type = bar_type
+        .parse::<BarType>()
+        .map_err(pyo3::exceptions::PyValueError::new_err)?;
+    let unbundle = unbundle
+        .parse()
+        .map_err(pyo3::exceptions::PyValueError::new_err)?;
+    let (all_series, num_records) = py
+        .detach(|| -> Result<_, String> {
+            let scid = ScidFile::open(path)?;
+            let (all_bars, skipped) = bar::aggregate_bars_multi(&scid, &parsed, unbundle);
+            let all_series: Vec<bar::BarSeries> = all_bars
+                .into_iter()
+                .zip(&parsed)
+                .map(|(bars, &interval)| {
+                    let (bars, unfilled_gaps) = if fill_gaps {
+                        bar::fill_gaps(&bars, interval, max_gap_bars)
+                    } else {
+                        (bars, 0)
+                    };
+                    bar::BarSeries {
+                        bars,
+                        skipped,
+                        num_records: scid.num_records,
+                        truncated: false,
+                        unfilled_gaps,
+                    }
+                })
+                .collect();
+            Ok((all_series, scid.num_records))
+        })
+        .map_err(pyo3::exceptions::PyIOError::new_err)?;
 
     let d = PyDict::new(py);
-    d.set_item("timestamp", PyArray1::from_vec(py, timestamps))?;
-    d.set_item("open", PyArray1::from_vec(py, opens))?;
-    d.set_item("high", PyArray1::from_vec(py, highs))?;
-    d.set_item("low", PyArray1::from_vec(py, lows))?;
-    d.set_item("close", PyArray1::from_vec(py, closes))?;
-    d.set_item("volume", PyArray1::from_vec(py, volumes))?;
-    d.set_item("bid_volume", PyArray1::from_vec(py, bid_vols))?;
-    d.set_item("ask_volume", PyArray1::from_vec(py, ask_vols))?;
-    d.set_item("num_bars", n)?;
+    for (name, series) in intervals.iter().zip(&all_series) {
+        engine::check_skipped(&series.skipped, num_records, limit)?;
+        engine::warn_unfilled_gaps(py, series, max_gap_bars)?;
+        let bars = py.detach(|| bar_type.apply(&series.bars));
+        let bd = py.detach(|| bar::bar_columns(&bars)).into_pydict(py)?;
+        bd.set_item("skipped_records", skip_stats_to_dict(py, &series.skipped)?)?;
+        d.set_item(name, bd)?;
+    }
+    Ok(d.into())
+}
+
+/// Load the raw ticks of several SCID files merged into timestamp order, for files that
+/// may overlap (e.g. repeated downloads). Where they do, the first file listed that holds
+/// a timestamp provides its ticks. Same columns as load_scid.
+#[pyfunction]
+#[pyo3(signature = (paths, strict=false, max_skipped_fraction=0.01))]
+fn load_scid_batch(
+    py: Python<'_>,
+    paths: Vec<PathBuf>,
+    strict: bool,
+    max_skipped_fraction: f64,
+) -> PyResult<Py<PyDict>> {
+    let limit = skip_limit(strict, max_skipped_fraction)?;
+    let ticks = py
+        .detach(|| {
+            paths
+                .par_iter()
+                .map(|path| ScidFile::open(path).map(|scid| scid.iter().collect::<Vec<Tick>>()))
+                .collect::<Result<Vec<_>, _>>()
+                .map(scid::merge_tick_vecs)
+        })
+        .map_err(pyo3::exceptions::PyIOError::new_err)?;
+    scid_dict(py, &ticks, limit, TickOrder::Skip)
+}
+
+/// Load bars from several SCID files, aggregated per file in parallel and merged into
+/// timestamp order. Where files overlap, the bar from the first file listed is kept for
+/// each timestamp. Same columns as load_bars; gaps are filled after merging. No bar cache.
+#[pyfunction]
+#[pyo3(signature = (paths, interval, bar_type="standard", fill_gaps=false, strict=false, max_skipped_fraction=0.01, max_gap_bars=bar::DEFAULT_MAX_GAP_BARS))]
+#[allow(clippy::too_many_arguments)]
+fn load_bars_batch(
+    py: Python<'_>,
+    paths: Vec<PathBuf>,
+    interval: &str,
+    bar_type: &str,
+    fill_gaps: bool,
+    strict: bool,
+    max_skipped_fraction: f64,
+    max_gap_bars: usize,
+) -> PyResult<Py<PyDict>> {
+    let limit = skip_limit(strict, max_skipped_fraction)?;
+    let opts = bar_options(interval, bar_type, false, None)?;
+    let per_file = py
+        .detach(|| {
+            paths
+                .par_iter()
+                .map(|path| opts.build(&ScidFile::open(path)?, Some(path)))
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .map_err(pyo3::exceptions::PyIOError::new_err)?;
+    let skipped = per_file
+        .iter()
+        .fold(SkipStats::default(), |total, s| total + s.skipped);
+    let num_records = per_file.iter().map(|s| s.num_records).sum();
+    engine::check_skipped(&skipped, num_records, limit)?;
+    let mut series = bar::BarSeries {
+        bars: py.detach(|| bar::merge_bar_vecs(per_file.into_iter().map(|s| s.bars).collect())),
+        skipped,
+        num_records,
+        truncated: false,
+        unfilled_gaps: 0,
+    };
+    if fill_gaps {
+        (series.bars, series.unfilled_gaps) =
+            py.detach(|| bar::fill_gaps(&series.bars, opts.interval, max_gap_bars));
+        engine::warn_unfilled_gaps(py, &series, max_gap_bars)?;
+    }
+    let bars = py.detach(|| opts.bar_type.apply(&series.bars));
+
+    let d = py.detach(|| bar::bar_columns(&bars)).into_pydict(py)?;
+    d.set_item("skipped_records", skip_stats_to_dict(py, &skipped)?)?;
+    d.set_item("bars_truncated", false)?;
+    Ok(d.into())
+}
+
+/// Load ticks from a CSV file with one trade per row, for data from outside Sierra Chart.
+/// The named columns hold Unix seconds or ISO-8601 date-times, price and volume. Returns
+/// the load_scid columns, in file order; bid and ask equal the price and bid_volume and
+/// ask_volume are 0.
+#[pyfunction]
+fn load_csv(
+    py: Python<'_>,
+    path: PathBuf,
+    timestamp_col: &str,
+    price_col: &str,
+    volume_col: &str,
+) -> PyResult<Py<PyDict>> {
+    let ticks = py
+        .detach(|| csv_loader::load_csv_ticks(&path, timestamp_col, price_col, volume_col))
+        .map_err(pyo3::exceptions::PyValueError::new_err)?;
+    let n = ticks.len();
+    let d = py
+        .detach(|| ticks.into_iter().collect::<TickColumns>())
+        .into_pydict(py)?;
+    d.set_item("num_records", n)?;
+    Ok(d.into())
+}
+
+/// Generate n ticks of a seeded random walk for testing strategies without data files.
+/// Ticks are one second apart from 2024-01-02 00:00 UTC, starting at start_price, with
+/// per-tick log returns of standard deviation volatility. Returns the load_scid columns;
+/// the same seed gives the same ticks.
+#[pyfunction]
+#[pyo3(signature = (n, start_price=100.0, volatility=0.0001, seed=0))]
+fn generate_synthetic_ticks(
+    py: Python<'_>,
+    n: usize,
+    start_price: f64,
+    volatility: f64,
+    seed: u64,
+) -> PyResult<Py<PyDict>> {
+    if !(start_price.is_finite() && start_price > 0.0) {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "start_price must be > 0, got {start_price}"
+        )));
+    }
+    if !(volatility.is_finite() && volatility >= 0.0) {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "volatility must be >= 0, got {volatility}"
+        )));
+    }
+    let source = py.detach(|| {
+        datasource::InMemoryDataSource::from_synthetic(n, start_price, volatility, seed)
+    });
+    let d = py
+        .detach(|| source.iter().collect::<TickColumns>())
+        .into_pydict(py)?;
+    d.set_item("num_records", n)?;
+    Ok(d.into())
+}
+
+/// Load bars aggregated from a CSV tick file (see load_csv). Rows are put in timestamp
+/// order first. Same columns as load_bars; no bar cache.
+#[pyfunction]
+#[pyo3(signature = (path, timestamp_col, price_col, volume_col, interval, bar_type="standard", fill_gaps=false, max_gap_bars=bar::DEFAULT_MAX_GAP_BARS))]
+#[allow(clippy::too_many_arguments)]
+fn load_csv_bars(
+    py: Python<'_>,
+    path: PathBuf,
+    timestamp_col: &str,
+    price_col: &str,
+    volume_col: &str,
+    interval: &str,
+    bar_type: &str,
+    fill_gaps: bool,
+    max_gap_bars: usize,
+) -> PyResult<Py<PyDict>> {
+    let mut opts = bar_options(interval, bar_type, fill_gaps, None)?;
+    opts.max_gap_bars = max_gap_bars;
+    let ticks = py
+        .detach(|| {
+            let mut ticks =
+                csv_loader::load_csv_ticks(&path, timestamp_col, price_col, volume_col)?;
+            ticks.sort_by_key(|t| t.timestamp_us);
+            Ok::<_, String>(ticks)
+        })
+        .map_err(pyo3::exceptions::PyValueError::new_err)?;
+    bars_dict(py, None, || Ok(ticks), &opts, None)
+}
+
+/// Load ticks from a Parquet file with columns timestamp_us (Unix microseconds), price,
+/// bid, ask, volume, bid_volume and ask_volume. Returns the load_scid columns, in file
+/// order.
+#[pyfunction]
+fn load_parquet(py: Python<'_>, path: PathBuf) -> PyResult<Py<PyDict>> {
+    let loader = py
+        .detach(|| parquet_loader::ParquetLoader::open(&path))
+        .map_err(pyo3::exceptions::PyValueError::new_err)?;
+    let n = loader.ticks.len();
+    let d = py
+        .detach(|| loader.ticks.into_iter().collect::<TickColumns>())
+        .into_pydict(py)?;
+    d.set_item("num_records", n)?;
+    Ok(d.into())
+}
+
+/// Load bars aggregated from a Parquet tick file (see load_parquet). Same columns as
+/// load_bars; no bar cache. order: see load_bars.
+#[pyfunction]
+#[pyo3(signature = (path, interval, bar_type="standard", fill_gaps=false, max_gap_bars=bar::DEFAULT_MAX_GAP_BARS, order="skip"))]
+#[allow(clippy::too_many_arguments)]
+fn load_parquet_bars(
+    py: Python<'_>,
+    path: PathBuf,
+    interval: &str,
+    bar_type: &str,
+    fill_gaps: bool,
+    max_gap_bars: usize,
+    order: &str,
+) -> PyResult<Py<PyDict>> {
+    let mut opts = bar_options(interval, bar_type, fill_gaps, None)?;
+    opts.max_gap_bars = max_gap_bars;
+    opts.order = tick_order(order)?;
+    let loader = py
+        .detach(|| parquet_loader::ParquetLoader::open(&path))
+        .map_err(pyo3::exceptions::PyValueError::new_err)?;
+    bars_dict(py, None, || Ok(loader), &opts, None)
+}
+
+/// Open the tick data with `open` (read from the file at `path`, if any), aggregate it
+/// per `opts` and build the load_bars dict.
+fn bars_dict<S: DataSource>(
+    py: Python<'_>,
+    path: Option<&str>,
+    open: impl FnOnce() -> Result<S, String> + Send,
+    opts: &BarOptions,
+    limit: Option<f64>,
+) -> PyResult<Py<PyDict>> {
+    let max_gap_bars = opts.max_gap_bars;
+    // Scan, aggregate and fill the columns without holding the GIL
+    let series = py
+        .detach(|| opts.build(&open()?, path.map(Path::new)))
+        .map_err(pyo3::exceptions::PyIOError::new_err)?;
+    engine::check_skipped(&series.skipped, series.num_records, limit)?;
+    engine::warn_unfilled_gaps(py, &series, max_gap_bars)?;
+    let skipped = series.skipped;
+    let bars = py.detach(|| opts.bar_type.apply(&series.bars));
+
+    let d = py.detach(|| bar::bar_columns(&bars)).into_pydict(py)?;
+    d.set_item("skipped_records", skip_stats_to_dict(py, &skipped)?)?;
+    d.set_item("bars_truncated", series.truncated)?;
 
     Ok(d.into())
 }
 
 /// Run a bar-based backtest with a Python strategy callback.
 /// point_value: dollar value per 1.0 point move (ES=50, NQ=20)
+/// bar_type: "standard" or "heikin_ashi" (strategy sees HA candles, fills use real prices)
+/// fill_gaps: insert flat zero-volume bars (previous close) for intervals with no ticks
+/// omega_threshold: per-trade PnL threshold for the Omega ratio
+/// initial_capital: account size the equity curve starts from; the base for annualized
+///     return, CAGR, total_return_pct and drawdown percentages
+/// sharpe_annual_factor: periods per year for Sharpe, Sortino and annualized volatility
+/// rolling_window: equity points per window for the rolling_sharpe array
+/// commission_per_side: dollars per contract per side, charged on entry and on exit
+/// quantity: contracts per entry (commission is charged per contract)
+/// direction: "both", "long_only" or "short_only"; signals for a disallowed side mean flat
+/// slippage: ticks per fill, or ("fixed", ticks) / ("volume_scaled", base, ref_volume) /
+///     ("range_fraction", fraction); always against the trader
+/// tick_size: minimum price increment, the unit of tick-based slippage (ES=0.25)
+/// target_ladder: list of (points, fraction) scale-out targets, fractions summing to <= 1
+/// daily_holding_cost: dollars per contract per day boundary a position is held across
+/// rollover_hour: UTC hour at which that day boundary falls
+/// stop_loss: stop distance in points from entry; enables per-trade R-multiples
+/// max_holding_secs: force-close positions held longer than this (None = no limit)
+/// cooldown_secs: ignore entry signals for this long after a stop-loss exit
+/// cooldown_after_any_exit: start the cooldown after every exit, not just stop-losses
+/// flat_by_time: "HH:MM[:SS]" local time at which open positions are closed; no new
+///     entries until the next local day
+/// tz_offset_minutes: local time minus UTC for flat_by_time (e.g. -300 for US/Eastern winter)
+/// strict: raise ValueError if more than max_skipped_fraction of records are invalid
+/// cache_dir: directory for cached aggregated bars (see load_bars)
+/// fill_mode: "current_close" fills at the signal bar's close, "next_open" at the next
+///     bar's open
+/// final_signal: "drop" or "fill_at_close" for the last bar's signal under "next_open" or
+///     an execution delay
+/// benchmark_path: SCID file to compare against (adds information_ratio vs holding
+///     quantity contracts of it)
+/// benchmark_point_value: dollar value per point of the benchmark (default point_value)
+/// risk_free_rate: annual risk-free rate for alpha and the Treynor ratio (benchmark only)
+/// warmup_bars: leading bars passed to the callback but never traded; they are left out of
+///     the equity curve and metrics
+/// last_n_bars, lookback_days: backtest only the end of the file (see load_bars)
+/// max_gap_bars: longest gap fill_gaps fills (see load_bars)
+/// strict_no_lookahead: re-run the callback on bars[:i+1] for every bar and raise
+///     ValueError if the last signal differs from the full-run signal at i (slow; for
+///     auditing strategies during development)
+/// benchmark: True or 1 (long) / -1 (short) to also hold quantity contracts of the traded
+///     instrument from the first to the last bar; adds benchmark_total_pnl,
+///     benchmark_sharpe, benchmark_max_drawdown and benchmark_equity_curve, and measures
+///     beta, alpha, daily_beta, daily_alpha, information_ratio and treynor_ratio against
+///     it (benchmark_path, if also given, takes precedence for those). The benchmark pays
+///     one commission_per_side per contract on entry
+/// min_hold_bars: ignore signal exits and reversals until this many bars after an entry
+///     (stops, time exits and the end-of-data close still apply)
+/// confirm_bars: act on a new signal only after it has held for this many consecutive bars
+/// drawdown_threshold: avg_pnl_after_drawdown averages trades entered more than this many
+///     dollars below the realized-PnL peak
+/// execution_delay_bars: fill the signal of bar i at the open of bar i + execution_delay_bars
+///     (0 = per fill_mode; "next_open" is a delay of 1)
+/// pyramiding: while a position is open, each repeated entry signal adds another quantity
+///     lot at the current price, up to this many lots; exits close lots oldest first with
+///     one trade per lot (1 = a single lot)
+/// pyramid_mode: "lots" keeps each add as its own lot as above; "average" merges adds into
+///     the position at the quantity-weighted average entry price, closed as one trade on
+///     the whole size
+/// save_json: also write metrics, trades and the equity curve to this path as JSON
+/// daily_funding_rate: fraction of the position's notional value (entry price x quantity x
+///     point_value) charged per rollover_hour day boundary it is held across
+/// exclude_end_of_data: leave trades closed because the data ran out (exit_reason
+///     "end_of_data") out of win_rate, num_wins/num_losses, avg/largest win and loss and
+///     profit_factor
+/// include_benchmark: shorthand for benchmark=True (ignored when benchmark is given)
+/// timezone: IANA zone name (e.g. "America/New_York") for flat_by_time instead of
+///     tz_offset_minutes; its UTC offset follows daylight saving time
+/// downsample_equity: return at most this many equity_curve points (at least 4), keeping
+///     the minimum and maximum of each span so drawdowns stay visible. Metrics and the
+///     save_json output use the full curve (None = the full curve)
+/// time_breakdown: add trade count, PnL and win rate by local entry hour (pnl_by_hour,
+///     trades_by_hour, winrate_by_hour; 24 each) and weekday (pnl_by_weekday,
+///     trades_by_weekday, winrate_by_weekday; Monday first), in the tz_offset_minutes or
+///     timezone local time
+/// unbundle: see load_bars
+/// metrics_basis: series behind sharpe_ratio and sortino_ratio: "daily" (percentage
+///     returns of the equity curve per UTC day) or "trade" (per-trade PnL)
+/// session_offset_secs, session_timezone: bar alignment, see load_bars
 #[pyfunction]
-#[pyo3(signature = (path, interval, callback, commission=0.0, point_value=50.0))]
+#[pyo3(signature = (path, interval, callback, commission_per_side=0.0, point_value=50.0, bar_type="standard", fill_gaps=false, omega_threshold=0.0, initial_capital=100000.0, sharpe_annual_factor=252.0, rolling_window=100, quantity=1.0, direction="both", slippage=None, tick_size=0.25, target_ladder=None, daily_holding_cost=0.0, rollover_hour=0, stop_loss=None, max_holding_secs=None, flat_by_time=None, tz_offset_minutes=0, strict=false, max_skipped_fraction=0.01, cache_dir=None, fill_mode="current_close", final_signal="drop", benchmark_path=None, benchmark_point_value=None, risk_free_rate=0.0, cooldown_secs=None, cooldown_after_any_exit=false, warmup_bars=0, last_n_bars=None, lookback_days=None, max_gap_bars=bar::DEFAULT_MAX_GAP_BARS, strict_no_lookahead=false, benchmark=None, min_hold_bars=0, confirm_bars=1, drawdown_threshold=0.0, execution_delay_bars=0, pyramiding=1, save_json=None, daily_funding_rate=0.0, exclude_end_of_data=false, include_benchmark=false, pyramid_mode="lots", timezone=None, downsample_equity=None, time_breakdown=false, unbundle="ignore", metrics_basis="daily", session_offset_secs=0, session_timezone=None))]
+#[allow(clippy::too_many_arguments)]
 fn run_backtest(
     py: Python<'_>,
     path: &str,
     interval: &str,
     callback: &Bound<'_, PyAny>,
-    commission: f64,
+    commission_per_side: f64,
+    point_value: f64,
+    bar_type: &str,
+    fill_gaps: bool,
+    omega_threshold: f64,
+    initial_capital: f64,
+    sharpe_annual_factor: f64,
+    rolling_window: usize,
+    quantity: f64,
+    direction: &str,
+    slippage: Option<&Bound<'_, PyAny>>,
+    tick_size: f64,
+    target_ladder: Option<Vec<(f64, f64)>>,
+    daily_holding_cost: f64,
+    rollover_hour: u32,
+    stop_loss: Option<f64>,
+    max_holding_secs: Option<i64>,
+    flat_by_time: Option<&str>,
+    tz_offset_minutes: i64,
+    strict: bool,
+    max_skipped_fraction: f64,
+    cache_dir: Option<PathBuf>,
+    fill_mode: &str,
+    final_signal: &str,
+    benchmark_path: Option<&str>,
+    benchmark_point_value: Option<f64>,
+    risk_free_rate: f64,
+    cooldown_secs: Option<i64>,
+    cooldown_after_any_exit: bool,
+    warmup_bars: usize,
+    last_n_bars: Option<usize>,
+    lookback_days: Option<f64>,
+    max_gap_bars: usize,
+    strict_no_lookahead: bool,
+    benchmark: Option<&Bound<'_, PyAny>>,
+    min_hold_bars: usize,
+    confirm_bars: usize,
+    drawdown_threshold: f64,
+    execution_delay_bars: usize,
+    pyramiding: usize,
+    save_json: Option<PathBuf>,
+    daily_funding_rate: f64,
+    exclude_end_of_data: bool,
+    include_benchmark: bool,
+    pyramid_mode: &str,
+    timezone: Option<&str>,
+    downsample_equity: Option<usize>,
+    time_breakdown: bool,
+    unbundle: &str,
+    metrics_basis: &str,
+    session_offset_secs: i64,
+    session_timezone: Option<&str>,
+) -> PyResult<Py<PyDict>> {
+    let max_points = equity_points(downsample_equity)?;
+    let mut opts = bar_options(interval, bar_type, fill_gaps, cache_dir)?;
+    opts.tail = bar_tail(last_n_bars, lookback_days)?;
+    opts.max_gap_bars = max_gap_bars;
+    opts.unbundle = unbundle
+        .parse()
+        .map_err(pyo3::exceptions::PyValueError::new_err)?;
+    bar_session(&mut opts, session_offset_secs, session_timezone)?;
+    let config = engine::BacktestConfig {
+        commission_per_side,
+        point_value,
+        metrics: metrics::MetricsOptions {
+            omega_threshold,
+            initial_capital,
+            annual_factor: sharpe_annual_factor,
+            risk_free_rate,
+            rolling_window,
+            drawdown_threshold,
+            exclude_end_of_data,
+            basis: metrics_basis
+                .parse()
+                .map_err(pyo3::exceptions::PyValueError::new_err)?,
+        },
+        quantity,
+        direction: direction
+            .parse()
+            .map_err(pyo3::exceptions::PyValueError::new_err)?,
+        slippage_model: slippage_model(slippage)?,
+        tick_size,
+        target_ladder: target_ladder.unwrap_or_default(),
+        daily_holding_cost,
+        rollover_hour,
+        daily_funding_rate,
+        stop_loss_points: stop_loss,
+        max_holding_secs,
+        cooldown_secs,
+        cooldown_after_any_exit,
+        flat_time_secs: flat_by_time
+            .map(session::parse_time_of_day)
+            .transpose()
+            .map_err(pyo3::exceptions::PyValueError::new_err)?,
+        session_clock: session_clock(tz_offset_minutes, timezone)?,
+        max_skipped_fraction: skip_limit(strict, max_skipped_fraction)?,
+        fill_mode: fill_mode
+            .parse()
+            .map_err(pyo3::exceptions::PyValueError::new_err)?,
+        execution_delay_bars,
+        final_signal: final_signal
+            .parse()
+            .map_err(pyo3::exceptions::PyValueError::new_err)?,
+        warmup_bars,
+        strict_no_lookahead,
+        benchmark_side: match benchmark {
+            Some(spec) => benchmark_side(Some(spec))?,
+            None => include_benchmark.then_some(position::Side::Long),
+        },
+        min_hold_bars,
+        confirm_bars,
+        max_positions: pyramiding,
+        pyramid_mode: pyramid_mode
+            .parse()
+            .map_err(pyo3::exceptions::PyValueError::new_err)?,
+        equity_sampling: position::EquitySampling::Every,
+        unbundle: opts.unbundle,
+    };
+    let mut results = match benchmark_path {
+        Some(benchmark_path) => engine::run_backtest_vs_benchmark(
+            py,
+            path,
+            &opts,
+            callback,
+            benchmark_path,
+            benchmark_point_value.unwrap_or(point_value),
+            &config,
+        )?,
+        None => engine::run_bar_backtest(
+            py,
+            &engine::open_scid(py, path)?,
+            Some(Path::new(path)),
+            &opts,
+            callback,
+            &config,
+        )?,
+    };
+    if let Some(json_path) = save_json {
+        save_results_json(&results, &json_path)?;
+    }
+    if let Some(max_points) = max_points {
+        results.downsample_equity(max_points);
+    }
+    let buckets = time_breakdown
+        .then(|| metrics::compute_time_buckets(&results.trades, &config.session_clock));
+    let d = results_to_dict(py, results)?;
+    if let Some(buckets) = buckets {
+        set_time_buckets(py, d.bind(py), &buckets)?;
+    }
+    Ok(d)
+}
+
+/// Add the time_breakdown arrays for `buckets` to a results dict.
+fn set_time_buckets(
+    py: Python<'_>,
+    d: &Bound<'_, PyDict>,
+    buckets: &metrics::TimeBuckets,
+) -> PyResult<()> {
+    for (name, series) in [
+        ("hour", &buckets.by_hour[..]),
+        ("weekday", &buckets.by_weekday[..]),
+    ] {
+        let pnl: Vec<f64> = series.iter().map(|b| b.total_pnl).collect();
+        let trades: Vec<usize> = series.iter().map(|b| b.num_trades).collect();
+        let win_rate: Vec<f64> = series.iter().map(|b| b.win_rate()).collect();
+        d.set_item(format!("pnl_by_{name}"), PyArray1::from_vec(py, pnl))?;
+        d.set_item(format!("trades_by_{name}"), PyArray1::from_vec(py, trades))?;
+        d.set_item(
+            format!("winrate_by_{name}"),
+            PyArray1::from_vec(py, win_rate),
+        )?;
+    }
+    Ok(())
+}
+
+/// run_backtest scored against a benchmark SCID file; takes the same keyword
+/// arguments as run_backtest.
+#[pyfunction]
+#[pyo3(signature = (path, interval, callback, benchmark_path, **kwargs))]
+fn run_backtest_vs_benchmark(
+    py: Python<'_>,
+    path: &str,
+    interval: &str,
+    callback: &Bound<'_, PyAny>,
+    benchmark_path: &str,
+    kwargs: Option<&Bound<'_, PyDict>>,
+) -> PyResult<Py<PyDict>> {
+    let kwargs = match kwargs {
+        Some(kwargs) => kwargs.copy()?,
+        None => PyDict::new(py),
+    };
+    kwargs.set_item("benchmark_path", benchmark_path)?;
+    let results = wrap_pyfunction!(run_backtest, py)?
+        .call((path, interval, callback), Some(&kwargs))?
+        .cast_into::<PyDict>()?;
+    Ok(results.unbind())
+}
+
+/// Run an event-driven bar backtest. After each bar, callback(bars) gets the bars so far
+/// plus position (signed contracts) and entry_price, and returns a list of orders for the
+/// next bar: ("market", side, qty), ("limit", side, qty, price) or ("stop", side, qty,
+/// price) with side "buy" or "sell". Limits fill against the bar's low/high, stops at the
+/// stop price or the open if it gaps through; unfilled orders are cancelled.
+#[pyfunction]
+#[pyo3(signature = (path, interval, callback, commission_per_side=0.0, point_value=50.0, bar_type="standard", fill_gaps=false))]
+#[allow(clippy::too_many_arguments)]
+fn run_event_backtest(
+    py: Python<'_>,
+    path: &str,
+    interval: &str,
+    callback: &Bound<'_, PyAny>,
+    commission_per_side: f64,
+    point_value: f64,
+    bar_type: &str,
+    fill_gaps: bool,
+) -> PyResult<Py<PyDict>> {
+    let opts = bar_options(interval, bar_type, fill_gaps, None)?;
+    let config = engine::BacktestConfig {
+        commission_per_side,
+        point_value,
+        ..Default::default()
+    };
+    let results = engine::run_event_backtest(py, path, &opts, callback, &config)?;
+    results_to_dict(py, results)
+}
+
+/// Run a bar-based backtest that calls callback(bars) once per bar with the bars up to and
+/// including that bar, expecting a single int signal back (1=long, -1=short, 0=flat,
+/// HOLD=keep the current position).
+/// lookback: pass only the most recent lookback bars to each call (None = all so far)
+#[pyfunction]
+#[pyo3(signature = (path, interval, callback, commission_per_side=0.0, point_value=50.0, lookback=None, bar_type="standard", fill_gaps=false))]
+#[allow(clippy::too_many_arguments)]
+fn run_incremental_bar_backtest(
+    py: Python<'_>,
+    path: &str,
+    interval: &str,
+    callback: &Bound<'_, PyAny>,
+    commission_per_side: f64,
+    point_value: f64,
+    lookback: Option<usize>,
+    bar_type: &str,
+    fill_gaps: bool,
+) -> PyResult<Py<PyDict>> {
+    let opts = bar_options(interval, bar_type, fill_gaps, None)?;
+    let config = engine::BacktestConfig {
+        commission_per_side,
+        point_value,
+        ..Default::default()
+    };
+    let results =
+        engine::run_incremental_bar_backtest(py, path, &opts, callback, lookback, &config)?;
+    results_to_dict(py, results)
+}
+
+/// Run a bar-based backtest with a strategy built into the engine, without Python
+/// callbacks: "sma_cross" (long while the fast SMA of closes is above the slow one, short
+/// while below; params fast=10, slow=30) or "breakout" (long on a close above the
+/// highest high of the previous period bars, short below their lowest low; period=20).
+/// params: dict of parameter overrides (None = the defaults)
+#[pyfunction]
+#[pyo3(signature = (path, interval, name, params=None, commission_per_side=0.0, point_value=50.0, bar_type="standard", fill_gaps=false))]
+#[allow(clippy::too_many_arguments)]
+fn run_builtin_backtest(
+    py: Python<'_>,
+    path: &str,
+    interval: &str,
+    name: &str,
+    params: Option<std::collections::HashMap<String, f64>>,
+    commission_per_side: f64,
+    point_value: f64,
+    bar_type: &str,
+    fill_gaps: bool,
+) -> PyResult<Py<PyDict>> {
+    let opts = bar_options(interval, bar_type, fill_gaps, None)?;
+    let mut strategy = strategy::builtin(name, &params.unwrap_or_default())
+        .map_err(pyo3::exceptions::PyValueError::new_err)?;
+    let config = engine::BacktestConfig {
+        commission_per_side,
+        point_value,
+        ..Default::default()
+    };
+    let scid = engine::open_scid(py, path)?;
+    let results = py.detach(|| {
+        engine::run_rust_backtest(
+            &scid,
+            Some(Path::new(path)),
+            &opts,
+            strategy.as_mut(),
+            &config,
+        )
+    })?;
+    results_to_dict(py, results)
+}
+
+/// Walk-forward analysis over rolling in-sample / out-of-sample bar windows.
+/// optimize_callback(in_sample_bars) returns parameters for the window; then
+/// evaluate_callback(out_of_sample_bars, params) returns that window's signals.
+/// Out-of-sample segments share one position tracker and are flattened at each segment's
+/// end. Returns the combined out-of-sample result dict with a "windows" list holding each
+/// segment's result dict plus in_sample_start, out_sample_start, out_sample_end (Unix
+/// seconds, bar opens) and num_bars.
+/// min_oos_bars: evaluate a final partial out-of-sample window with at least this many
+///     bars (None = only full windows)
+#[pyfunction]
+#[pyo3(signature = (path, interval, optimize_callback, evaluate_callback, in_sample_bars, out_sample_bars, commission_per_side=0.0, point_value=50.0, bar_type="standard", fill_gaps=false, min_oos_bars=None))]
+#[allow(clippy::too_many_arguments)]
+fn run_walk_forward(
+    py: Python<'_>,
+    path: &str,
+    interval: &str,
+    optimize_callback: &Bound<'_, PyAny>,
+    evaluate_callback: &Bound<'_, PyAny>,
+    in_sample_bars: usize,
+    out_sample_bars: usize,
+    commission_per_side: f64,
+    point_value: f64,
+    bar_type: &str,
+    fill_gaps: bool,
+    min_oos_bars: Option<usize>,
+) -> PyResult<Py<PyDict>> {
+    let opts = bar_options(interval, bar_type, fill_gaps, None)?;
+    let config = engine::BacktestConfig {
+        commission_per_side,
+        point_value,
+        ..Default::default()
+    };
+    let wf = engine::run_walk_forward(
+        py,
+        path,
+        &opts,
+        optimize_callback,
+        evaluate_callback,
+        in_sample_bars,
+        out_sample_bars,
+        min_oos_bars,
+        &config,
+    )?;
+    let windows = PyList::empty(py);
+    for window in wf.windows {
+        let wd = results_to_dict(py, window.results)?.into_bound(py);
+        wd.set_item(
+            "in_sample_start",
+            window.in_sample_start_us as f64 / 1_000_000.0,
+        )?;
+        wd.set_item(
+            "out_sample_start",
+            window.out_sample_start_us as f64 / 1_000_000.0,
+        )?;
+        wd.set_item(
+            "out_sample_end",
+            window.out_sample_end_us as f64 / 1_000_000.0,
+        )?;
+        wd.set_item("num_bars", window.num_bars)?;
+        windows.append(wd)?;
+    }
+    let d = results_to_dict(py, wf.combined)?;
+    d.bind(py).set_item("windows", windows)?;
+    Ok(d)
+}
+
+/// Bar backtest with tick-accurate fills: callback(bars) returns the signal array as in
+/// run_backtest, but each signal fills at the first tick of the next bar (or of bar
+/// i + execution_delay_bars) and stop_loss, target_ladder and max_holding_secs exits fill
+/// at the tick that triggers them, with tick-precise trade times. Only bars where the
+/// position changes or an exit level is within the bar's range are read tick by tick.
+/// Other arguments are as in run_backtest.
+#[pyfunction]
+#[pyo3(signature = (path, interval, callback, commission_per_side=0.0, point_value=50.0, bar_type="standard", fill_gaps=false, quantity=1.0, slippage=None, tick_size=0.25, stop_loss=None, target_ladder=None, max_holding_secs=None, execution_delay_bars=0, cache_dir=None))]
+#[allow(clippy::too_many_arguments)]
+fn run_hybrid_backtest(
+    py: Python<'_>,
+    path: &str,
+    interval: &str,
+    callback: &Bound<'_, PyAny>,
+    commission_per_side: f64,
     point_value: f64,
+    bar_type: &str,
+    fill_gaps: bool,
+    quantity: f64,
+    slippage: Option<&Bound<'_, PyAny>>,
+    tick_size: f64,
+    stop_loss: Option<f64>,
+    target_ladder: Option<Vec<(f64, f64)>>,
+    max_holding_secs: Option<i64>,
+    execution_delay_bars: usize,
+    cache_dir: Option<PathBuf>,
 ) -> PyResult<Py<PyDict>> {
-    let results = engine::run_bar_backtest(py, path, interval, callback, commission, point_value)?;
+    let opts = bar_options(interval, bar_type, fill_gaps, cache_dir)?;
+    let config = engine::BacktestConfig {
+        commission_per_side,
+        point_value,
+        quantity,
+        slippage_model: slippage_model(slippage)?,
+        tick_size,
+        stop_loss_points: stop_loss,
+        target_ladder: target_ladder.unwrap_or_default(),
+        max_holding_secs,
+        execution_delay_bars,
+        ..Default::default()
+    };
+    let results = engine::run_hybrid_backtest(py, path, &opts, callback, &config)?;
     results_to_dict(py, results)
 }
 
+/// Rolling-window backtest: callback(bars) is run on each window of window_bars bars,
+/// advancing by step_bars, and each window is simulated on its own. Returns a list of
+/// result dicts, one per window, each with window_start and window_end (Unix seconds,
+/// first and last bar opens).
+#[pyfunction]
+#[pyo3(signature = (path, interval, callback, window_bars, step_bars, commission_per_side=0.0, point_value=50.0, bar_type="standard", fill_gaps=false))]
+#[allow(clippy::too_many_arguments)]
+fn run_rolling_backtest(
+    py: Python<'_>,
+    path: &str,
+    interval: &str,
+    callback: &Bound<'_, PyAny>,
+    window_bars: usize,
+    step_bars: usize,
+    commission_per_side: f64,
+    point_value: f64,
+    bar_type: &str,
+    fill_gaps: bool,
+) -> PyResult<Py<PyList>> {
+    let opts = bar_options(interval, bar_type, fill_gaps, None)?;
+    let config = engine::BacktestConfig {
+        commission_per_side,
+        point_value,
+        ..Default::default()
+    };
+    let windows = PyList::empty(py);
+    for results in
+        engine::run_rolling_backtest(py, path, &opts, callback, window_bars, step_bars, &config)?
+    {
+        let span = (
+            results.equity_timestamps.first().copied(),
+            results.equity_timestamps.last().copied(),
+        );
+        let wd = results_to_dict(py, results)?.into_bound(py);
+        if let (Some(start), Some(end)) = span {
+            wd.set_item("window_start", start as f64 / 1_000_000.0)?;
+            wd.set_item("window_end", end as f64 / 1_000_000.0)?;
+        }
+        windows.append(wd)?;
+    }
+    Ok(windows.unbind())
+}
+
+/// Parameter sweep: callback_factory(params) is called for each dict in param_grid and
+/// must return a bar strategy (a callable, or an object with on_bars). Strategies are
+/// called one after another; their simulations run in parallel across threads. Returns
+/// a list of (params, result dict) tuples sorted by sharpe_ratio, best first.
+#[pyfunction]
+#[pyo3(signature = (path, interval, callback_factory, param_grid, commission_per_side=0.0, point_value=50.0, bar_type="standard", fill_gaps=false))]
+#[allow(clippy::too_many_arguments)]
+fn run_param_sweep(
+    py: Python<'_>,
+    path: &str,
+    interval: &str,
+    callback_factory: &Bound<'_, PyAny>,
+    param_grid: Vec<Bound<'_, PyDict>>,
+    commission_per_side: f64,
+    point_value: f64,
+    bar_type: &str,
+    fill_gaps: bool,
+) -> PyResult<Py<PyList>> {
+    let opts = bar_options(interval, bar_type, fill_gaps, None)?;
+    let config = engine::BacktestConfig {
+        commission_per_side,
+        point_value,
+        ..Default::default()
+    };
+    let sweep = engine::run_param_sweep(py, path, &opts, callback_factory, param_grid, &config)?;
+    let out = PyList::empty(py);
+    for (params, results) in sweep {
+        out.append((params, results_to_dict(py, results)?))?;
+    }
+    Ok(out.unbind())
+}
+
+/// Monte Carlo trade-shuffle simulation over a result dict from run_backtest or
+/// run_tick_backtest. Returns p5/p50/p95 equity curves (one point per trade plus the
+/// start) and the p5/p95 max drawdown and p5 final equity across n_simulations orderings.
+#[pyfunction]
+#[pyo3(signature = (results, n_simulations=1000))]
+fn monte_carlo_simulation(
+    py: Python<'_>,
+    results: &Bound<'_, PyDict>,
+    n_simulations: usize,
+) -> PyResult<Py<PyDict>> {
+    let trades_obj = results
+        .get_item("trades")?
+        .ok_or_else(|| pyo3::exceptions::PyKeyError::new_err("trades"))?;
+    let trades = trades_obj
+        .cast::<PyList>()?
+        .iter()
+        .map(|t| trade_from_dict(t.cast::<PyDict>()?))
+        .collect::<PyResult<Vec<_>>>()?;
+    let equity_curve: Vec<f64> = match results.get_item("equity_curve")? {
+        Some(eq) => eq.extract()?,
+        None => Vec::new(),
+    };
+
+    let bands = engine::run_monte_carlo(&trades, n_simulations, &equity_curve);
+    let d = PyDict::new(py);
+    d.set_item("p5_curve", PyArray1::from_vec(py, bands.p5_curve))?;
+    d.set_item("p50_curve", PyArray1::from_vec(py, bands.p50_curve))?;
+    d.set_item("p95_curve", PyArray1::from_vec(py, bands.p95_curve))?;
+    d.set_item("p5_max_dd", bands.p5_max_dd)?;
+    d.set_item("p95_max_dd", bands.p95_max_dd)?;
+    d.set_item("p5_final_equity", bands.p5_final_equity)?;
+    Ok(d.into())
+}
+
+/// Inverse of the per-trade dict built by `results_to_dict` (`initial_risk` is not in
+/// the dict and is left unset).
+fn trade_from_dict(td: &Bound<'_, PyDict>) -> PyResult<position::Trade> {
+    let get = |key: &str| -> PyResult<Bound<'_, PyAny>> {
+        td.get_item(key)?
+            .ok_or_else(|| pyo3::exceptions::PyKeyError::new_err(key.to_string()))
+    };
+    let side = match get("side")?.extract::<String>()?.as_str() {
+        "long" => position::Side::Long,
+        "short" => position::Side::Short,
+        _ => position::Side::Flat,
+    };
+    let exit_reason = get("exit_reason")?
+        .extract::<String>()?
+        .parse()
+        .map_err(pyo3::exceptions::PyValueError::new_err)?;
+    let pnl = get("pnl")?.extract()?;
+    let equity_at_entry = get("equity_at_entry")?.extract()?;
+    Ok(position::Trade {
+        entry_time_us: (get("entry_time")?.extract::<f64>()? * 1_000_000.0).round() as i64,
+        exit_time_us: (get("exit_time")?.extract::<f64>()? * 1_000_000.0).round() as i64,
+        side,
+        entry_price: get("entry_price")?.extract()?,
+        exit_price: get("exit_price")?.extract()?,
+        quantity: get("quantity")?.extract()?,
+        pnl,
+        pnl_ticks: get("pnl_ticks")?.extract()?,
+        return_pct: get("return_pct")?.extract()?,
+        capital_return_pct: position::capital_return_pct(pnl, equity_at_entry),
+        holding_cost: get("holding_cost")?.extract()?,
+        funding: get("funding")?.extract()?,
+        commission: get("commission")?.extract()?,
+        exit_reason,
+        partial: get("partial")?.extract()?,
+        initial_risk: None,
+        r_multiple: get("r_multiple")?.extract()?,
+        tag: get("tag")?.extract()?,
+        bars_held: get("bars_held")?.extract()?,
+        equity_at_entry,
+        drawdown_at_entry: get("drawdown_at_entry")?.extract()?,
+    })
+}
+
+/// Write the trades of a result dict (results["trades"]) to a CSV file with columns
+/// entry_time, exit_time (ISO-8601 UTC), side, entry_price, exit_price and pnl.
+#[pyfunction]
+fn export_trades_csv(trades: &Bound<'_, PyList>, path: PathBuf) -> PyResult<()> {
+    let trades = trades
+        .iter()
+        .map(|t| trade_from_dict(t.cast::<PyDict>()?))
+        .collect::<PyResult<Vec<_>>>()?;
+    export::write_trades_csv(&path, &trades)
+        .map_err(|e| pyo3::exceptions::PyIOError::new_err(format!("{}: {e}", path.display())))
+}
+
 /// Run a tick-based backtest with a Python strategy callback.
+/// Each batch dict also carries position (-1/0/1) and entry_price (NaN when flat) as of
+/// the start of the batch.
+/// depth_path: Sierra Chart .depth file whose top depth_levels are forward-filled onto
+///     each tick as bid_price_1, bid_size_1, ask_price_1, ask_size_1, ...
+/// stateful: call callback(tick_data, state) and expect (signals, state) or
+///     (signals, tags, state) back; the returned state is passed to the next batch,
+///     starting from initial_state
 /// point_value: dollar value per 1.0 point move (ES=50, NQ=20)
+/// omega_threshold: per-trade PnL threshold for the Omega ratio
+/// initial_capital: account size the equity curve starts from; the base for annualized
+///     return, CAGR, total_return_pct and drawdown percentages
+/// sharpe_annual_factor: periods per year for Sharpe, Sortino and annualized volatility
+/// rolling_window: equity points per window for the rolling_sharpe array
+/// commission_per_side: dollars per contract per side, charged on entry and on exit
+/// quantity: contracts per entry (commission is charged per contract)
+/// direction: "both", "long_only" or "short_only"; signals for a disallowed side mean flat
+/// slippage: ticks per fill, or ("fixed", ticks) / ("volume_scaled", base, ref_volume) /
+///     ("range_fraction", fraction); always against the trader
+/// tick_size: minimum price increment, the unit of tick-based slippage (ES=0.25)
+/// target_ladder: list of (points, fraction) scale-out targets, fractions summing to <= 1
+/// daily_holding_cost: dollars per contract per day boundary a position is held across
+/// rollover_hour: UTC hour at which that day boundary falls
+/// stop_loss: stop distance in points from entry; enables per-trade R-multiples
+/// max_holding_secs: force-close positions held longer than this (None = no limit)
+/// cooldown_secs: ignore entry signals for this long after a stop-loss exit
+/// cooldown_after_any_exit: start the cooldown after every exit, not just stop-losses
+/// flat_by_time: "HH:MM[:SS]" local time at which open positions are closed; no new
+///     entries until the next local day
+/// tz_offset_minutes: local time minus UTC for flat_by_time (e.g. -300 for US/Eastern winter)
+/// strict: raise ValueError if more than max_skipped_fraction of records are invalid
+/// drawdown_threshold: see run_backtest
+/// pyramiding, save_json, daily_funding_rate, exclude_end_of_data, pyramid_mode,
+///     downsample_equity: see run_backtest
+/// equity_sampling: equity curve points to keep: "interval:<seconds>" keeps the last
+///     point in each window of data time, "on_trade" only points after a trade closes,
+///     "every_tick" one per tick. max_drawdown and max_drawdown_pct are exact either
+///     way; drawdown duration, Ulcer Index and Sharpe use the sampled curve
+/// timezone: see run_backtest
+/// unbundle: see load_bars; applies to the volume and num_trades columns of each batch
+/// metrics_basis: see run_backtest
 #[pyfunction]
-#[pyo3(signature = (path, callback, batch_size=100000, commission=0.0, point_value=50.0))]
+#[pyo3(signature = (path, callback, batch_size=100000, commission_per_side=0.0, point_value=50.0, omega_threshold=0.0, initial_capital=100000.0, sharpe_annual_factor=252.0, rolling_window=100, quantity=1.0, direction="both", slippage=None, tick_size=0.25, target_ladder=None, daily_holding_cost=0.0, rollover_hour=0, stop_loss=None, max_holding_secs=None, flat_by_time=None, tz_offset_minutes=0, strict=false, max_skipped_fraction=0.01, stateful=false, initial_state=None, depth_path=None, depth_levels=1, cooldown_secs=None, cooldown_after_any_exit=false, drawdown_threshold=0.0, pyramiding=1, save_json=None, daily_funding_rate=0.0, exclude_end_of_data=false, pyramid_mode="lots", equity_sampling="interval:1", timezone=None, downsample_equity=None, unbundle="ignore", metrics_basis="daily"))]
+#[allow(clippy::too_many_arguments)]
 fn run_tick_backtest(
     py: Python<'_>,
     path: &str,
     callback: &Bound<'_, PyAny>,
     batch_size: usize,
-    commission: f64,
+    commission_per_side: f64,
     point_value: f64,
+    omega_threshold: f64,
+    initial_capital: f64,
+    sharpe_annual_factor: f64,
+    rolling_window: usize,
+    quantity: f64,
+    direction: &str,
+    slippage: Option<&Bound<'_, PyAny>>,
+    tick_size: f64,
+    target_ladder: Option<Vec<(f64, f64)>>,
+    daily_holding_cost: f64,
+    rollover_hour: u32,
+    stop_loss: Option<f64>,
+    max_holding_secs: Option<i64>,
+    flat_by_time: Option<&str>,
+    tz_offset_minutes: i64,
+    strict: bool,
+    max_skipped_fraction: f64,
+    stateful: bool,
+    initial_state: Option<Bound<'_, PyAny>>,
+    depth_path: Option<&str>,
+    depth_levels: usize,
+    cooldown_secs: Option<i64>,
+    cooldown_after_any_exit: bool,
+    drawdown_threshold: f64,
+    pyramiding: usize,
+    save_json: Option<PathBuf>,
+    daily_funding_rate: f64,
+    exclude_end_of_data: bool,
+    pyramid_mode: &str,
+    equity_sampling: &str,
+    timezone: Option<&str>,
+    downsample_equity: Option<usize>,
+    unbundle: &str,
+    metrics_basis: &str,
 ) -> PyResult<Py<PyDict>> {
-    let results =
-        engine::run_tick_backtest(py, path, batch_size, callback, commission, point_value)?;
+    let max_points = equity_points(downsample_equity)?;
+    let config = engine::BacktestConfig {
+        commission_per_side,
+        point_value,
+        metrics: metrics::MetricsOptions {
+            omega_threshold,
+            initial_capital,
+            annual_factor: sharpe_annual_factor,
+            rolling_window,
+            drawdown_threshold,
+            exclude_end_of_data,
+            basis: metrics_basis
+                .parse()
+                .map_err(pyo3::exceptions::PyValueError::new_err)?,
+            ..Default::default()
+        },
+        quantity,
+        direction: direction
+            .parse()
+            .map_err(pyo3::exceptions::PyValueError::new_err)?,
+        slippage_model: slippage_model(slippage)?,
+        tick_size,
+        target_ladder: target_ladder.unwrap_or_default(),
+        daily_holding_cost,
+        rollover_hour,
+        daily_funding_rate,
+        stop_loss_points: stop_loss,
+        max_holding_secs,
+        cooldown_secs,
+        cooldown_after_any_exit,
+        flat_time_secs: flat_by_time
+            .map(session::parse_time_of_day)
+            .transpose()
+            .map_err(pyo3::exceptions::PyValueError::new_err)?,
+        session_clock: session_clock(tz_offset_minutes, timezone)?,
+        max_skipped_fraction: skip_limit(strict, max_skipped_fraction)?,
+        max_positions: pyramiding,
+        pyramid_mode: pyramid_mode
+            .parse()
+            .map_err(pyo3::exceptions::PyValueError::new_err)?,
+        equity_sampling: equity_sampling
+            .parse()
+            .map_err(pyo3::exceptions::PyValueError::new_err)?,
+        unbundle: unbundle
+            .parse()
+            .map_err(pyo3::exceptions::PyValueError::new_err)?,
+        ..Default::default()
+    };
+    let state = stateful.then(|| initial_state.unwrap_or_else(|| py.None().into_bound(py)));
+    let depth = depth_path
+        .map(|p| load_depth_snapshots(p, depth_levels))
+        .transpose()?;
+    let mut results = engine::run_tick_backtest(
+        py,
+        &engine::open_scid(py, path)?,
+        batch_size,
+        callback,
+        &config,
+        state,
+        depth.as_ref(),
+    )?;
+    if let Some(json_path) = save_json {
+        save_results_json(&results, &json_path)?;
+    }
+    if let Some(max_points) = max_points {
+        results.downsample_equity(max_points);
+    }
     results_to_dict(py, results)
 }
 
-fn results_to_dict(py: Python<'_>, results: engine::BacktestResults) -> PyResult<Py<PyDict>> {
-    let m = &results.metrics;
+/// Write `results` as JSON (see `BacktestResults::to_json`) to `path`.
+fn save_results_json(results: &engine::BacktestResults, path: &Path) -> PyResult<()> {
+    std::fs::write(path, results.to_json())
+        .map_err(|e| pyo3::exceptions::PyIOError::new_err(format!("{}: {e}", path.display())))
+}
+
+fn metrics_to_dict<'py>(
+    py: Python<'py>,
+    m: &metrics::BacktestMetrics,
+) -> PyResult<Bound<'py, PyDict>> {
     let d = PyDict::new(py);
     d.set_item("total_pnl", m.total_pnl)?;
+    d.set_item("total_commission_paid", m.total_commission_paid)?;
+    d.set_item("total_funding_paid", m.total_funding_paid)?;
     d.set_item("num_trades", m.num_trades)?;
     d.set_item("num_wins", m.num_wins)?;
     d.set_item("num_losses", m.num_losses)?;
+    d.set_item("num_forced_exits", m.num_forced_exits)?;
     d.set_item("win_rate", m.win_rate)?;
     d.set_item("profit_factor", m.profit_factor)?;
     d.set_item("avg_win", m.avg_win)?;
     d.set_item("avg_loss", m.avg_loss)?;
     d.set_item("largest_win", m.largest_win)?;
     d.set_item("largest_loss", m.largest_loss)?;
+    d.set_item("kelly_fraction", m.kelly_fraction)?;
+    d.set_item("optimal_f", m.optimal_f)?;
     d.set_item("max_drawdown", m.max_drawdown)?;
     d.set_item("max_drawdown_pct", m.max_drawdown_pct)?;
+    d.set_item("max_drawdown_duration_bars", m.max_drawdown_duration_bars)?;
+    d.set_item("max_drawdown_duration_secs", m.max_drawdown_duration_secs)?;
     d.set_item("sharpe_ratio", m.sharpe_ratio)?;
+    d.set_item("sortino_ratio", m.sortino_ratio)?;
+    d.set_item("sharpe_per_trade", m.sharpe_per_trade)?;
+    d.set_item("trades_per_day", m.trades_per_day)?;
+    d.set_item("information_ratio", m.information_ratio)?;
+    d.set_item("beta", m.beta)?;
+    d.set_item("alpha", m.alpha)?;
+    d.set_item("treynor_ratio", m.treynor_ratio)?;
+    d.set_item("daily_beta", m.daily_beta)?;
+    d.set_item("daily_alpha", m.daily_alpha)?;
+    if let Some(pnl) = m.benchmark_total_pnl {
+        d.set_item("benchmark_total_pnl", pnl)?;
+    }
+    if let Some(sharpe) = m.benchmark_sharpe {
+        d.set_item("benchmark_sharpe", sharpe)?;
+    }
+    if let Some(drawdown) = m.benchmark_max_drawdown {
+        d.set_item("benchmark_max_drawdown", drawdown)?;
+    }
+    d.set_item("annualized_return", m.annualized_return)?;
+    d.set_item("annualized_volatility", m.annualized_volatility)?;
+    d.set_item("total_return_pct", m.total_return_pct)?;
+    d.set_item("cagr", m.cagr)?;
+    d.set_item("volatility_annualized_pct", m.volatility_annualized_pct)?;
+    d.set_item("ulcer_index", m.ulcer_index)?;
+    d.set_item("ulcer_performance_index", m.ulcer_performance_index)?;
+    d.set_item("omega_ratio", m.omega_ratio)?;
+    d.set_item("sqn", m.sqn)?;
+    d.set_item("var_95", m.var_95)?;
+    d.set_item("cvar_95", m.cvar_95)?;
+    d.set_item("var_99", m.var_99)?;
+    d.set_item("cvar_99", m.cvar_99)?;
     d.set_item("avg_holding_time_secs", m.avg_holding_time_secs)?;
+    d.set_item("avg_r", m.avg_r)?;
+    d.set_item("std_r", m.std_r)?;
+    d.set_item("expectancy_r", m.expectancy_r)?;
+    d.set_item("avg_pnl_after_drawdown", m.avg_pnl_after_drawdown)?;
     d.set_item("num_long", m.num_long)?;
     d.set_item("num_short", m.num_short)?;
+    Ok(d)
+}
+
+fn results_to_dict(py: Python<'_>, results: engine::BacktestResults) -> PyResult<Py<PyDict>> {
+    let d = metrics_to_dict(py, &results.metrics)?;
+    d.set_item("equity_curve", PyArray1::from_vec(py, results.equity_curve))?;
+    let equity_times: Vec<f64> = results
+        .equity_timestamps
+        .iter()
+        .map(|&ts| ts as f64 / 1_000_000.0)
+        .collect();
+    d.set_item("equity_timestamps", PyArray1::from_vec(py, equity_times))?;
+    if let Some(benchmark) = results.benchmark_pnl {
+        d.set_item("benchmark_equity_curve", PyArray1::from_vec(py, benchmark))?;
+    }
     d.set_item(
-        "equity_curve",
-        PyArray1::from_vec(py, results.equity_curve),
+        "rolling_sharpe",
+        PyArray1::from_vec(py, results.rolling_sharpe),
     )?;
 
     // Trade list
@@ -173,11 +1885,52 @@ fn results_to_dict(py: Python<'_>, results: engine::BacktestResults) -> PyResult
             .unwrap();
             td.set_item("entry_price", t.entry_price).unwrap();
             td.set_item("exit_price", t.exit_price).unwrap();
+            td.set_item("quantity", t.quantity).unwrap();
             td.set_item("pnl", t.pnl).unwrap();
+            td.set_item("pnl_ticks", t.pnl_ticks).unwrap();
+            td.set_item("return_pct", t.return_pct).unwrap();
+            td.set_item("capital_return_pct", t.capital_return_pct)
+                .unwrap();
+            td.set_item("holding_cost", t.holding_cost).unwrap();
+            td.set_item("funding", t.funding).unwrap();
+            td.set_item("commission", t.commission).unwrap();
+            td.set_item("exit_reason", t.exit_reason.as_str()).unwrap();
+            td.set_item("partial", t.partial).unwrap();
+            td.set_item("r_multiple", t.r_multiple).unwrap();
+            td.set_item("tag", t.tag).unwrap();
+            td.set_item("bars_held", t.bars_held).unwrap();
+            td.set_item("equity_at_entry", t.equity_at_entry).unwrap();
+            td.set_item("drawdown_at_entry", t.drawdown_at_entry)
+                .unwrap();
             td.into()
         })
         .collect();
     d.set_item("trades", trades)?;
+    d.set_item("skipped_records", skip_stats_to_dict(py, &results.skipped)?)?;
+    d.set_item("bars_truncated", results.bars_truncated)?;
+    let r_multiples: Vec<f64> = results
+        .trades
+        .iter()
+        .map(|t| t.r_multiple.unwrap_or(f64::NAN))
+        .collect();
+    d.set_item("r_multiples", PyArray1::from_vec(py, r_multiples))?;
+
+    let by_tag = PyDict::new(py);
+    for (tag, m) in &results.metrics_by_tag {
+        by_tag.set_item(tag, metrics_to_dict(py, m)?)?;
+    }
+    d.set_item("metrics_by_tag", by_tag)?;
+
+    let monthly = PyDict::new(py);
+    for ((year, month), ms) in &results.monthly_stats {
+        let md = PyDict::new(py);
+        md.set_item("num_trades", ms.num_trades)?;
+        md.set_item("total_pnl", ms.total_pnl)?;
+        md.set_item("sharpe_ratio", ms.sharpe_ratio)?;
+        md.set_item("sortino_ratio", ms.sortino_ratio)?;
+        monthly.set_item(format!("{year:04}-{month:02}"), md)?;
+    }
+    d.set_item("monthly_stats", monthly)?;
 
     Ok(d.into())
 }
@@ -185,9 +1938,40 @@ fn results_to_dict(py: Python<'_>, results: engine::BacktestResults) -> PyResult
 /// PyO3 module
 #[pymodule]
 fn _engine(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("HOLD", position::HOLD)?;
     m.add_function(wrap_pyfunction!(load_scid, m)?)?;
     m.add_function(wrap_pyfunction!(load_bars, m)?)?;
+    m.add_function(wrap_pyfunction!(load_scid_range, m)?)?;
+    m.add_function(wrap_pyfunction!(load_scid_multi, m)?)?;
+    m.add_function(wrap_pyfunction!(load_bars_multi, m)?)?;
+    m.add_function(wrap_pyfunction!(load_bars_intervals, m)?)?;
+    m.add_function(wrap_pyfunction!(load_scid_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(load_bars_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(load_csv, m)?)?;
+    m.add_function(wrap_pyfunction!(load_csv_bars, m)?)?;
+    m.add_function(wrap_pyfunction!(load_parquet, m)?)?;
+    m.add_function(wrap_pyfunction!(load_parquet_bars, m)?)?;
+    m.add_function(wrap_pyfunction!(generate_synthetic_ticks, m)?)?;
+    m.add_function(wrap_pyfunction!(load_bars_range, m)?)?;
+    m.add_function(wrap_pyfunction!(write_scid, m)?)?;
+    m.add_function(wrap_pyfunction!(validate_scid, m)?)?;
+    m.add_function(wrap_pyfunction!(check_scid_integrity, m)?)?;
+    m.add_function(wrap_pyfunction!(scid_info, m)?)?;
+    m.add_function(wrap_pyfunction!(scid_header, m)?)?;
+    m.add_function(wrap_pyfunction!(load_depth, m)?)?;
+    m.add_function(wrap_pyfunction!(replay_scid, m)?)?;
+    m.add_class::<replay::Replay>()?;
     m.add_function(wrap_pyfunction!(run_backtest, m)?)?;
+    m.add_function(wrap_pyfunction!(run_backtest_vs_benchmark, m)?)?;
+    m.add_function(wrap_pyfunction!(run_incremental_bar_backtest, m)?)?;
+    m.add_function(wrap_pyfunction!(run_builtin_backtest, m)?)?;
+    m.add_function(wrap_pyfunction!(run_event_backtest, m)?)?;
+    m.add_function(wrap_pyfunction!(run_walk_forward, m)?)?;
+    m.add_function(wrap_pyfunction!(run_hybrid_backtest, m)?)?;
+    m.add_function(wrap_pyfunction!(run_rolling_backtest, m)?)?;
+    m.add_function(wrap_pyfunction!(run_param_sweep, m)?)?;
     m.add_function(wrap_pyfunction!(run_tick_backtest, m)?)?;
+    m.add_function(wrap_pyfunction!(monte_carlo_simulation, m)?)?;
+    m.add_function(wrap_pyfunction!(export_trades_csv, m)?)?;
     Ok(())
 }
@@ -1,49 +1,242 @@
-use crate::position::{Side, Trade};
+use std::collections::BTreeMap;
 
-#[derive(Clone, Debug)]
+use serde::Serialize;
+
+use crate::position::{ExitReason, Side, Trade};
+use crate::session::SessionClock;
+
+const US_PER_DAY: i64 = 86_400_000_000;
+const US_PER_YEAR: f64 = 365.25 * US_PER_DAY as f64;
+
+/// Settings that change how metrics are computed, independent of the simulation.
+#[derive(Clone, Copy, Debug)]
+pub struct MetricsOptions {
+    /// Per-trade PnL threshold separating gains from losses in the Omega ratio
+    pub omega_threshold: f64,
+    /// Account size that annualized return is measured against
+    pub initial_capital: f64,
+    /// Periods per year used to annualize Sharpe, Sortino and volatility
+    pub annual_factor: f64,
+    /// Annual risk-free rate for alpha and the Treynor ratio
+    pub risk_free_rate: f64,
+    /// Equity points per window for the rolling Sharpe
+    pub rolling_window: usize,
+    /// Drawdown at entry above which a trade counts for `avg_pnl_after_drawdown`
+    pub drawdown_threshold: f64,
+    /// Leave trades closed at the end of the data out of the win/loss statistics
+    /// (win rate, win and loss counts, sizes and profit factor)
+    pub exclude_end_of_data: bool,
+    /// Series `sharpe_ratio` and `sortino_ratio` are computed over
+    pub basis: MetricsBasis,
+}
+
+/// What risk-adjusted ratios are measured over.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MetricsBasis {
+    /// Per-trade PnL, annualized as if each trade were a period
+    Trade,
+    /// Daily (UTC) returns of the mark-to-market equity curve
+    #[default]
+    Daily,
+}
+
+impl std::str::FromStr for MetricsBasis {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "trade" => Ok(MetricsBasis::Trade),
+            "daily" => Ok(MetricsBasis::Daily),
+            _ => Err(format!("Unknown metrics basis: {s}")),
+        }
+    }
+}
+
+impl Default for MetricsOptions {
+    fn default() -> Self {
+        MetricsOptions {
+            omega_threshold: 0.0,
+            initial_capital: 100_000.0,
+            annual_factor: 252.0,
+            risk_free_rate: 0.0,
+            rolling_window: 100,
+            drawdown_threshold: 0.0,
+            exclude_end_of_data: false,
+            basis: MetricsBasis::Daily,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
 pub struct BacktestMetrics {
     pub total_pnl: f64,
+    /// Commission over all trades, both sides times quantity
+    pub total_commission_paid: f64,
+    /// Daily funding charged over all trades
+    pub total_funding_paid: f64,
     pub num_trades: usize,
     pub num_wins: usize,
     pub num_losses: usize,
+    /// Trades closed by the engine rather than a strategy signal (stops, targets, time
+    /// exits and the end-of-data close)
+    pub num_forced_exits: usize,
     pub win_rate: f64,
     pub profit_factor: f64,
     pub avg_win: f64,
     pub avg_loss: f64,
     pub largest_win: f64,
     pub largest_loss: f64,
+    /// Kelly fraction from win rate and avg win / avg loss payoff, floored at 0
+    pub kelly_fraction: f64,
+    /// Vince's optimal f: fraction of the largest loss risked per trade that maximizes
+    /// terminal wealth (0 when there are no losses)
+    pub optimal_f: f64,
     pub max_drawdown: f64,
     pub max_drawdown_pct: f64,
+    /// Longest drawdown (peak to recovery) measured in equity curve points
+    pub max_drawdown_duration_bars: usize,
+    /// Longest drawdown (peak to recovery) measured in seconds
+    pub max_drawdown_duration_secs: f64,
+    /// Annualized Sharpe over `MetricsOptions::basis`: daily (UTC) returns on equity by
+    /// default, or per-trade PnL
     pub sharpe_ratio: f64,
+    /// Annualized Sortino (downside deviation) over the same series as `sharpe_ratio`
+    pub sortino_ratio: f64,
+    /// Sharpe of per-trade PnL, annualized as if each trade were a day
+    pub sharpe_per_trade: f64,
+    /// Trades per day with equity activity
+    pub trades_per_day: f64,
+    /// Annualized mean over std of per-step PnL in excess of a benchmark (0 without one)
+    pub information_ratio: f64,
+    /// Regression slope of per-step strategy returns on benchmark returns (NaN without one)
+    pub beta: f64,
+    /// Jensen's alpha, annualized, as a fraction of initial capital (NaN without a benchmark)
+    pub alpha: f64,
+    /// Annualized return in excess of the risk-free rate per unit of beta (NaN without a
+    /// benchmark)
+    pub treynor_ratio: f64,
+    /// Regression slope of daily (UTC) strategy PnL on daily benchmark PnL (0 without a
+    /// benchmark or with fewer than two days)
+    pub daily_beta: f64,
+    /// Mean daily strategy PnL minus `daily_beta` times mean daily benchmark PnL, in
+    /// dollars (0 without a benchmark or with fewer than two days)
+    pub daily_alpha: f64,
+    /// PnL of statically holding the traded instrument over the backtest (None unless a
+    /// buy-and-hold benchmark was requested)
+    pub benchmark_total_pnl: Option<f64>,
+    /// `sharpe_ratio` of the buy-and-hold benchmark
+    pub benchmark_sharpe: Option<f64>,
+    /// `max_drawdown` of the buy-and-hold benchmark
+    pub benchmark_max_drawdown: Option<f64>,
+    /// Total PnL over initial capital, per year elapsed
+    pub annualized_return: f64,
+    /// Per-trade PnL standard deviation scaled by sqrt(annual factor), in dollars
+    pub annualized_volatility: f64,
+    /// Total PnL as a percentage of initial capital
+    pub total_return_pct: f64,
+    /// Compound annual growth rate of equity from initial capital to the final equity
+    /// point over the years spanned, as a fraction (-1 if equity was wiped out)
+    pub cagr: f64,
+    /// Standard deviation of daily (UTC) percentage returns on equity, annualized
+    pub volatility_annualized_pct: f64,
+    /// RMS of percentage drawdowns over the equity curve
+    pub ulcer_index: f64,
+    /// Mean trade PnL divided by the Ulcer Index
+    pub ulcer_performance_index: f64,
+    pub omega_ratio: f64,
+    /// System Quality Number (Van Tharp)
+    pub sqn: f64,
+    /// Historical per-trade Value-at-Risk at 95% confidence (positive = loss, NaN with
+    /// fewer than 20 trades)
+    pub var_95: f64,
+    /// Mean loss of trades at or beyond the 95% VaR (NaN with fewer than 20 trades)
+    pub cvar_95: f64,
+    /// As `var_95` at 99% confidence
+    pub var_99: f64,
+    /// As `cvar_95` at 99% confidence
+    pub cvar_99: f64,
     pub avg_holding_time_secs: f64,
+    /// Mean R-multiple over trades with a stop (0 when no stop was set)
+    pub avg_r: f64,
+    /// Sample standard deviation of R-multiples
+    pub std_r: f64,
+    /// Mean trade PnL over mean initial risk
+    pub expectancy_r: f64,
+    /// Mean PnL of trades entered with `drawdown_at_entry` above the drawdown threshold
+    /// (NaN if there are none)
+    pub avg_pnl_after_drawdown: f64,
     pub num_long: usize,
     pub num_short: usize,
 }
 
-pub fn compute_metrics(trades: &[Trade], equity_curve: &[f64]) -> BacktestMetrics {
+/// `timestamps_us` must be aligned with `equity_curve` (one timestamp per equity point).
+pub fn compute_metrics(
+    trades: &[Trade],
+    equity_curve: &[f64],
+    timestamps_us: &[i64],
+    options: &MetricsOptions,
+) -> BacktestMetrics {
     let num_trades = trades.len();
     if num_trades == 0 {
         return BacktestMetrics {
             total_pnl: 0.0,
+            total_commission_paid: 0.0,
+            total_funding_paid: 0.0,
             num_trades: 0,
             num_wins: 0,
             num_losses: 0,
+            num_forced_exits: 0,
             win_rate: 0.0,
             profit_factor: 0.0,
             avg_win: 0.0,
             avg_loss: 0.0,
             largest_win: 0.0,
             largest_loss: 0.0,
+            kelly_fraction: 0.0,
+            optimal_f: 0.0,
             max_drawdown: 0.0,
             max_drawdown_pct: 0.0,
+            max_drawdown_duration_bars: 0,
+            max_drawdown_duration_secs: 0.0,
             sharpe_ratio: 0.0,
+            sortino_ratio: 0.0,
+            sharpe_per_trade: 0.0,
+            trades_per_day: 0.0,
+            information_ratio: 0.0,
+            beta: f64::NAN,
+            alpha: f64::NAN,
+            treynor_ratio: f64::NAN,
+            daily_beta: 0.0,
+            daily_alpha: 0.0,
+            benchmark_total_pnl: None,
+            benchmark_sharpe: None,
+            benchmark_max_drawdown: None,
+            annualized_return: 0.0,
+            annualized_volatility: 0.0,
+            total_return_pct: 0.0,
+            cagr: 0.0,
+            volatility_annualized_pct: 0.0,
+            ulcer_index: 0.0,
+            ulcer_performance_index: 0.0,
+            omega_ratio: 0.0,
+            sqn: 0.0,
             avg_holding_time_secs: 0.0,
+            var_95: f64::NAN,
+            cvar_95: f64::NAN,
+            var_99: f64::NAN,
+            cvar_99: f64::NAN,
+            avg_r: 0.0,
+            std_r: 0.0,
+            expectancy_r: 0.0,
+            avg_pnl_after_drawdown: f64::NAN,
             num_long: 0,
             num_short: 0,
         };
     }
 
     let total_pnl: f64 = trades.iter().map(|t| t.pnl).sum();
+    let total_commission_paid: f64 = trades.iter().map(|t| t.commission).sum();
+    let total_funding_paid: f64 = trades.iter().map(|t| t.funding).sum();
     let mut gross_profit = 0.0_f64;
     let mut gross_loss = 0.0_f64;
     let mut num_wins = 0usize;
@@ -53,8 +246,24 @@ pub fn compute_metrics(trades: &[Trade], equity_curve: &[f64]) -> BacktestMetric
     let mut total_holding_us = 0i64;
     let mut num_long = 0usize;
     let mut num_short = 0usize;
+    let mut num_forced_exits = 0usize;
+    // Trades counted in the win/loss statistics
+    let mut num_scored = 0usize;
 
     for t in trades {
+        total_holding_us += t.exit_time_us - t.entry_time_us;
+        match t.side {
+            Side::Long => num_long += 1,
+            Side::Short => num_short += 1,
+            _ => {}
+        }
+        if t.exit_reason != ExitReason::Signal {
+            num_forced_exits += 1;
+        }
+        if options.exclude_end_of_data && t.exit_reason == ExitReason::EndOfData {
+            continue;
+        }
+        num_scored += 1;
         if t.pnl > 0.0 {
             num_wins += 1;
             gross_profit += t.pnl;
@@ -68,15 +277,13 @@ pub fn compute_metrics(trades: &[Trade], equity_curve: &[f64]) -> BacktestMetric
                 largest_loss = t.pnl;
             }
         }
-        total_holding_us += t.exit_time_us - t.entry_time_us;
-        match t.side {
-            Side::Long => num_long += 1,
-            Side::Short => num_short += 1,
-            _ => {}
-        }
     }
 
-    let win_rate = num_wins as f64 / num_trades as f64;
+    let win_rate = if num_scored > 0 {
+        num_wins as f64 / num_scored as f64
+    } else {
+        0.0
+    };
     let profit_factor = if gross_loss > 0.0 {
         gross_profit / gross_loss
     } else if gross_profit > 0.0 {
@@ -84,38 +291,320 @@ pub fn compute_metrics(trades: &[Trade], equity_curve: &[f64]) -> BacktestMetric
     } else {
         0.0
     };
-    let avg_win = if num_wins > 0 { gross_profit / num_wins as f64 } else { 0.0 };
-    let avg_loss = if num_losses > 0 { -(gross_loss / num_losses as f64) } else { 0.0 };
+    let avg_win = if num_wins > 0 {
+        gross_profit / num_wins as f64
+    } else {
+        0.0
+    };
+    let avg_loss = if num_losses > 0 {
+        -(gross_loss / num_losses as f64)
+    } else {
+        0.0
+    };
     let avg_holding_time_secs = (total_holding_us as f64 / num_trades as f64) / 1_000_000.0;
 
     // Max drawdown from equity curve
     let (max_drawdown, max_drawdown_pct) = calc_max_drawdown(equity_curve);
+    let ulcer_index = calc_ulcer_index(equity_curve);
+    let ulcer_performance_index = if ulcer_index > 0.0 {
+        (total_pnl / num_trades as f64) / ulcer_index
+    } else {
+        0.0
+    };
+    let (max_drawdown_duration_bars, max_drawdown_duration_secs) =
+        calc_drawdown_duration(equity_curve, timestamps_us);
 
-    // Sharpe ratio from per-trade returns
     let trade_pnls: Vec<f64> = trades.iter().map(|t| t.pnl).collect();
-    let sharpe_ratio = calc_sharpe(&trade_pnls);
+    let daily_pnls = calc_daily_pnls(equity_curve, timestamps_us, options.initial_capital);
+    let daily = compute_metrics_from_returns(
+        equity_curve,
+        timestamps_us,
+        US_PER_DAY,
+        options.initial_capital,
+        options.annual_factor,
+    );
+    let sharpe_per_trade = calc_sharpe(&trade_pnls, options.annual_factor);
+    let (sharpe_ratio, sortino_ratio) = match options.basis {
+        MetricsBasis::Trade => (
+            sharpe_per_trade,
+            calc_sortino(&trade_pnls, options.annual_factor),
+        ),
+        MetricsBasis::Daily => (daily.sharpe_ratio, daily.sortino_ratio),
+    };
+    let trades_per_day = if daily_pnls.is_empty() {
+        0.0
+    } else {
+        num_trades as f64 / daily_pnls.len() as f64
+    };
+    let omega_ratio = calc_omega(&trade_pnls, options.omega_threshold);
+    let years = years_spanned(trades, timestamps_us);
+    let annualized_return = calc_annualized_return(total_pnl, years, options.initial_capital);
+    let annualized_volatility = sample_std(&trade_pnls) * options.annual_factor.sqrt();
+    let total_return_pct = total_pnl / options.initial_capital * 100.0;
+    let final_equity = equity_curve
+        .last()
+        .copied()
+        .unwrap_or(options.initial_capital + total_pnl);
+    let cagr = calc_cagr(options.initial_capital, final_equity, years);
+    let volatility_annualized_pct = daily.volatility_annualized_pct;
+    let sqn = calc_sqn(&trade_pnls);
+    let (var_95, cvar_95) = calc_var_cvar(&trade_pnls, 0.95);
+    let (var_99, cvar_99) = calc_var_cvar(&trade_pnls, 0.99);
+
+    let (avg_r, std_r, expectancy_r) = calc_r_stats(trades);
+    let after_drawdown: Vec<f64> = trades
+        .iter()
+        .filter(|t| t.drawdown_at_entry > options.drawdown_threshold)
+        .map(|t| t.pnl)
+        .collect();
+    let avg_pnl_after_drawdown = if after_drawdown.is_empty() {
+        f64::NAN
+    } else {
+        after_drawdown.iter().sum::<f64>() / after_drawdown.len() as f64
+    };
 
     BacktestMetrics {
         total_pnl,
+        total_commission_paid,
+        total_funding_paid,
         num_trades,
         num_wins,
         num_losses,
+        num_forced_exits,
         win_rate,
         profit_factor,
         avg_win,
         avg_loss,
         largest_win,
         largest_loss,
+        kelly_fraction: calc_kelly(win_rate, avg_win, avg_loss),
+        optimal_f: calc_optimal_f(trades, largest_loss),
         max_drawdown,
         max_drawdown_pct,
+        max_drawdown_duration_bars,
+        max_drawdown_duration_secs,
         sharpe_ratio,
+        sortino_ratio,
+        sharpe_per_trade,
+        trades_per_day,
+        information_ratio: 0.0,
+        beta: f64::NAN,
+        alpha: f64::NAN,
+        treynor_ratio: f64::NAN,
+        daily_beta: 0.0,
+        daily_alpha: 0.0,
+        benchmark_total_pnl: None,
+        benchmark_sharpe: None,
+        benchmark_max_drawdown: None,
+        annualized_return,
+        annualized_volatility,
+        total_return_pct,
+        cagr,
+        volatility_annualized_pct,
+        ulcer_index,
+        ulcer_performance_index,
+        omega_ratio,
+        sqn,
+        var_95,
+        cvar_95,
+        var_99,
+        cvar_99,
         avg_holding_time_secs,
+        avg_r,
+        std_r,
+        expectancy_r,
+        avg_pnl_after_drawdown,
         num_long,
         num_short,
     }
 }
 
-fn calc_max_drawdown(equity: &[f64]) -> (f64, f64) {
+/// Trade statistics for one calendar month.
+#[derive(Clone, Debug)]
+pub struct MonthlyStats {
+    pub num_trades: usize,
+    pub total_pnl: f64,
+    /// NaN when the month has fewer than two trades
+    pub sharpe_ratio: f64,
+    /// NaN when the month has fewer than two trades
+    pub sortino_ratio: f64,
+}
+
+/// Bucket trades by the UTC calendar month of their exit, keyed by (year, month).
+/// Sharpe and Sortino use that month's trade PnLs, annualized like `sharpe_per_trade`.
+pub fn compute_monthly_stats(
+    trades: &[Trade],
+    annual_factor: f64,
+) -> BTreeMap<(i32, u32), MonthlyStats> {
+    let mut months: BTreeMap<(i32, u32), Vec<f64>> = BTreeMap::new();
+    for t in trades {
+        let (year, month, _) = civil_from_days(t.exit_time_us.div_euclid(US_PER_DAY));
+        months.entry((year, month)).or_default().push(t.pnl);
+    }
+    months
+        .into_iter()
+        .map(|(key, pnls)| {
+            let (sharpe_ratio, sortino_ratio) = if pnls.len() < 2 {
+                (f64::NAN, f64::NAN)
+            } else {
+                (
+                    calc_sharpe(&pnls, annual_factor),
+                    calc_sortino(&pnls, annual_factor),
+                )
+            };
+            let stats = MonthlyStats {
+                num_trades: pnls.len(),
+                total_pnl: pnls.iter().sum(),
+                sharpe_ratio,
+                sortino_ratio,
+            };
+            (key, stats)
+        })
+        .collect()
+}
+
+/// Trade statistics for one hour-of-day or weekday bucket.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TimeBucket {
+    pub num_trades: usize,
+    pub total_pnl: f64,
+    pub num_wins: usize,
+}
+
+impl TimeBucket {
+    /// Fraction of winning trades, NaN for an empty bucket.
+    pub fn win_rate(&self) -> f64 {
+        if self.num_trades == 0 {
+            f64::NAN
+        } else {
+            self.num_wins as f64 / self.num_trades as f64
+        }
+    }
+}
+
+/// Trades bucketed by the local hour and weekday of their entry.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TimeBuckets {
+    /// Hours 0-23
+    pub by_hour: [TimeBucket; 24],
+    /// Monday = 0 to Sunday = 6
+    pub by_weekday: [TimeBucket; 7],
+}
+
+/// Bucket trades by the hour of day and weekday of their entry in `clock`'s local time.
+/// A trade entered exactly on the hour counts towards that hour.
+pub fn compute_time_buckets(trades: &[Trade], clock: &SessionClock) -> TimeBuckets {
+    let mut buckets = TimeBuckets::default();
+    for t in trades {
+        let local_secs =
+            t.entry_time_us.div_euclid(1_000_000) + clock.utc_offset_at(t.entry_time_us);
+        let hour = local_secs.rem_euclid(86_400) / 3600;
+        // 1970-01-01 was a Thursday
+        let weekday = (local_secs.div_euclid(86_400) + 3).rem_euclid(7);
+        for bucket in [
+            &mut buckets.by_hour[hour as usize],
+            &mut buckets.by_weekday[weekday as usize],
+        ] {
+            bucket.num_trades += 1;
+            bucket.total_pnl += t.pnl;
+            bucket.num_wins += usize::from(t.pnl > 0.0);
+        }
+    }
+    buckets
+}
+
+/// Metrics for each strategy tag, computed over the trades entered with that tag.
+/// Each subset's equity curve is `initial_capital` plus its cumulative realized PnL,
+/// sampled at trade exits.
+pub fn compute_metrics_by_tag(
+    trades: &[Trade],
+    options: &MetricsOptions,
+) -> BTreeMap<i32, BacktestMetrics> {
+    let mut groups: BTreeMap<i32, Vec<Trade>> = BTreeMap::new();
+    for t in trades {
+        groups.entry(t.tag).or_default().push(t.clone());
+    }
+    groups
+        .into_iter()
+        .map(|(tag, subset)| {
+            let mut equity = Vec::with_capacity(subset.len());
+            let mut timestamps = Vec::with_capacity(subset.len());
+            let mut cum = options.initial_capital;
+            for t in &subset {
+                cum += t.pnl;
+                equity.push(cum);
+                timestamps.push(t.exit_time_us);
+            }
+            let m = compute_metrics(&subset, &equity, &timestamps, options);
+            (tag, m)
+        })
+        .collect()
+}
+
+/// Kelly fraction `p - (1 - p) / b` with `b = avg_win / |avg_loss|`. 0 when there are no
+/// losses to size against, and negative edges are clamped to 0 (don't trade).
+fn calc_kelly(win_rate: f64, avg_win: f64, avg_loss: f64) -> f64 {
+    if avg_loss == 0.0 || avg_win <= 0.0 {
+        return 0.0;
+    }
+    let payoff = avg_win / avg_loss.abs();
+    (win_rate - (1.0 - win_rate) / payoff).max(0.0)
+}
+
+/// Optimal f by grid search over f in (0, 1] at 0.01 steps, maximizing the log of the
+/// terminal wealth relative `prod(1 + f * pnl / |largest_loss|)`.
+fn calc_optimal_f(trades: &[Trade], largest_loss: f64) -> f64 {
+    if largest_loss >= 0.0 {
+        return 0.0;
+    }
+    let worst = largest_loss.abs();
+    let mut best_f = 0.0;
+    let mut best_log_twr = 0.0;
+    for step in 1..=100 {
+        let f = step as f64 / 100.0;
+        let mut log_twr = 0.0;
+        for t in trades {
+            let hpr = 1.0 + f * t.pnl / worst;
+            if hpr <= 0.0 {
+                log_twr = f64::NEG_INFINITY;
+                break;
+            }
+            log_twr += hpr.ln();
+        }
+        if log_twr > best_log_twr {
+            best_log_twr = log_twr;
+            best_f = f;
+        }
+    }
+    best_f
+}
+
+/// (mean R, std R, expectancy in R) over the trades that carry an R-multiple.
+fn calc_r_stats(trades: &[Trade]) -> (f64, f64, f64) {
+    let with_r: Vec<&Trade> = trades.iter().filter(|t| t.r_multiple.is_some()).collect();
+    if with_r.is_empty() {
+        return (0.0, 0.0, 0.0);
+    }
+    let n = with_r.len() as f64;
+    let rs: Vec<f64> = with_r.iter().filter_map(|t| t.r_multiple).collect();
+    let avg_r = rs.iter().sum::<f64>() / n;
+    let std_r = if rs.len() > 1 {
+        (rs.iter().map(|r| (r - avg_r).powi(2)).sum::<f64>() / (n - 1.0)).sqrt()
+    } else {
+        0.0
+    };
+    let mean_pnl = with_r.iter().map(|t| t.pnl).sum::<f64>() / n;
+    let mean_risk = with_r.iter().filter_map(|t| t.initial_risk).sum::<f64>() / n;
+    let expectancy_r = if mean_risk > 0.0 {
+        mean_pnl / mean_risk
+    } else {
+        0.0
+    };
+    (avg_r, std_r, expectancy_r)
+}
+
+/// Largest peak-to-trough drop of `equity`, in dollars and as a percent of the peak.
+pub fn calc_max_drawdown(equity: &[f64]) -> (f64, f64) {
     if equity.is_empty() {
         return (0.0, 0.0);
     }
@@ -141,7 +630,397 @@ fn calc_max_drawdown(equity: &[f64]) -> (f64, f64) {
     (max_dd, max_dd_pct * 100.0)
 }
 
-fn calc_sharpe(pnls: &[f64]) -> f64 {
+/// Ulcer Index: `sqrt(mean(dd_pct^2))` where `dd_pct` is the percentage drawdown from the
+/// running peak at each equity point (points before a positive peak count as 0).
+fn calc_ulcer_index(equity: &[f64]) -> f64 {
+    if equity.is_empty() {
+        return 0.0;
+    }
+    let mut peak = equity[0];
+    let mut sum_sq = 0.0_f64;
+    for &eq in equity {
+        if eq > peak {
+            peak = eq;
+        }
+        if peak > 0.0 {
+            let dd_pct = (peak - eq) / peak * 100.0;
+            sum_sq += dd_pct * dd_pct;
+        }
+    }
+    (sum_sq / equity.len() as f64).sqrt()
+}
+
+/// Longest drawdown as (equity points, seconds), measured from the peak that started it
+/// to the first point that gets back to that peak. An unrecovered drawdown runs to the end.
+fn calc_drawdown_duration(equity: &[f64], timestamps_us: &[i64]) -> (usize, f64) {
+    if equity.is_empty() {
+        return (0, 0.0);
+    }
+    let has_time = timestamps_us.len() == equity.len();
+    let mut peak = equity[0];
+    let mut peak_idx = 0usize;
+    let mut in_drawdown = false;
+    let mut max_bars = 0usize;
+    let mut max_us = 0i64;
+
+    let mut record = |start: usize, end: usize| {
+        if end - start > max_bars {
+            max_bars = end - start;
+        }
+        if has_time {
+            let us = timestamps_us[end] - timestamps_us[start];
+            if us > max_us {
+                max_us = us;
+            }
+        }
+    };
+
+    for (i, &eq) in equity.iter().enumerate() {
+        if eq >= peak {
+            if in_drawdown {
+                record(peak_idx, i);
+                in_drawdown = false;
+            }
+            peak = eq;
+            peak_idx = i;
+        } else {
+            in_drawdown = true;
+        }
+    }
+    if in_drawdown {
+        record(peak_idx, equity.len() - 1);
+    }
+    (max_bars, max_us as f64 / 1_000_000.0)
+}
+
+fn calc_sharpe(pnls: &[f64], annual_factor: f64) -> f64 {
+    if pnls.len() < 2 {
+        return 0.0;
+    }
+    let mean = pnls.iter().sum::<f64>() / pnls.len() as f64;
+    let std = sample_std(pnls);
+    if std == 0.0 {
+        return 0.0;
+    }
+    (mean / std) * annual_factor.sqrt()
+}
+
+/// Sharpe of the equity changes within each trailing window of `window` equity points,
+/// aligned with the equity curve. The first `window - 1` entries are NaN.
+pub fn calc_rolling_sharpe(equity: &[f64], window: usize, annual_factor: f64) -> Vec<f64> {
+    let steps: Vec<f64> = equity.windows(2).map(|w| w[1] - w[0]).collect();
+    let mut out = vec![f64::NAN; equity.len()];
+    if window < 2 {
+        return out;
+    }
+    // Window ending at equity point i covers steps i+1-window .. i
+    for (i, value) in out.iter_mut().enumerate().skip(window - 1) {
+        *value = calc_sharpe(&steps[i + 1 - window..i], annual_factor);
+    }
+    out
+}
+
+/// Indices of at most `max_points` (at least 4) of `values`, for plotting: the first and
+/// last points, plus the minimum and maximum of each of `(max_points - 2) / 2` equal
+/// spans in between, in order. Every span's extremes survive, so no drawdown is hidden.
+/// All indices when there are no more than `max_points` values.
+pub fn downsample_min_max(values: &[f64], max_points: usize) -> Vec<usize> {
+    let n = values.len();
+    if n <= max_points {
+        return (0..n).collect();
+    }
+    let spans = (max_points.max(4) - 2) / 2;
+    let inner = n - 2;
+    let mut out = Vec::with_capacity(2 * spans + 2);
+    out.push(0);
+    for span in 0..spans {
+        let start = 1 + span * inner / spans;
+        let end = 1 + (span + 1) * inner / spans;
+        if start == end {
+            continue;
+        }
+        let (mut lo, mut hi) = (start, start);
+        for i in start + 1..end {
+            if values[i] < values[lo] {
+                lo = i;
+            }
+            if values[i] > values[hi] {
+                hi = i;
+            }
+        }
+        out.push(lo.min(hi));
+        if lo != hi {
+            out.push(lo.max(hi));
+        }
+    }
+    out.push(n - 1);
+    out
+}
+
+/// Information ratio of aligned per-period strategy and benchmark PnLs: mean over
+/// standard deviation of the active (strategy minus benchmark) PnL, annualized.
+pub fn calc_information_ratio(
+    strategy_pnls: &[f64],
+    benchmark_pnls: &[f64],
+    annual_factor: f64,
+) -> f64 {
+    let active: Vec<f64> = strategy_pnls
+        .iter()
+        .zip(benchmark_pnls)
+        .map(|(s, b)| s - b)
+        .collect();
+    if active.len() < 2 {
+        return 0.0;
+    }
+    let mean = active.iter().sum::<f64>() / active.len() as f64;
+    let std = sample_std(&active);
+    if std == 0.0 {
+        return 0.0;
+    }
+    (mean / std) * annual_factor.sqrt()
+}
+
+/// Fill in the information ratio, beta, alpha, Treynor ratio and daily beta and alpha of
+/// a strategy equity curve against a benchmark equity curve sampled at the same points.
+pub fn score_against_benchmark(
+    metrics: &mut BacktestMetrics,
+    equity_curve: &[f64],
+    benchmark_equity: &[f64],
+    timestamps_us: &[i64],
+    options: &MetricsOptions,
+) {
+    let step_pnls =
+        |equity: &[f64]| -> Vec<f64> { equity.windows(2).map(|w| w[1] - w[0]).collect() };
+    let strategy_pnls = step_pnls(equity_curve);
+    let benchmark_pnls = step_pnls(benchmark_equity);
+    metrics.information_ratio =
+        calc_information_ratio(&strategy_pnls, &benchmark_pnls, options.annual_factor);
+
+    // Beta and alpha on returns relative to the initial capital
+    let as_returns =
+        |pnls: &[f64]| -> Vec<f64> { pnls.iter().map(|p| p / options.initial_capital).collect() };
+    let (beta, alpha) = calc_beta_alpha(
+        &as_returns(&strategy_pnls),
+        &as_returns(&benchmark_pnls),
+        options.risk_free_rate / options.annual_factor,
+    );
+    metrics.beta = beta;
+    metrics.alpha = alpha * options.annual_factor;
+    metrics.treynor_ratio = if beta != 0.0 {
+        (metrics.annualized_return - options.risk_free_rate) / beta
+    } else {
+        f64::NAN
+    };
+
+    let daily_pnls = |equity: &[f64]| {
+        calc_daily_pnls(
+            equity,
+            timestamps_us,
+            equity.first().copied().unwrap_or(0.0),
+        )
+    };
+    (metrics.daily_beta, metrics.daily_alpha) =
+        calc_daily_beta_alpha(&daily_pnls(equity_curve), &daily_pnls(benchmark_equity));
+}
+
+/// `calc_beta_alpha` of daily dollar PnL with no risk-free rate, with zeros instead of
+/// NaN when there are fewer than two days or the benchmark is flat.
+pub fn calc_daily_beta_alpha(strategy_daily: &[f64], benchmark_daily: &[f64]) -> (f64, f64) {
+    match calc_beta_alpha(strategy_daily, benchmark_daily, 0.0) {
+        (beta, alpha) if beta.is_finite() && alpha.is_finite() => (beta, alpha),
+        _ => (0.0, 0.0),
+    }
+}
+
+/// OLS beta (cov / var) of aligned per-period strategy returns on benchmark returns, and
+/// Jensen's alpha per period given the per-period `risk_free` rate. NaN when there are
+/// fewer than two periods or the benchmark doesn't move.
+pub fn calc_beta_alpha(
+    strategy_pnls: &[f64],
+    benchmark_pnls: &[f64],
+    risk_free: f64,
+) -> (f64, f64) {
+    let n = strategy_pnls.len().min(benchmark_pnls.len());
+    if n < 2 {
+        return (f64::NAN, f64::NAN);
+    }
+    let (s, b) = (&strategy_pnls[..n], &benchmark_pnls[..n]);
+    let mean_s = s.iter().sum::<f64>() / n as f64;
+    let mean_b = b.iter().sum::<f64>() / n as f64;
+    let cov = s
+        .iter()
+        .zip(b)
+        .map(|(x, y)| (x - mean_s) * (y - mean_b))
+        .sum::<f64>()
+        / (n as f64 - 1.0);
+    let var = b.iter().map(|y| (y - mean_b).powi(2)).sum::<f64>() / (n as f64 - 1.0);
+    if var == 0.0 {
+        return (f64::NAN, f64::NAN);
+    }
+    let beta = cov / var;
+    let alpha = (mean_s - risk_free) - beta * (mean_b - risk_free);
+    (beta, alpha)
+}
+
+/// Sharpe of daily (UTC) returns on an equity curve, annualized, as in
+/// `BacktestMetrics::sharpe_ratio` on the daily basis.
+pub fn calc_daily_sharpe(
+    equity: &[f64],
+    timestamps_us: &[i64],
+    start: f64,
+    annual_factor: f64,
+) -> f64 {
+    compute_metrics_from_returns(equity, timestamps_us, US_PER_DAY, start, annual_factor)
+        .sharpe_ratio
+}
+
+/// Risk-adjusted ratios of an equity curve's periodic returns.
+#[derive(Clone, Copy, Debug)]
+pub struct ReturnsMetrics {
+    pub sharpe_ratio: f64,
+    pub sortino_ratio: f64,
+    /// Standard deviation of the period returns, annualized, in percent
+    pub volatility_annualized_pct: f64,
+    /// Periods with at least one equity point
+    pub num_periods: usize,
+}
+
+/// Resample `equity` (aligned with `timestamps_us`) to its last point in each
+/// `period_us`-long window from the Unix epoch, take each window's return on the previous
+/// window's closing equity (the first on `start`), and annualize Sharpe, Sortino and
+/// volatility with `annual_factor` periods per year. Windows without equity points are
+/// left out rather than counted as flat.
+pub fn compute_metrics_from_returns(
+    equity: &[f64],
+    timestamps_us: &[i64],
+    period_us: i64,
+    start: f64,
+    annual_factor: f64,
+) -> ReturnsMetrics {
+    let returns = calc_period_returns(equity, timestamps_us, period_us, start);
+    ReturnsMetrics {
+        sharpe_ratio: calc_sharpe(&returns, annual_factor),
+        sortino_ratio: calc_sortino(&returns, annual_factor),
+        volatility_annualized_pct: sample_std(&returns) * annual_factor.sqrt() * 100.0,
+        num_periods: returns.len(),
+    }
+}
+
+/// PnL per UTC calendar day: the change in end-of-day equity, starting from `start`.
+/// Days without any equity points are left out rather than counted as flat.
+fn calc_daily_pnls(equity: &[f64], timestamps_us: &[i64], start: f64) -> Vec<f64> {
+    calc_period_pnls(equity, timestamps_us, US_PER_DAY, start)
+}
+
+/// `calc_daily_pnls` over `period_us`-long windows from the Unix epoch.
+fn calc_period_pnls(equity: &[f64], timestamps_us: &[i64], period_us: i64, start: f64) -> Vec<f64> {
+    let mut pnls = Vec::new();
+    let mut prev_close = start;
+    let mut current: Option<(i64, f64)> = None;
+    for (&eq, &ts) in equity.iter().zip(timestamps_us) {
+        let period = ts.div_euclid(period_us);
+        match current {
+            Some((p, close)) if p != period => {
+                pnls.push(close - prev_close);
+                prev_close = close;
+            }
+            _ => {}
+        }
+        current = Some((period, eq));
+    }
+    if let Some((_, close)) = current {
+        pnls.push(close - prev_close);
+    }
+    pnls
+}
+
+/// Returns of the equity curve per `period_us` window as fractions of the previous
+/// window's closing equity, starting from `start`. Windows that open with non-positive
+/// equity return 0.
+fn calc_period_returns(
+    equity: &[f64],
+    timestamps_us: &[i64],
+    period_us: i64,
+    start: f64,
+) -> Vec<f64> {
+    let mut prev_close = start;
+    calc_period_pnls(equity, timestamps_us, period_us, start)
+        .into_iter()
+        .map(|pnl| {
+            let base = prev_close;
+            prev_close += pnl;
+            if base > 0.0 {
+                pnl / base
+            } else {
+                0.0
+            }
+        })
+        .collect()
+}
+
+/// Sample standard deviation (n - 1); 0 for fewer than two values.
+fn sample_std(values: &[f64]) -> f64 {
+    if values.len() < 2 {
+        return 0.0;
+    }
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    (values.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt()
+}
+
+/// Years spanned by the equity curve (or by the trades, if there are no equity
+/// timestamps).
+fn years_spanned(trades: &[Trade], timestamps_us: &[i64]) -> f64 {
+    let (start, end) = match (timestamps_us.first(), timestamps_us.last()) {
+        (Some(&first), Some(&last)) => (first, last),
+        _ => (
+            trades.iter().map(|t| t.entry_time_us).min().unwrap_or(0),
+            trades.iter().map(|t| t.exit_time_us).max().unwrap_or(0),
+        ),
+    };
+    (end - start) as f64 / US_PER_YEAR
+}
+
+/// Total PnL as a fraction of `initial_capital`, divided by `years`.
+fn calc_annualized_return(total_pnl: f64, years: f64, initial_capital: f64) -> f64 {
+    if years <= 0.0 || initial_capital <= 0.0 {
+        return 0.0;
+    }
+    total_pnl / initial_capital / years
+}
+
+/// `(final_equity / initial_capital)^(1 / years) - 1`; 0 over a zero span and -1 once
+/// equity reaches zero.
+fn calc_cagr(initial_capital: f64, final_equity: f64, years: f64) -> f64 {
+    if years <= 0.0 || initial_capital <= 0.0 {
+        return 0.0;
+    }
+    if final_equity <= 0.0 {
+        return -1.0;
+    }
+    (final_equity / initial_capital).powf(1.0 / years) - 1.0
+}
+
+/// Omega ratio: sum of gains above `threshold` over sum of shortfalls below it.
+fn calc_omega(pnls: &[f64], threshold: f64) -> f64 {
+    let mut upside = 0.0_f64;
+    let mut downside = 0.0_f64;
+    for &p in pnls {
+        upside += (p - threshold).max(0.0);
+        downside += (threshold - p).max(0.0);
+    }
+    if downside > 0.0 {
+        upside / downside
+    } else if upside > 0.0 {
+        f64::INFINITY
+    } else {
+        0.0
+    }
+}
+
+/// System Quality Number as defined by Van Tharp: `sqrt(N) * expectancy / stdev(R)`, with R
+/// taken as the per-trade PnL. Rough guide: > 2.0 is good, > 3.0 excellent.
+fn calc_sqn(pnls: &[f64]) -> f64 {
     if pnls.len() < 2 {
         return 0.0;
     }
@@ -152,7 +1031,417 @@ fn calc_sharpe(pnls: &[f64]) -> f64 {
     if std == 0.0 {
         return 0.0;
     }
-    // Annualize: assume ~252 trading days, ~20 trades/day as rough approximation
-    // Or just use sqrt(n) for total-period normalization
-    (mean / std) * (252.0_f64).sqrt()
+    n.sqrt() * mean / std
+}
+
+/// Fewer trades than this give `NaN` VaR/CVaR, the tail being too thin to estimate.
+const MIN_VAR_TRADES: usize = 20;
+
+/// Historical VaR and CVaR of per-trade PnL at the given confidence level, as losses
+/// (positive = loss). VaR is the negated `1 - confidence` percentile (linearly
+/// interpolated); CVaR is the negated mean of the PnLs at or below that percentile.
+fn calc_var_cvar(pnls: &[f64], confidence: f64) -> (f64, f64) {
+    if pnls.len() < MIN_VAR_TRADES {
+        return (f64::NAN, f64::NAN);
+    }
+    let mut sorted = pnls.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let quantile = percentile(&sorted, 1.0 - confidence);
+    let tail: Vec<f64> = sorted
+        .iter()
+        .copied()
+        .take_while(|&p| p <= quantile)
+        .collect();
+    // The lowest PnL is always at or below the interpolated percentile
+    let cvar = tail.iter().sum::<f64>() / tail.len() as f64;
+    (-quantile, -cvar)
+}
+
+/// Linearly interpolated percentile (`p` in [0, 1]) of ascending, non-empty `sorted`.
+pub fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let pos = p * (sorted.len() - 1) as f64;
+    let lo = pos.floor() as usize;
+    let hi = pos.ceil() as usize;
+    sorted[lo] + (sorted[hi] - sorted[lo]) * (pos - lo as f64)
+}
+
+/// Sortino ratio: mean over downside deviation (RMS of negative PnLs), annualized like
+/// `calc_sharpe`.
+fn calc_sortino(pnls: &[f64], annual_factor: f64) -> f64 {
+    if pnls.len() < 2 {
+        return 0.0;
+    }
+    let n = pnls.len() as f64;
+    let mean = pnls.iter().sum::<f64>() / n;
+    let downside = (pnls.iter().map(|x| x.min(0.0).powi(2)).sum::<f64>() / n).sqrt();
+    if downside == 0.0 {
+        return if mean > 0.0 { f64::INFINITY } else { 0.0 };
+    }
+    (mean / downside) * annual_factor.sqrt()
+}
+
+/// Convert days since 1970-01-01 to a (year, month, day) civil date (proleptic Gregorian).
+pub fn civil_from_days(days: i64) -> (i32, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = (yoe + era * 400 + i64::from(month <= 2)) as i32;
+    (year, month, day)
+}
+
+/// Convert a (year, month, day) civil date (proleptic Gregorian) to days since 1970-01-01.
+pub fn days_from_civil(year: i32, month: u32, day: u32) -> i64 {
+    let y = i64::from(year) - i64::from(month <= 2);
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let mp = (i64::from(month) + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+    use crate::position::{ExitReason, FillContext, PositionTracker, Side};
+
+    const DAY: i64 = US_PER_DAY;
+
+    fn trade(exit_time_us: i64, pnl: f64, tag: i32) -> Trade {
+        Trade {
+            entry_time_us: exit_time_us - 3_600_000_000,
+            exit_time_us,
+            side: Side::Long,
+            entry_price: 100.0,
+            exit_price: 100.0,
+            quantity: 1.0,
+            pnl,
+            pnl_ticks: 0.0,
+            return_pct: 0.0,
+            capital_return_pct: 0.0,
+            holding_cost: 0.0,
+            funding: 0.0,
+            commission: 0.0,
+            exit_reason: ExitReason::Signal,
+            partial: false,
+            initial_risk: None,
+            r_multiple: None,
+            tag,
+            bars_held: 1,
+            equity_at_entry: 100_000.0,
+            drawdown_at_entry: 0.0,
+        }
+    }
+
+    #[test]
+    fn max_drawdown_pct_is_relative_to_account_equity() {
+        // $100k grows to $110k, then falls 10% to $99k
+        let trades = [trade(DAY, 10_000.0, 0), trade(2 * DAY, -11_000.0, 0)];
+        let equity = [100_000.0, 110_000.0, 99_000.0];
+        let timestamps = [0, DAY, 2 * DAY];
+        let m = compute_metrics(&trades, &equity, &timestamps, &MetricsOptions::default());
+        assert_eq!(m.max_drawdown, 11_000.0);
+        assert!((m.max_drawdown_pct - 10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn tag_equity_curves_start_at_initial_capital() {
+        let trades = [
+            trade(DAY, 10_000.0, 1),
+            trade(2 * DAY, 500.0, 2),
+            trade(3 * DAY, -11_000.0, 1),
+            trade(4 * DAY, 500.0, 2),
+        ];
+        let by_tag = compute_metrics_by_tag(&trades, &MetricsOptions::default());
+        let tag1 = &by_tag[&1];
+        assert!((tag1.max_drawdown_pct - 10.0).abs() < 1e-9);
+        // Two winning days on $100k: no drawdown and a positive, finite Sharpe
+        let tag2 = &by_tag[&2];
+        assert_eq!(tag2.max_drawdown_pct, 0.0);
+        assert!(tag2.cagr > 0.0);
+        assert!(tag2.volatility_annualized_pct < 1.0);
+    }
+
+    #[test]
+    fn pnl_after_drawdown_counts_trades_entered_below_the_peak() {
+        let mut tracker = PositionTracker::new(0.0, 50.0);
+        tracker.initial_capital = 100_000.0;
+        // +$100, then -$200, then a third trade entered $200 below the realized peak
+        let steps = [
+            (1, 100.0),
+            (0, 102.0),
+            (1, 102.0),
+            (0, 98.0),
+            (1, 98.0),
+            (0, 101.0),
+        ];
+        for (i, &(signal, price)) in steps.iter().enumerate() {
+            tracker.process_signal(signal, price, i as i64 * DAY, &FillContext::default());
+        }
+        let dd: Vec<f64> = tracker.trades.iter().map(|t| t.drawdown_at_entry).collect();
+        assert_eq!(dd, [0.0, 0.0, 200.0]);
+        assert_eq!(tracker.trades[2].pnl, 150.0);
+
+        let m = compute_metrics(
+            &tracker.trades,
+            &tracker.equity_curve,
+            &tracker.equity_timestamps,
+            &MetricsOptions::default(),
+        );
+        assert_eq!(m.avg_pnl_after_drawdown, 150.0);
+        let strict = MetricsOptions {
+            drawdown_threshold: 200.0,
+            ..MetricsOptions::default()
+        };
+        let m = compute_metrics(
+            &tracker.trades,
+            &tracker.equity_curve,
+            &tracker.equity_timestamps,
+            &strict,
+        );
+        assert!(m.avg_pnl_after_drawdown.is_nan());
+    }
+
+    #[test]
+    fn ulcer_index_matches_a_hand_calculation() {
+        // Drawdowns from the running peak: 0%, 10%, 5%, 0%, 20%
+        let equity = [100.0, 90.0, 95.0, 100.0, 80.0];
+        // sqrt((0 + 100 + 25 + 0 + 400) / 5)
+        assert!((calc_ulcer_index(&equity) - 105.0_f64.sqrt()).abs() < 1e-12);
+        assert_eq!(calc_ulcer_index(&[100.0, 101.0, 102.0]), 0.0);
+        assert_eq!(calc_ulcer_index(&[]), 0.0);
+    }
+
+    #[test]
+    fn downsampling_keeps_the_global_extremes() {
+        let mut equity: Vec<f64> = (0..10_000)
+            .map(|i| 100_000.0 + 500.0 * (i as f64 / 300.0).sin() + i as f64)
+            .collect();
+        equity[4_321] = 90_000.0;
+        equity[7_777] = 120_000.0;
+        for max_points in [4, 7, 100, 101, 1_000] {
+            let keep = downsample_min_max(&equity, max_points);
+            assert!(keep.len() <= max_points, "{max_points}");
+            assert!(keep.windows(2).all(|w| w[0] < w[1]));
+            assert_eq!((keep[0], keep[keep.len() - 1]), (0, equity.len() - 1));
+            assert!(keep.contains(&4_321) && keep.contains(&7_777));
+        }
+        assert_eq!(
+            downsample_min_max(&equity[..50], 100),
+            (0..50).collect::<Vec<_>>()
+        );
+    }
+
+    /// Trades closing one per day with the given PnLs.
+    fn daily_trades(pnls: &[f64]) -> Vec<Trade> {
+        pnls.iter()
+            .enumerate()
+            .map(|(i, &pnl)| trade((i as i64 + 1) * DAY, pnl, 0))
+            .collect()
+    }
+
+    #[test]
+    fn kelly_for_a_60_percent_two_to_one_system() {
+        // 0.6 - 0.4 / 2
+        assert!((calc_kelly(0.6, 200.0, -100.0) - 0.4).abs() < 1e-12);
+        assert_eq!(calc_kelly(0.6, 200.0, 0.0), 0.0);
+        // A negative edge means don't trade
+        assert_eq!(calc_kelly(0.3, 100.0, -100.0), 0.0);
+
+        let trades = daily_trades(&[
+            200.0, -100.0, 200.0, 200.0, -100.0, 200.0, -100.0, 200.0, 200.0, -100.0,
+        ]);
+        let m = compute_metrics(&trades, &[], &[], &MetricsOptions::default());
+        assert!((m.kelly_fraction - 0.4).abs() < 1e-12);
+        // 6 ln(1 + 2f) + 4 ln(1 - f) peaks at f = 0.4
+        assert!((m.optimal_f - 0.4).abs() < 1e-12);
+    }
+
+    #[test]
+    fn var_95_interpolates_the_fifth_percentile() {
+        // -50, -49, ..., 49: the 5th percentile sits 4.95 of 99 steps up, at -45.05
+        let pnls: Vec<f64> = (-50..50).rev().map(f64::from).collect();
+        let m = compute_metrics(&daily_trades(&pnls), &[], &[], &MetricsOptions::default());
+        assert!((m.var_95 - 45.05).abs() < 1e-9);
+        // Mean of -50..=-46
+        assert!((m.cvar_95 - 48.0).abs() < 1e-9);
+
+        let few = compute_metrics(
+            &daily_trades(&pnls[..19]),
+            &[],
+            &[],
+            &MetricsOptions::default(),
+        );
+        assert!(few.var_95.is_nan() && few.cvar_95.is_nan());
+    }
+
+    /// Metrics of `days` daily trades cycling through `pattern`, with the equity curve
+    /// marked once a day.
+    fn cycled_days(pattern: &[f64], days: usize) -> BacktestMetrics {
+        let pnls: Vec<f64> = pattern.iter().copied().cycle().take(days).collect();
+        let trades = daily_trades(&pnls);
+        let options = MetricsOptions::default();
+        let mut equity = vec![options.initial_capital];
+        for pnl in &pnls {
+            equity.push(equity[equity.len() - 1] + pnl);
+        }
+        let timestamps: Vec<i64> = (0..equity.len() as i64).map(|i| i * DAY).collect();
+        compute_metrics(&trades, &equity, &timestamps, &options)
+    }
+
+    #[test]
+    fn daily_sharpe_does_not_grow_with_the_span() {
+        let pattern = [1_000.0, -1_000.0, 500.0];
+        let short = cycled_days(&pattern, 126);
+        let long = cycled_days(&pattern, 252);
+        assert!(short.sharpe_ratio > 0.0);
+        // Equal up to the sample-variance correction and slightly smaller returns as
+        // equity grows
+        assert!((long.sharpe_ratio / short.sharpe_ratio - 1.0).abs() < 0.02);
+        // One trade a day, over the days with equity points including the starting one
+        assert_eq!(short.trades_per_day, 126.0 / 127.0);
+        assert_eq!(long.trades_per_day, 252.0 / 253.0);
+        // $500 every three days on $100k is about 0.6% a year either way
+        let yearly = 500.0 / 3.0 * 365.25 / 100_000.0;
+        for m in [&short, &long] {
+            assert!(
+                (m.annualized_return - yearly).abs() < 1e-3,
+                "{}",
+                m.annualized_return
+            );
+        }
+    }
+
+    #[test]
+    fn rolling_sharpe_is_aligned_with_the_equity_curve() {
+        // Choppy for ten points, then steady growth of 10 or 11 a point
+        let mut equity = vec![
+            100.0, 103.0, 99.0, 104.0, 98.0, 102.0, 97.0, 101.0, 99.0, 100.0,
+        ];
+        for i in 0..10 {
+            equity.push(equity[equity.len() - 1] + 10.0 + (i % 2) as f64);
+        }
+        let rolling = calc_rolling_sharpe(&equity, 5, 252.0);
+        assert_eq!(rolling.len(), equity.len());
+        assert!(rolling[..4].iter().all(|v| v.is_nan()));
+        assert!(rolling[4..].iter().all(|v| v.is_finite()));
+        // The window ending at point 4 holds the four changes up to it
+        assert_eq!(rolling[4], calc_sharpe(&[3.0, -4.0, 5.0, -6.0], 252.0));
+        assert!(rolling[9] < 2.0);
+        assert!(rolling[14..].iter().all(|&v| v > 100.0));
+        assert!(calc_rolling_sharpe(&equity, 1, 252.0)
+            .iter()
+            .all(|v| v.is_nan()));
+    }
+
+    #[test]
+    fn sqn_matches_a_hand_calculation() {
+        // Mean 60; squared deviations sum to 67,000, so the sample variance is 16,750
+        let pnls = [100.0, -50.0, 200.0, -100.0, 150.0];
+        let expected = 5.0_f64.sqrt() * 60.0 / 16_750.0_f64.sqrt();
+        assert!((calc_sqn(&pnls) - expected).abs() < 1e-12);
+        assert!((expected - 1.0366).abs() < 1e-4);
+        let m = compute_metrics(&daily_trades(&pnls), &[], &[], &MetricsOptions::default());
+        assert_eq!(m.sqn, calc_sqn(&pnls));
+        assert_eq!(calc_sqn(&[100.0]), 0.0);
+        assert_eq!(calc_sqn(&[50.0, 50.0]), 0.0);
+    }
+
+    #[test]
+    fn var_and_cvar_of_normal_pnls_match_the_analytical_values() {
+        // PnLs drawn from N(0, 100^2) by Box-Muller
+        let mut rng = StdRng::seed_from_u64(42);
+        let pnls: Vec<f64> = (0..200_000)
+            .map(|_| {
+                let (u1, u2): (f64, f64) = (1.0 - rng.gen::<f64>(), rng.gen());
+                100.0 * (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos()
+            })
+            .collect();
+        // VaR = z * sigma; CVaR = pdf(z) / (1 - confidence) * sigma
+        for (confidence, var, cvar) in [(0.95, 164.485, 206.271), (0.99, 232.635, 266.521)] {
+            let (v, c) = calc_var_cvar(&pnls, confidence);
+            assert!((v / var - 1.0).abs() < 0.02, "VaR {confidence}: {v}");
+            assert!((c / cvar - 1.0).abs() < 0.02, "CVaR {confidence}: {c}");
+        }
+    }
+
+    #[test]
+    fn omega_of_a_known_distribution() {
+        let pnls = [100.0, -50.0, 200.0, -100.0];
+        // 300 of gains over 150 of losses
+        assert_eq!(calc_omega(&pnls, 0.0), 2.0);
+        // Above 50: 50 + 150 over 100 + 150
+        assert_eq!(calc_omega(&pnls, 50.0), 200.0 / 250.0);
+        assert_eq!(calc_omega(&[10.0, 20.0], 0.0), f64::INFINITY);
+        assert_eq!(calc_omega(&[0.0, 0.0], 0.0), 0.0);
+        let m = compute_metrics(&daily_trades(&pnls), &[], &[], &MetricsOptions::default());
+        assert_eq!(m.omega_ratio, 2.0);
+    }
+
+    #[test]
+    fn commission_is_charged_per_side_per_contract() {
+        let mut tracker = PositionTracker::new(2.5, 50.0);
+        tracker.quantity = 3.0;
+        // Three round trips, long, short and long again, each flat in between
+        for (i, &signal) in [1, 0, -1, 0, 1, 0].iter().enumerate() {
+            let ts = i as i64 * DAY;
+            tracker.process_signal(signal, 100.0, ts, &FillContext::default());
+        }
+        assert_eq!(tracker.trades.len(), 3);
+        let m = compute_metrics(
+            &tracker.trades,
+            &tracker.equity_curve,
+            &tracker.equity_timestamps,
+            &MetricsOptions::default(),
+        );
+        assert_eq!(m.total_commission_paid, 6.0 * 2.5 * 3.0);
+        // Every trade is flat on price, so commission is the whole loss
+        assert_eq!(m.total_pnl, -45.0);
+    }
+
+    #[test]
+    fn monthly_sharpe_and_sortino_use_each_months_trades() {
+        const JAN_1_2024: i64 = 19_723 * DAY;
+        const MAR_1_2024: i64 = JAN_1_2024 + 60 * DAY;
+        let trades = [
+            trade(JAN_1_2024 + 4 * DAY, 100.0, 0),
+            trade(JAN_1_2024 + 9 * DAY, -50.0, 0),
+            trade(JAN_1_2024 + 19 * DAY, 200.0, 0),
+            trade(JAN_1_2024 + 40 * DAY, 30.0, 0),
+            trade(MAR_1_2024 + 2 * DAY, 10.0, 0),
+            trade(MAR_1_2024 + 3 * DAY, 30.0, 0),
+        ];
+        let months = compute_monthly_stats(&trades, 252.0);
+        assert_eq!(
+            months.keys().copied().collect::<Vec<_>>(),
+            [(2024, 1), (2024, 2), (2024, 3)]
+        );
+        let annualize = 252f64.sqrt();
+
+        // January: mean 250/3, sample variance 47500/3, downside deviation sqrt(2500/3)
+        let jan = &months[&(2024, 1)];
+        assert_eq!((jan.num_trades, jan.total_pnl), (3, 250.0));
+        let sharpe = (250.0 / 3.0) / (47_500.0f64 / 3.0).sqrt() * annualize;
+        let sortino = (250.0 / 3.0) / (2_500.0f64 / 3.0).sqrt() * annualize;
+        assert!((jan.sharpe_ratio - sharpe).abs() < 1e-9);
+        assert!((jan.sortino_ratio - sortino).abs() < 1e-9);
+
+        // February has a single trade
+        let feb = &months[&(2024, 2)];
+        assert_eq!(feb.num_trades, 1);
+        assert!(feb.sharpe_ratio.is_nan() && feb.sortino_ratio.is_nan());
+
+        // March: mean 20, sample standard deviation sqrt(200), no losses
+        let mar = &months[&(2024, 3)];
+        assert!((mar.sharpe_ratio - 20.0 / 200f64.sqrt() * annualize).abs() < 1e-9);
+        assert_eq!(mar.sortino_ratio, f64::INFINITY);
+    }
 }
@@ -0,0 +1,89 @@
+//! Orders for the event-driven bar engine and how they fill against a bar.
+
+use crate::bar::Bar;
+use crate::position::Side;
+
+/// An order working for the next bar. `side` is Long to buy and Short to sell.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Order {
+    /// Fill at the bar's open
+    Market { side: Side, qty: u32 },
+    /// Fill at `price` or better once the bar trades through it
+    Limit { side: Side, qty: u32, price: f64 },
+    /// Fill once the bar trades through `price`, at the open if it gaps past it
+    Stop { side: Side, qty: u32, price: f64 },
+}
+
+impl Order {
+    /// Build an order from its kind ("market", "limit", "stop"), side ("buy"/"long" or
+    /// "sell"/"short"), quantity and, for limit and stop orders, price.
+    pub fn from_parts(
+        kind: &str,
+        side: &str,
+        qty: u32,
+        price: Option<f64>,
+    ) -> Result<Self, String> {
+        let side = match side {
+            "buy" | "long" => Side::Long,
+            "sell" | "short" => Side::Short,
+            _ => return Err(format!("Unknown order side: {side}")),
+        };
+        if qty == 0 {
+            return Err("Order quantity must be positive".into());
+        }
+        let price = |kind: &str| match price {
+            Some(p) if p.is_finite() && p > 0.0 => Ok(p),
+            Some(p) => Err(format!("{kind} order price must be positive, got {p}")),
+            None => Err(format!("{kind} order needs a price")),
+        };
+        match kind {
+            "market" => Ok(Order::Market { side, qty }),
+            "limit" => Ok(Order::Limit {
+                side,
+                qty,
+                price: price("Limit")?,
+            }),
+            "stop" => Ok(Order::Stop {
+                side,
+                qty,
+                price: price("Stop")?,
+            }),
+            _ => Err(format!("Unknown order type: {kind}")),
+        }
+    }
+
+    pub fn side(&self) -> Side {
+        match *self {
+            Order::Market { side, .. } | Order::Limit { side, .. } | Order::Stop { side, .. } => {
+                side
+            }
+        }
+    }
+
+    pub fn qty(&self) -> u32 {
+        match *self {
+            Order::Market { qty, .. } | Order::Limit { qty, .. } | Order::Stop { qty, .. } => qty,
+        }
+    }
+
+    /// Price the order fills at during `bar`, or None if it doesn't fill.
+    ///
+    /// Limits fill when the low (buy) or high (sell) reaches the limit price, at the open
+    /// if the bar opens through it. Stops trigger when the high (buy) or low (sell) reaches
+    /// the stop price and fill at the worse of the stop price and the open.
+    pub fn fill_price(&self, bar: &Bar) -> Option<f64> {
+        match *self {
+            Order::Market { .. } => Some(bar.open),
+            Order::Limit { side, price, .. } => match side {
+                Side::Long if bar.low <= price => Some(price.min(bar.open)),
+                Side::Short if bar.high >= price => Some(price.max(bar.open)),
+                _ => None,
+            },
+            Order::Stop { side, price, .. } => match side {
+                Side::Long if bar.high >= price => Some(price.max(bar.open)),
+                Side::Short if bar.low <= price => Some(price.min(bar.open)),
+                _ => None,
+            },
+        }
+    }
+}
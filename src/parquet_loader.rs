@@ -0,0 +1,286 @@
+//! Tick data from Parquet files.
+//!
+//! Expected schema, one row per trade (extra columns are ignored; columns may be nullable
+//! but must hold no nulls):
+//!
+//! | Column | Type |
+//! |---|---|
+//! | `timestamp_us` | INT64, Unix microseconds |
+//! | `price`, `bid`, `ask` | FLOAT or DOUBLE (integers are accepted) |
+//! | `volume`, `bid_volume`, `ask_volume` | INT32 (signed or UINT_32) or INT64, 0 to 2^32 - 1 |
+
+use std::fs::File;
+use std::ops::Range;
+use std::path::Path;
+
+use parquet::basic::ConvertedType;
+use parquet::column::reader::{ColumnReader, ColumnReaderImpl};
+use parquet::data_type::DataType;
+use parquet::file::reader::{FileReader, RowGroupReader, SerializedFileReader};
+
+use crate::datasource::DataSource;
+use crate::scid::Tick;
+
+/// Ticks read from a Parquet file, in file order.
+pub struct ParquetLoader {
+    pub ticks: Vec<Tick>,
+}
+
+/// A column's values, widened.
+enum Column {
+    Int(Vec<i64>),
+    Float(Vec<f64>),
+}
+
+impl ParquetLoader {
+    /// Read every row group of the file at `path` (see the module docs for the schema).
+    /// Parquet rows carry no trade count, so each tick counts as one trade.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| format!("open {}: {e}", path.display()))?;
+        let reader = SerializedFileReader::new(file).map_err(|e| format!("Parquet: {e}"))?;
+        let schema = reader.metadata().file_metadata().schema_descr_ptr();
+        let column = |name: &str| {
+            schema
+                .columns()
+                .iter()
+                .position(|c| c.path().parts() == [name])
+                .ok_or_else(|| format!("Parquet file has no column named {name:?}"))
+        };
+        let names = [
+            "timestamp_us",
+            "price",
+            "bid",
+            "ask",
+            "volume",
+            "bid_volume",
+            "ask_volume",
+        ];
+        let indices = names
+            .map(column)
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let num_rows = reader.metadata().file_metadata().num_rows().max(0) as usize;
+        let mut ticks = Vec::with_capacity(num_rows);
+        for group in 0..reader.num_row_groups() {
+            let row_group = reader
+                .get_row_group(group)
+                .map_err(|e| format!("Parquet row group {group}: {e}"))?;
+            let rows = row_group.metadata().num_rows().max(0) as usize;
+            let read = |i: usize| read_column(row_group.as_ref(), indices[i], names[i], rows);
+            let Column::Int(timestamp) = read(0)? else {
+                return Err("Parquet column \"timestamp_us\" must be an integer".into());
+            };
+            let (price, bid, ask) = (floats(read(1)?), floats(read(2)?), floats(read(3)?));
+            let volume = volumes(read(4)?, names[4])?;
+            let bid_volume = volumes(read(5)?, names[5])?;
+            let ask_volume = volumes(read(6)?, names[6])?;
+            for i in 0..rows {
+                ticks.push(Tick {
+                    timestamp_us: timestamp[i],
+                    open: 0.0,
+                    price: price[i],
+                    bid: bid[i],
+                    ask: ask[i],
+                    volume: volume[i],
+                    bid_volume: bid_volume[i],
+                    ask_volume: ask_volume[i],
+                    num_trades: 1,
+                });
+            }
+        }
+        Ok(ParquetLoader { ticks })
+    }
+}
+
+impl DataSource for ParquetLoader {
+    fn num_ticks(&self) -> usize {
+        self.ticks.len()
+    }
+
+    #[inline]
+    fn tick(&self, index: usize) -> Tick {
+        self.ticks[index]
+    }
+
+    fn iter_range(&self, start: usize, end: usize) -> Box<dyn Iterator<Item = Tick> + '_> {
+        self.ticks.iter_range(start, end)
+    }
+
+    fn skip_stats_in(&self, range: Range<usize>) -> crate::scid::SkipStats {
+        self.ticks.skip_stats_in(range)
+    }
+}
+
+/// Read all `rows` values of column `index` of a row group, failing on nulls.
+fn read_column(
+    row_group: &dyn RowGroupReader,
+    index: usize,
+    name: &str,
+    rows: usize,
+) -> Result<Column, String> {
+    let descr = row_group.metadata().column(index).column_descr_ptr();
+    let nullable = descr.max_def_level() > 0;
+    let reader = row_group
+        .get_column_reader(index)
+        .map_err(|e| format!("Parquet column {name:?}: {e}"))?;
+    let column = match reader {
+        ColumnReader::Int32ColumnReader(r) => {
+            let unsigned = descr.converted_type() == ConvertedType::UINT_32;
+            let values = read_values(r, rows, nullable, name)?;
+            Column::Int(
+                values
+                    .into_iter()
+                    .map(|v| if unsigned { v as u32 as i64 } else { v as i64 })
+                    .collect(),
+            )
+        }
+        ColumnReader::Int64ColumnReader(r) => Column::Int(read_values(r, rows, nullable, name)?),
+        ColumnReader::FloatColumnReader(r) => Column::Float(
+            read_values(r, rows, nullable, name)?
+                .into_iter()
+                .map(f64::from)
+                .collect(),
+        ),
+        ColumnReader::DoubleColumnReader(r) => Column::Float(read_values(r, rows, nullable, name)?),
+        _ => return Err(format!("Parquet column {name:?} is not numeric")),
+    };
+    Ok(column)
+}
+
+fn read_values<T: DataType>(
+    mut reader: ColumnReaderImpl<T>,
+    rows: usize,
+    nullable: bool,
+    name: &str,
+) -> Result<Vec<T::T>, String> {
+    let mut values = Vec::with_capacity(rows);
+    let mut levels = Vec::new();
+    let mut read = 0;
+    while read < rows {
+        let (records, _, _) = reader
+            .read_records(
+                rows - read,
+                nullable.then_some(&mut levels),
+                None,
+                &mut values,
+            )
+            .map_err(|e| format!("Parquet column {name:?}: {e}"))?;
+        if records == 0 {
+            break;
+        }
+        read += records;
+    }
+    if read != rows || values.len() != rows {
+        return Err(format!(
+            "Parquet column {name:?} has null or missing values"
+        ));
+    }
+    Ok(values)
+}
+
+fn floats(column: Column) -> Vec<f64> {
+    match column {
+        Column::Float(values) => values,
+        Column::Int(values) => values.into_iter().map(|v| v as f64).collect(),
+    }
+}
+
+fn volumes(column: Column, name: &str) -> Result<Vec<u32>, String> {
+    let Column::Int(values) = column else {
+        return Err(format!("Parquet column {name:?} must be an integer"));
+    };
+    values
+        .into_iter()
+        .map(|v| {
+            u32::try_from(v).map_err(|_| format!("Parquet column {name:?}: {v} is out of range"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use parquet::basic::Compression;
+    use parquet::data_type::{FloatType, Int32Type, Int64Type};
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::{SerializedFileWriter, SerializedRowGroupWriter};
+    use parquet::schema::parser::parse_message_type;
+
+    use super::*;
+
+    const SCHEMA: &str = "
+        message ticks {
+            required int64 timestamp_us;
+            required float price;
+            required float bid;
+            required float ask;
+            required int32 volume (UINT_32);
+            required int32 bid_volume (UINT_32);
+            required int32 ask_volume (UINT_32);
+        }
+    ";
+
+    fn write_column<T: DataType>(group: &mut SerializedRowGroupWriter<'_, File>, values: &[T::T]) {
+        let mut column = group.next_column().unwrap().unwrap();
+        column.typed::<T>().write_batch(values, None, None).unwrap();
+        column.close().unwrap();
+    }
+
+    #[test]
+    fn reads_back_the_ticks_written() {
+        let ticks: Vec<Tick> = (0..5)
+            .map(|i| Tick {
+                timestamp_us: 1_704_153_600_000_000 + i * 250_000,
+                open: 0.0,
+                price: 4800.25 + i as f64 * 0.25,
+                bid: 4800.0 + i as f64 * 0.25,
+                ask: 4800.25 + i as f64 * 0.25,
+                // Past i32::MAX on the last row, which only UINT_32 can hold
+                volume: if i == 4 { u32::MAX } else { i as u32 + 1 },
+                bid_volume: i as u32,
+                ask_volume: 1,
+                num_trades: 1,
+            })
+            .collect();
+
+        let path = std::env::temp_dir().join(format!("ticks_{}.parquet", std::process::id()));
+        let props = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let mut writer = SerializedFileWriter::new(
+            File::create(&path).unwrap(),
+            Arc::new(parse_message_type(SCHEMA).unwrap()),
+            Arc::new(props),
+        )
+        .unwrap();
+        // Two row groups, so the reader has to stitch them together
+        for chunk in ticks.chunks(3) {
+            let mut group = writer.next_row_group().unwrap();
+            let ints = |field: fn(&Tick) -> u32| -> Vec<i32> {
+                chunk.iter().map(|t| field(t) as i32).collect()
+            };
+            let floats = |field: fn(&Tick) -> f64| -> Vec<f32> {
+                chunk.iter().map(|t| field(t) as f32).collect()
+            };
+            let timestamps: Vec<i64> = chunk.iter().map(|t| t.timestamp_us).collect();
+            write_column::<Int64Type>(&mut group, &timestamps);
+            write_column::<FloatType>(&mut group, &floats(|t| t.price));
+            write_column::<FloatType>(&mut group, &floats(|t| t.bid));
+            write_column::<FloatType>(&mut group, &floats(|t| t.ask));
+            write_column::<Int32Type>(&mut group, &ints(|t| t.volume));
+            write_column::<Int32Type>(&mut group, &ints(|t| t.bid_volume));
+            write_column::<Int32Type>(&mut group, &ints(|t| t.ask_volume));
+            group.close().unwrap();
+        }
+        writer.close().unwrap();
+
+        let loaded = ParquetLoader::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let debug =
+            |ticks: &[Tick]| -> Vec<String> { ticks.iter().map(|t| format!("{t:?}")).collect() };
+        assert_eq!(debug(&loaded.ticks), debug(&ticks));
+    }
+}
@@ -1,103 +1,1303 @@
-#[derive(Clone, Copy, Debug, PartialEq)]
+use serde::Serialize;
+
+use crate::session::SessionClock;
+
+/// Signal meaning "no opinion": keep the current position (or flatness) unchanged.
+/// Float signal arrays express it as NaN.
+pub const HOLD: i32 = i32::MIN;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Side {
     Flat,
     Long,
     Short,
 }
 
-#[derive(Clone, Debug)]
+/// Why a trade was closed.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExitReason {
+    /// The strategy signal changed
+    Signal,
+    /// A profit target rung of the target ladder was reached
+    Target,
+    /// The position was open longer than the maximum holding time
+    MaxHoldTime,
+    /// The end-of-session flat time was reached
+    EndOfSession,
+    /// Price moved against the position by the stop-loss distance
+    StopLoss,
+    /// The data ran out with the position still open
+    EndOfData,
+}
+
+impl ExitReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExitReason::Signal => "signal",
+            ExitReason::Target => "target",
+            ExitReason::MaxHoldTime => "max_hold_time",
+            ExitReason::EndOfSession => "end_of_session",
+            ExitReason::StopLoss => "stop_loss",
+            ExitReason::EndOfData => "end_of_data",
+        }
+    }
+}
+
+impl std::str::FromStr for ExitReason {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "signal" => Ok(ExitReason::Signal),
+            "target" => Ok(ExitReason::Target),
+            "max_hold_time" => Ok(ExitReason::MaxHoldTime),
+            "end_of_session" => Ok(ExitReason::EndOfSession),
+            "stop_loss" => Ok(ExitReason::StopLoss),
+            "end_of_data" => Ok(ExitReason::EndOfData),
+            other => Err(format!("Unknown exit reason: {other}")),
+        }
+    }
+}
+
+/// How repeated entry signals build up a position.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum PyramidMode {
+    /// Each add is its own lot, closed oldest first as its own trade
+    #[default]
+    Lots,
+    /// Adds merge into one lot at the quantity-weighted average entry price, closed as a
+    /// single trade on the whole size
+    Average,
+}
+
+impl std::str::FromStr for PyramidMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "lots" => Ok(PyramidMode::Lots),
+            "average" => Ok(PyramidMode::Average),
+            _ => Err(format!("Unknown pyramid mode: {s}")),
+        }
+    }
+}
+
+/// Which equity points the tracker keeps on its equity curve. Drawdown is tracked over
+/// every point regardless (see `PositionTracker::max_drawdown`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EquitySampling {
+    /// Every point: one per processed signal or mark (every tick in tick mode)
+    #[default]
+    Every,
+    /// The first point and those after a trade closes
+    OnTrade,
+    /// The last point in each window of this many microseconds of data time
+    Interval(i64),
+}
+
+impl std::str::FromStr for EquitySampling {
+    type Err = String;
+
+    /// "every_tick", "on_trade" or "interval:<seconds>".
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "every_tick" => Ok(EquitySampling::Every),
+            "on_trade" => Ok(EquitySampling::OnTrade),
+            _ => {
+                let secs = s
+                    .strip_prefix("interval:")
+                    .and_then(|secs| secs.parse::<f64>().ok())
+                    .ok_or_else(|| format!("Unknown equity sampling: {s}"))?;
+                let us = (secs * 1_000_000.0).round();
+                if !(us >= 1.0 && us < i64::MAX as f64) {
+                    return Err(format!("Equity sampling interval must be positive: {s}"));
+                }
+                Ok(EquitySampling::Interval(us as i64))
+            }
+        }
+    }
+}
+
+/// Market conditions at a fill: the volume, high and low of the bar the signal is
+/// processed on. In tick mode they come from the tick, with its ask and bid as high and low.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FillContext {
+    pub volume: f64,
+    pub high: f64,
+    pub low: f64,
+}
+
+/// How far fills land from the signal price, always against the trader.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SlippageModel {
+    /// Constant number of ticks
+    Fixed(f64),
+    /// `base` ticks at `ref_volume`, scaling inversely with the bar's volume
+    VolumeScaled { base: f64, ref_volume: f64 },
+    /// Fraction of the bar's high-low range
+    RangeFraction(f64),
+}
+
+impl Default for SlippageModel {
+    fn default() -> Self {
+        SlippageModel::Fixed(0.0)
+    }
+}
+
+impl SlippageModel {
+    /// Build a model from its name ("fixed", "volume_scaled", "range_fraction") and
+    /// parameters.
+    pub fn from_parts(name: &str, params: &[f64]) -> Result<Self, String> {
+        if params.iter().any(|&p| !p.is_finite() || p < 0.0) {
+            return Err("Slippage parameters must be non-negative".into());
+        }
+        match (name, params) {
+            ("fixed", &[ticks]) => Ok(SlippageModel::Fixed(ticks)),
+            ("volume_scaled", &[base, ref_volume]) => {
+                Ok(SlippageModel::VolumeScaled { base, ref_volume })
+            }
+            ("range_fraction", &[fraction]) => Ok(SlippageModel::RangeFraction(fraction)),
+            _ => Err(format!(
+                "Unknown slippage model {name:?} with {} parameter(s). Use (\"fixed\", ticks), \
+                 (\"volume_scaled\", base, ref_volume) or (\"range_fraction\", fraction)",
+                params.len()
+            )),
+        }
+    }
+
+    /// Slippage in price units for a fill under `ctx`.
+    pub fn slippage(&self, ctx: &FillContext, tick_size: f64) -> f64 {
+        match *self {
+            SlippageModel::Fixed(ticks) => ticks * tick_size,
+            SlippageModel::VolumeScaled { base, ref_volume } => {
+                base * ref_volume / ctx.volume.max(1.0) * tick_size
+            }
+            SlippageModel::RangeFraction(fraction) => fraction * (ctx.high - ctx.low).max(0.0),
+        }
+    }
+}
+
+/// One entry into the open position. Pyramiding opens a lot per repeated entry signal;
+/// exits consume lots oldest first, recording a trade per lot.
+#[derive(Clone, Copy, Debug)]
+pub struct Lot {
+    pub qty: f64,
+    /// Fill price after slippage
+    pub entry_price: f64,
+    pub entry_time_us: i64,
+    /// Tag of the signal that opened the lot
+    pub tag: i32,
+    /// `bar_index` when the lot was opened
+    pub entry_bar: usize,
+    /// Account equity (initial capital plus realized PnL) when the lot was opened
+    pub equity_at_entry: f64,
+    /// Realized PnL below its running peak when the lot was opened
+    pub drawdown_at_entry: f64,
+}
+
+#[derive(Clone, Debug, Serialize)]
 pub struct Trade {
     pub entry_time_us: i64,
     pub exit_time_us: i64,
     pub side: Side,
     pub entry_price: f64,
     pub exit_price: f64,
+    /// Contracts closed by this trade
+    pub quantity: f64,
     pub pnl: f64,
+    /// Price move in the trade's favour, in ticks (before costs)
+    pub pnl_ticks: f64,
+    /// Price move in the trade's favour as a percentage of the entry price
+    pub return_pct: f64,
+    /// `pnl` as a percentage of `equity_at_entry`
+    pub capital_return_pct: f64,
+    /// Overnight holding cost included in `pnl`
+    pub holding_cost: f64,
+    /// Daily funding on the position's notional value, included in `pnl`
+    pub funding: f64,
+    /// Commission for both sides of the closed contracts, included in `pnl`
+    pub commission: f64,
+    pub exit_reason: ExitReason,
+    /// Closed only part of the position, leaving the rest open
+    pub partial: bool,
+    /// Dollars at risk at entry (stop distance x point value x quantity), if a stop loss or
+    /// bracket was set; a bracket's distance is from the lot's entry to its stop price
+    pub initial_risk: Option<f64>,
+    /// PnL in units of initial risk, if a stop loss or bracket was set
+    pub r_multiple: Option<f64>,
+    /// Strategy tag of the signal that opened the trade
+    pub tag: i32,
+    /// Bars between the entry and exit bars (0 in the tick engine)
+    pub bars_held: usize,
+    /// Account equity (initial capital plus realized PnL) when the position was opened
+    pub equity_at_entry: f64,
+    /// Realized PnL below its running peak when the position was opened (>= 0)
+    pub drawdown_at_entry: f64,
+}
+
+/// `pnl` as a percentage of the account equity it was risked from (0 without equity).
+pub fn capital_return_pct(pnl: f64, equity_at_entry: f64) -> f64 {
+    if equity_at_entry > 0.0 {
+        pnl / equity_at_entry * 100.0
+    } else {
+        0.0
+    }
+}
+
+/// One-cancels-other exit levels attached to a position by `enter_with_bracket`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bracket {
+    pub stop_price: f64,
+    pub target_price: f64,
 }
 
 #[derive(Clone, Debug)]
 pub struct PositionTracker {
     pub side: Side,
+    /// Quantity-weighted average entry price over the open lots
     pub entry_price: f64,
+    /// Entry time of the oldest open lot
     pub entry_time_us: i64,
-    pub commission: f64,
+    /// Open lots, oldest first
+    pub lots: Vec<Lot>,
+    /// Lots the position may hold; repeated entry signals add a lot until the cap
+    pub max_positions: usize,
+    /// Whether adds are kept as separate lots or averaged into one
+    pub pyramid_mode: PyramidMode,
+    /// Tag accompanying the next processed signal, stamped on any position it opens
+    pub signal_tag: i32,
+    /// Commission in dollars per contract per side (charged twice per round turn)
+    pub commission_per_side: f64,
     pub point_value: f64,
+    /// Contracts opened on each entry
+    pub quantity: f64,
+    /// Contracts still open in the current position
+    pub open_quantity: f64,
+    pub slippage_model: SlippageModel,
+    /// Minimum price increment, the unit of tick-based slippage
+    pub tick_size: f64,
+    /// Slippage applied to fills on the signal being processed
+    pub current_slippage: f64,
+    /// Profit targets as (points from entry, fraction of `ladder_quantity`), sorted by
+    /// points
+    pub target_ladder: Vec<(f64, f64)>,
+    /// Number of target rungs already filled for the current position
+    pub targets_hit: usize,
+    /// Contracts open when the current position's first target rung filled
+    pub ladder_quantity: f64,
+    /// Stop-loss distance in points from the entry price
+    pub stop_loss_points: Option<f64>,
+    /// Stop and target of the open position, if it was entered with a bracket
+    pub bracket: Option<Bracket>,
+    /// Force-close positions held longer than this many seconds
+    pub max_holding_secs: Option<i64>,
+    /// Ignore entries for this many seconds after a stop-loss exit
+    pub cooldown_secs: Option<i64>,
+    /// Start the cooldown after every exit, not just stop-loss exits
+    pub cooldown_after_any_exit: bool,
+    /// Timestamp of the last exit that started a cooldown
+    pub last_exit_time_us: Option<i64>,
+    /// Local time of day (seconds after midnight) at which open positions are closed
+    pub flat_time_secs: Option<i64>,
+    /// Wall clock used for `flat_time_secs`
+    pub session_clock: SessionClock,
+    /// Flat time of the local day the current position was opened on
+    pub flat_deadline_us: i64,
+    /// Carrying cost in dollars per contract for each day boundary a position is held over
+    pub daily_holding_cost: f64,
+    /// UTC hour at which the holding-cost day rolls over
+    pub rollover_hour: u32,
+    /// Holding cost accrued on the open position, not yet attributed to a trade
+    pub accrued_holding_cost: f64,
+    /// Fraction of the open position's notional value (entry price x quantity x point
+    /// value) charged for each day boundary it is held over
+    pub daily_funding_rate: f64,
+    /// Funding accrued on the open position, not yet attributed to a trade
+    pub accrued_funding: f64,
+    /// Funding charged over the whole run
+    pub total_funding_paid: f64,
+    /// Timestamp of the last signal processed while in a position
+    pub last_mark_us: i64,
+    /// Side the engine last force-exited; signals for it are ignored until the signal changes
+    pub blocked_side: Side,
+    /// Index of the bar being processed, set by the bar engines
+    pub bar_index: usize,
     pub trades: Vec<Trade>,
+    /// Mark-to-market account equity (initial capital plus realized PnL plus the open
+    /// position at the fill price) after each processed signal or mark
     pub equity_curve: Vec<f64>,
+    /// Timestamp (Unix microseconds) of each equity curve point
+    pub equity_timestamps: Vec<i64>,
+    /// Which equity points are kept on `equity_curve`
+    pub equity_sampling: EquitySampling,
+    /// Trades closed when the last equity point was kept
+    pub sampled_trades: usize,
+    /// Highest equity so far over every point, kept or not
+    pub equity_peak: Option<f64>,
+    /// Largest fall from `equity_peak`, in dollars and as a percentage of the peak, over
+    /// every equity point; exact even when sampling thins the curve
+    pub max_drawdown: f64,
+    pub max_drawdown_pct: f64,
     pub running_pnl: f64,
+    /// Account size the equity curve starts from
+    pub initial_capital: f64,
+    /// Highest `running_pnl` so far
+    pub peak_pnl: f64,
 }
 
 impl PositionTracker {
-    pub fn new(commission: f64, point_value: f64) -> Self {
+    pub fn new(commission_per_side: f64, point_value: f64) -> Self {
+        Self::new_with_config(commission_per_side, point_value, 1)
+    }
+
+    /// Tracker that pyramids up to `max_positions` lots per position (1 = one lot).
+    pub fn new_with_config(
+        commission_per_side: f64,
+        point_value: f64,
+        max_positions: usize,
+    ) -> Self {
         PositionTracker {
             side: Side::Flat,
             entry_price: 0.0,
             entry_time_us: 0,
-            commission,
+            lots: Vec::new(),
+            max_positions,
+            pyramid_mode: PyramidMode::default(),
+            signal_tag: 0,
+            commission_per_side,
             point_value,
+            quantity: 1.0,
+            open_quantity: 0.0,
+            slippage_model: SlippageModel::default(),
+            tick_size: 0.25,
+            current_slippage: 0.0,
+            target_ladder: Vec::new(),
+            targets_hit: 0,
+            ladder_quantity: 0.0,
+            stop_loss_points: None,
+            bracket: None,
+            max_holding_secs: None,
+            cooldown_secs: None,
+            cooldown_after_any_exit: false,
+            last_exit_time_us: None,
+            flat_time_secs: None,
+            session_clock: SessionClock::default(),
+            flat_deadline_us: i64::MAX,
+            daily_holding_cost: 0.0,
+            rollover_hour: 0,
+            accrued_holding_cost: 0.0,
+            daily_funding_rate: 0.0,
+            accrued_funding: 0.0,
+            total_funding_paid: 0.0,
+            last_mark_us: 0,
+            blocked_side: Side::Flat,
+            bar_index: 0,
             trades: Vec::new(),
             equity_curve: Vec::new(),
+            equity_timestamps: Vec::new(),
+            equity_sampling: EquitySampling::default(),
+            sampled_trades: 0,
+            equity_peak: None,
+            max_drawdown: 0.0,
+            max_drawdown_pct: 0.0,
             running_pnl: 0.0,
+            initial_capital: 0.0,
+            peak_pnl: 0.0,
         }
     }
 
-    /// Process a signal at the given price and time.
-    /// signal: 1 = long, -1 = short, 0 = flat
-    pub fn process_signal(&mut self, signal: i32, price: f64, timestamp_us: i64) {
-        let desired = match signal {
+    /// Set the scale-out ladder. Fractions are of the quantity open when the first rung
+    /// fills, including any pyramided lots, and must sum to <= 1; whatever is left after
+    /// the last rung runs until the signal changes.
+    pub fn set_target_ladder(&mut self, mut ladder: Vec<(f64, f64)>) -> Result<(), String> {
+        if ladder.iter().any(|&(pts, frac)| pts <= 0.0 || frac <= 0.0) {
+            return Err("Target ladder points and fractions must be positive".into());
+        }
+        let total: f64 = ladder.iter().map(|&(_, frac)| frac).sum();
+        if total > 1.0 + 1e-9 {
+            return Err(format!(
+                "Target ladder fractions sum to {total}, must be <= 1"
+            ));
+        }
+        ladder.sort_by(|a, b| a.0.total_cmp(&b.0));
+        self.target_ladder = ladder;
+        Ok(())
+    }
+
+    /// Process a signal at the given price and time. `ctx` describes the bar or tick
+    /// for the slippage model.
+    /// signal: 1 = long, -1 = short, 0 = flat, `HOLD` = keep the current position
+    pub fn process_signal(
+        &mut self,
+        signal: i32,
+        price: f64,
+        timestamp_us: i64,
+        ctx: &FillContext,
+    ) {
+        self.apply_signal(signal, price, timestamp_us, ctx);
+        // Exactly one equity point per signal, after any transition
+        self.push_equity(price, timestamp_us);
+    }
+
+    /// `process_signal` without adding an equity point, for engines that mark equity on
+    /// their own schedule (see `mark`).
+    pub fn apply_signal(&mut self, signal: i32, price: f64, timestamp_us: i64, ctx: &FillContext) {
+        self.check_exits(price, timestamp_us, ctx);
+        // No opinion: stops, targets and time exits still apply, but nothing else changes
+        if signal == HOLD {
+            return;
+        }
+        let mut desired = match signal {
             1 => Side::Long,
             -1 => Side::Short,
             _ => Side::Flat,
         };
 
+        // Don't re-enter a side the engine just exited until the strategy lets go of it
+        if desired == self.blocked_side {
+            desired = Side::Flat;
+        } else {
+            self.blocked_side = Side::Flat;
+        }
+        // No new entries between the flat time and the next local day
+        if self.after_flat_time(timestamp_us) {
+            desired = Side::Flat;
+        }
+
         if desired == self.side {
-            // No change
-            self.equity_curve.push(self.running_pnl + self.unrealized_pnl(price));
+            // Pyramid: a repeated entry signal adds to the position, up to the cap
+            if desired != Side::Flat && self.can_pyramid() && !self.in_cooldown(timestamp_us) {
+                match self.pyramid_mode {
+                    PyramidMode::Lots => self.add_lot(self.quantity, price, timestamp_us),
+                    PyramidMode::Average => self.average_in(self.quantity, price),
+                }
+            }
+        } else {
+            // Close current position if not flat
+            if self.side != Side::Flat {
+                self.exit(self.open_quantity, price, timestamp_us, ExitReason::Signal);
+            }
+            // Open new position if not flat and not cooling down after an exit
+            if desired != Side::Flat && !self.in_cooldown(timestamp_us) {
+                self.enter(desired, self.quantity, price, timestamp_us);
+            }
+        }
+    }
+
+    /// Whether a repeated entry signal may add to the open position: fewer than
+    /// `max_positions` lots, or under `PyramidMode::Average`, fewer than `max_positions`
+    /// entries' worth of contracts.
+    pub fn can_pyramid(&self) -> bool {
+        match self.pyramid_mode {
+            PyramidMode::Lots => self.lots.len() < self.max_positions,
+            PyramidMode::Average => {
+                self.open_quantity + self.quantity
+                    <= self.quantity * self.max_positions as f64 + 1e-9
+            }
+        }
+    }
+
+    /// Run the engine-managed exits (stop loss, maximum holding time, session end and
+    /// targets) at `price`, without acting on any signal or adding an equity point.
+    pub fn check_exits(&mut self, price: f64, timestamp_us: i64, ctx: &FillContext) {
+        self.current_slippage = self.slippage_model.slippage(ctx, self.tick_size);
+        self.accrue_holding_cost(timestamp_us);
+        self.check_stop_loss(price, timestamp_us);
+        self.check_bracket(price, timestamp_us);
+        self.check_max_holding(price, timestamp_us);
+        self.check_session_end(price, timestamp_us);
+        self.check_targets(price, timestamp_us);
+    }
+
+    /// Whether an engine-managed exit could trigger for prices within `low..=high` up to
+    /// `end_us`. False guarantees `check_exits` is a no-op over that span.
+    pub fn may_exit_within(&self, high: f64, low: f64, end_us: i64) -> bool {
+        let (reach_adverse, reach_favorable) = match self.side {
+            Side::Flat => return false,
+            Side::Long => (self.entry_price - low, high - self.entry_price),
+            Side::Short => (high - self.entry_price, self.entry_price - low),
+        };
+        let stop = self
+            .stop_loss_points
+            .is_some_and(|stop| reach_adverse >= stop);
+        let target = self
+            .target_ladder
+            .get(self.targets_hit)
+            .is_some_and(|&(pts, _)| reach_favorable >= pts);
+        let bracket = self.bracket.is_some_and(|b| match self.side {
+            Side::Long => low <= b.stop_price || high >= b.target_price,
+            _ => high >= b.stop_price || low <= b.target_price,
+        });
+        let max_hold = self
+            .max_holding_secs
+            .is_some_and(|secs| end_us - self.entry_time_us > secs * 1_000_000);
+        stop || target || bracket || max_hold || end_us >= self.flat_deadline_us
+    }
+
+    /// Fill an order for `qty` contracts on `side` (Long = buy, Short = sell) at `price`.
+    /// An opposing fill closes the open position first and any remainder opens the other
+    /// side; a fill on the open side adds to it at the averaged entry price. Fills don't
+    /// add equity points; call `mark` once the bar is done.
+    pub fn fill_order(
+        &mut self,
+        side: Side,
+        qty: f64,
+        price: f64,
+        timestamp_us: i64,
+        ctx: &FillContext,
+    ) {
+        if side == Side::Flat || qty <= 0.0 {
             return;
         }
+        self.current_slippage = self.slippage_model.slippage(ctx, self.tick_size);
+        self.accrue_holding_cost(timestamp_us);
 
-        // Close current position if not flat
+        if self.side == side {
+            self.average_in(qty, price);
+            return;
+        }
+
+        let mut remaining = qty;
         if self.side != Side::Flat {
-            let pnl = self.calc_pnl(price) - self.commission;
-            self.running_pnl += pnl;
-            self.trades.push(Trade {
-                entry_time_us: self.entry_time_us,
-                exit_time_us: timestamp_us,
-                side: self.side,
-                entry_price: self.entry_price,
-                exit_price: price,
-                pnl,
-            });
-            self.side = Side::Flat;
+            let closed = remaining.min(self.open_quantity);
+            self.exit(closed, price, timestamp_us, ExitReason::Signal);
+            remaining -= closed;
+        }
+        if remaining > 1e-9 {
+            self.enter(side, remaining, price, timestamp_us);
+        }
+    }
+
+    /// Scale out of `fraction` (0 < fraction <= 1) of the open quantity at `price`, oldest
+    /// lots first. The rest stays open at its original entry prices. A no-op when flat;
+    /// like `fill_order`, it adds no equity point.
+    pub fn process_partial_exit(
+        &mut self,
+        fraction: f64,
+        price: f64,
+        timestamp_us: i64,
+        ctx: &FillContext,
+    ) -> Result<(), String> {
+        if !(fraction > 0.0 && fraction <= 1.0) {
+            return Err(format!("fraction must be in (0, 1], got {fraction}"));
+        }
+        if self.side == Side::Flat {
+            return Ok(());
+        }
+        self.current_slippage = self.slippage_model.slippage(ctx, self.tick_size);
+        self.accrue_holding_cost(timestamp_us);
+        self.exit(
+            self.open_quantity * fraction,
+            price,
+            timestamp_us,
+            ExitReason::Signal,
+        );
+        Ok(())
+    }
+
+    /// Open a `quantity` contract position on `side` at `price` with a stop `stop_points`
+    /// against and a target `target_points` in favour of the (slipped) entry. Whichever is
+    /// reached first closes the whole position; the stop is checked first. Any open
+    /// position is closed first. Adds no equity point.
+    pub fn enter_with_bracket(
+        &mut self,
+        side: Side,
+        stop_points: f64,
+        target_points: f64,
+        price: f64,
+        timestamp_us: i64,
+        ctx: &FillContext,
+    ) -> Result<(), String> {
+        if side == Side::Flat {
+            return Err("A bracket entry needs a long or short side".into());
+        }
+        if !(stop_points > 0.0 && target_points > 0.0) {
+            return Err(format!(
+                "Bracket stop and target must be positive, got {stop_points} and {target_points}"
+            ));
+        }
+        self.current_slippage = self.slippage_model.slippage(ctx, self.tick_size);
+        self.accrue_holding_cost(timestamp_us);
+        if self.side != Side::Flat {
+            self.exit(self.open_quantity, price, timestamp_us, ExitReason::Signal);
         }
+        self.enter(side, self.quantity, price, timestamp_us);
+        let dir = if side == Side::Long { 1.0 } else { -1.0 };
+        self.bracket = Some(Bracket {
+            stop_price: self.entry_price - dir * stop_points,
+            target_price: self.entry_price + dir * target_points,
+        });
+        Ok(())
+    }
+
+    /// Record an equity point marking the open position to `price`.
+    pub fn mark(&mut self, price: f64, timestamp_us: i64) {
+        self.accrue_holding_cost(timestamp_us);
+        self.push_equity(price, timestamp_us);
+    }
 
-        // Open new position if not flat
-        if desired != Side::Flat {
-            self.side = desired;
-            self.entry_price = price;
-            self.entry_time_us = timestamp_us;
+    /// Append account equity with the open position marked to `price` to the equity curve,
+    /// subject to `equity_sampling`, and update the drawdown.
+    fn push_equity(&mut self, price: f64, timestamp_us: i64) {
+        let equity = self.initial_capital + self.running_pnl + self.unrealized_pnl(price);
+        self.track_drawdown(equity);
+        match self.equity_sampling {
+            EquitySampling::Every => {}
+            EquitySampling::OnTrade => {
+                if !self.equity_curve.is_empty() && self.trades.len() == self.sampled_trades {
+                    return;
+                }
+                self.sampled_trades = self.trades.len();
+            }
+            EquitySampling::Interval(window_us) => {
+                let window = |ts: i64| ts.div_euclid(window_us);
+                if let Some(last_ts) = self.equity_timestamps.last_mut() {
+                    if window(*last_ts) == window(timestamp_us) {
+                        // Keep only the latest point of the window
+                        *last_ts = timestamp_us;
+                        *self.equity_curve.last_mut().unwrap() = equity;
+                        return;
+                    }
+                }
+            }
         }
+        self.equity_curve.push(equity);
+        self.equity_timestamps.push(timestamp_us);
+    }
+
+    /// Update the running equity peak and the largest drawdown from it, as
+    /// `metrics::calc_max_drawdown` measures them over a full curve.
+    fn track_drawdown(&mut self, equity: f64) {
+        let peak = self.equity_peak.map_or(equity, |peak| peak.max(equity));
+        self.equity_peak = Some(peak);
+        let drawdown = peak - equity;
+        self.max_drawdown = self.max_drawdown.max(drawdown);
+        if peak > 0.0 {
+            self.max_drawdown_pct = self.max_drawdown_pct.max(drawdown / peak * 100.0);
+        }
+    }
 
-        self.equity_curve.push(self.running_pnl);
+    /// Open a new `qty` contract position on `side` from flat, slipping the entry price
+    /// against the trader.
+    fn enter(&mut self, side: Side, qty: f64, price: f64, timestamp_us: i64) {
+        self.side = side;
+        self.entry_time_us = timestamp_us;
+        self.bracket = None;
+        self.lots.clear();
+        self.add_lot(qty, price, timestamp_us);
+        self.targets_hit = 0;
+        self.accrued_holding_cost = 0.0;
+        self.accrued_funding = 0.0;
+        self.last_mark_us = timestamp_us;
+        self.flat_deadline_us = match self.flat_time_secs {
+            Some(secs) => self.session_clock.day_start_us(timestamp_us) + secs * 1_000_000,
+            None => i64::MAX,
+        };
     }
 
+    /// Open another lot of `qty` contracts on the current side at `price` (slipped).
+    fn add_lot(&mut self, qty: f64, price: f64, timestamp_us: i64) {
+        self.lots.push(Lot {
+            qty,
+            entry_price: self.entry_fill(self.side, price),
+            entry_time_us: timestamp_us,
+            tag: self.signal_tag,
+            entry_bar: self.bar_index,
+            equity_at_entry: self.initial_capital + self.running_pnl,
+            drawdown_at_entry: self.peak_pnl - self.running_pnl,
+        });
+        self.update_entry_price();
+    }
+
+    /// Add `qty` contracts at `price` (slipped) to the newest lot, averaging its entry price.
+    fn average_in(&mut self, qty: f64, price: f64) {
+        let fill = self.entry_fill(self.side, price);
+        if let Some(lot) = self.lots.last_mut() {
+            lot.entry_price = (lot.entry_price * lot.qty + fill * qty) / (lot.qty + qty);
+            lot.qty += qty;
+        }
+        self.update_entry_price();
+    }
+
+    /// Entry fill for `side` at `price`, slipped against the trader.
+    fn entry_fill(&self, side: Side, price: f64) -> f64 {
+        match side {
+            Side::Short => price - self.current_slippage,
+            _ => price + self.current_slippage,
+        }
+    }
+
+    /// Recompute the open quantity and average entry price from the lots.
+    fn update_entry_price(&mut self) {
+        self.open_quantity = self.lots.iter().map(|lot| lot.qty).sum();
+        if self.open_quantity > 0.0 {
+            self.entry_price = self
+                .lots
+                .iter()
+                .map(|lot| lot.entry_price * lot.qty)
+                .sum::<f64>()
+                / self.open_quantity;
+        }
+    }
+
+    /// Deduct the daily holding cost and funding for every rollover crossed since the last mark. The
+    /// cost hits the running PnL (and so the equity curve) when it accrues.
+    fn accrue_holding_cost(&mut self, timestamp_us: i64) {
+        if self.side == Side::Flat {
+            return;
+        }
+        if self.daily_holding_cost != 0.0 || self.daily_funding_rate != 0.0 {
+            let offset_us = self.rollover_hour as i64 * 3_600_000_000;
+            let day = |ts: i64| (ts - offset_us).div_euclid(86_400_000_000);
+            let crossed = day(timestamp_us) - day(self.last_mark_us);
+            if crossed > 0 {
+                let cost = crossed as f64 * self.daily_holding_cost * self.open_quantity;
+                self.running_pnl -= cost;
+                self.accrued_holding_cost += cost;
+                let notional = self.entry_price * self.open_quantity * self.point_value;
+                let funding = crossed as f64 * notional * self.daily_funding_rate;
+                self.running_pnl -= funding;
+                self.accrued_funding += funding;
+                self.total_funding_paid += funding;
+            }
+        }
+        self.last_mark_us = timestamp_us;
+    }
+
+    /// Close the whole position if price has moved `stop_loss_points` against it.
+    fn check_stop_loss(&mut self, price: f64, timestamp_us: i64) {
+        if let Some(stop) = self.stop_loss_points {
+            let hit = match self.side {
+                Side::Long => price <= self.entry_price - stop,
+                Side::Short => price >= self.entry_price + stop,
+                Side::Flat => false,
+            };
+            if hit {
+                self.force_exit(price, timestamp_us, ExitReason::StopLoss);
+            }
+        }
+    }
+
+    /// Close the whole position at its bracket's stop or target, stop first.
+    fn check_bracket(&mut self, price: f64, timestamp_us: i64) {
+        let Some(b) = self.bracket else {
+            return;
+        };
+        let (stop_hit, target_hit) = match self.side {
+            Side::Long => (price <= b.stop_price, price >= b.target_price),
+            Side::Short => (price >= b.stop_price, price <= b.target_price),
+            Side::Flat => (false, false),
+        };
+        if stop_hit {
+            self.force_exit(price, timestamp_us, ExitReason::StopLoss);
+        } else if target_hit {
+            self.force_exit(price, timestamp_us, ExitReason::Target);
+        }
+    }
+
+    /// Close the whole position if it has been held past `max_holding_secs`.
+    fn check_max_holding(&mut self, price: f64, timestamp_us: i64) {
+        if let Some(max_secs) = self.max_holding_secs {
+            if self.side != Side::Flat && timestamp_us - self.entry_time_us > max_secs * 1_000_000 {
+                self.force_exit(price, timestamp_us, ExitReason::MaxHoldTime);
+            }
+        }
+    }
+
+    /// Close the position once the flat time of its entry day has passed. Unlike other
+    /// forced exits this doesn't block the side: the time-of-day gate keeps the position
+    /// flat until the next session.
+    fn check_session_end(&mut self, price: f64, timestamp_us: i64) {
+        if self.side != Side::Flat && timestamp_us >= self.flat_deadline_us {
+            self.exit(
+                self.open_quantity,
+                price,
+                timestamp_us,
+                ExitReason::EndOfSession,
+            );
+        }
+    }
+
+    fn in_cooldown(&self, timestamp_us: i64) -> bool {
+        match (self.cooldown_secs, self.last_exit_time_us) {
+            (Some(secs), Some(exit_us)) => timestamp_us - exit_us < secs * 1_000_000,
+            _ => false,
+        }
+    }
+
+    fn after_flat_time(&self, timestamp_us: i64) -> bool {
+        match self.flat_time_secs {
+            Some(secs) => self.session_clock.secs_of_day(timestamp_us) >= secs,
+            None => false,
+        }
+    }
+
+    /// Engine-initiated exit of the whole position. The exited side stays blocked until
+    /// the strategy's signal moves off it, so a persisting signal doesn't re-enter at once.
+    fn force_exit(&mut self, price: f64, timestamp_us: i64, reason: ExitReason) {
+        let side = self.side;
+        self.exit(self.open_quantity, price, timestamp_us, reason);
+        self.blocked_side = side;
+    }
+
+    /// Scale out at every target rung the price has reached.
+    fn check_targets(&mut self, price: f64, timestamp_us: i64) {
+        while self.side != Side::Flat && self.targets_hit < self.target_ladder.len() {
+            let (pts, frac) = self.target_ladder[self.targets_hit];
+            let reached = match self.side {
+                Side::Long => price >= self.entry_price + pts,
+                Side::Short => price <= self.entry_price - pts,
+                Side::Flat => false,
+            };
+            if !reached {
+                break;
+            }
+            if self.targets_hit == 0 {
+                self.ladder_quantity = self.open_quantity;
+            }
+            self.targets_hit += 1;
+            let qty = (self.ladder_quantity * frac).min(self.open_quantity);
+            let side = self.side;
+            self.exit(qty, price, timestamp_us, ExitReason::Target);
+            if self.side == Side::Flat {
+                self.blocked_side = side;
+            }
+        }
+    }
+
+    /// Realize PnL on `qty` contracts of the open position, oldest lots first with one
+    /// trade per lot, going flat once nothing is left.
+    fn exit(&mut self, qty: f64, price: f64, timestamp_us: i64, reason: ExitReason) {
+        let price = match self.side {
+            Side::Short => price + self.current_slippage,
+            _ => price - self.current_slippage,
+        };
+        let partial = qty < self.open_quantity - 1e-9;
+        let mut remaining = qty;
+        while remaining > 1e-9 && !self.lots.is_empty() {
+            let lot = self.lots[0];
+            let closed = remaining.min(lot.qty);
+            self.close_lot(&lot, closed, price, timestamp_us, reason, partial);
+            remaining -= closed;
+            if lot.qty - closed <= 1e-9 {
+                self.lots.remove(0);
+            } else {
+                self.lots[0].qty -= closed;
+            }
+        }
+        if reason == ExitReason::StopLoss || self.cooldown_after_any_exit {
+            self.last_exit_time_us = Some(timestamp_us);
+        }
+        self.update_entry_price();
+        if self.lots.is_empty() {
+            self.open_quantity = 0.0;
+            self.side = Side::Flat;
+            self.bracket = None;
+        } else {
+            self.entry_time_us = self.lots[0].entry_time_us;
+        }
+    }
+
+    /// Realize and record a trade for `qty` contracts of `lot` at the (slipped) `price`.
+    /// `partial` marks an exit that leaves part of the position open.
+    fn close_lot(
+        &mut self,
+        lot: &Lot,
+        qty: f64,
+        price: f64,
+        timestamp_us: i64,
+        reason: ExitReason,
+        partial: bool,
+    ) {
+        // Entry and exit side of the closed contracts
+        let commission = 2.0 * self.commission_per_side * qty;
+        let gross = self.points_pnl(lot.entry_price, price) * qty - commission;
+        // Holding cost and funding were already taken from running_pnl as they accrued
+        let holding_cost = self.accrued_holding_cost * qty / self.open_quantity;
+        self.accrued_holding_cost -= holding_cost;
+        let funding = self.accrued_funding * qty / self.open_quantity;
+        self.accrued_funding -= funding;
+        self.open_quantity -= qty;
+        self.running_pnl += gross;
+        self.peak_pnl = self.peak_pnl.max(self.running_pnl);
+        let pnl = gross - holding_cost - funding;
+        let stop_points = match self.bracket {
+            Some(b) => Some((lot.entry_price - b.stop_price).abs()),
+            None => self.stop_loss_points,
+        };
+        let initial_risk = stop_points.map(|stop| stop * self.point_value * qty);
+        let r_multiple = initial_risk
+            .filter(|&risk| risk > 0.0)
+            .map(|risk| pnl / risk);
+        let points = self.signed_move(lot.entry_price, price);
+        self.trades.push(Trade {
+            entry_time_us: lot.entry_time_us,
+            exit_time_us: timestamp_us,
+            side: self.side,
+            entry_price: lot.entry_price,
+            exit_price: price,
+            quantity: qty,
+            pnl,
+            pnl_ticks: points / self.tick_size,
+            return_pct: points / lot.entry_price * 100.0,
+            capital_return_pct: capital_return_pct(pnl, lot.equity_at_entry),
+            holding_cost,
+            funding,
+            commission,
+            exit_reason: reason,
+            partial,
+            initial_risk,
+            r_multiple,
+            tag: lot.tag,
+            bars_held: self.bar_index.saturating_sub(lot.entry_bar),
+            equity_at_entry: lot.equity_at_entry,
+            drawdown_at_entry: lot.drawdown_at_entry,
+        });
+    }
+
+    /// PnL per contract of the open position at `exit_price`, before commission.
     fn calc_pnl(&self, exit_price: f64) -> f64 {
-        let diff = exit_price - self.entry_price;
+        self.points_pnl(self.entry_price, exit_price)
+    }
+
+    /// PnL per contract of a position on the current side from `entry` to `exit_price`.
+    fn points_pnl(&self, entry: f64, exit_price: f64) -> f64 {
+        self.signed_move(entry, exit_price) * self.point_value
+    }
+
+    /// Points a position on the current side gains from `entry` to `exit_price`.
+    fn signed_move(&self, entry: f64, exit_price: f64) -> f64 {
+        let diff = exit_price - entry;
         match self.side {
-            Side::Long => diff * self.point_value,
-            Side::Short => -diff * self.point_value,
+            Side::Long => diff,
+            Side::Short => -diff,
             Side::Flat => 0.0,
         }
     }
 
     fn unrealized_pnl(&self, current_price: f64) -> f64 {
-        self.calc_pnl(current_price)
+        self.calc_pnl(current_price) * self.open_quantity
     }
 
-    /// Force-close any open position at the given price/time.
-    pub fn close_position(&mut self, price: f64, timestamp_us: i64) {
-        if self.side != Side::Flat {
-            self.process_signal(0, price, timestamp_us);
+    /// Force-close any open position at the given price/time because the data has run
+    /// out; its trades are recorded with `ExitReason::EndOfData`.
+    pub fn close_position(&mut self, price: f64, timestamp_us: i64, ctx: &FillContext) {
+        if self.side == Side::Flat {
+            return;
+        }
+        self.current_slippage = self.slippage_model.slippage(ctx, self.tick_size);
+        self.accrue_holding_cost(timestamp_us);
+        self.exit(
+            self.open_quantity,
+            price,
+            timestamp_us,
+            ExitReason::EndOfData,
+        );
+        self.push_equity(price, timestamp_us);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Direction;
+
+    const MINUTE_US: i64 = 60_000_000;
+
+    fn ctx() -> FillContext {
+        FillContext::default()
+    }
+
+    /// Feed `signal` at each price, one minute apart.
+    fn run(tracker: &mut PositionTracker, steps: &[(i32, f64)]) {
+        for (i, &(signal, price)) in steps.iter().enumerate() {
+            tracker.process_signal(signal, price, i as i64 * MINUTE_US, &ctx());
         }
     }
+
+    #[test]
+    fn target_ladder_fills_both_rungs() {
+        let mut tracker = PositionTracker::new(0.0, 50.0);
+        tracker.quantity = 4.0;
+        tracker
+            .set_target_ladder(vec![(4.0, 0.25), (2.0, 0.5)])
+            .unwrap();
+        run(&mut tracker, &[(1, 100.0), (1, 102.0), (1, 104.5)]);
+        let exits: Vec<(f64, f64)> = tracker
+            .trades
+            .iter()
+            .map(|t| (t.quantity, t.exit_price))
+            .collect();
+        assert_eq!(exits, [(2.0, 102.0), (1.0, 104.5)]);
+        assert!(tracker
+            .trades
+            .iter()
+            .all(|t| t.exit_reason == ExitReason::Target));
+        assert!(tracker.trades.iter().all(|t| t.partial));
+        assert_eq!(tracker.open_quantity, 1.0);
+        assert_eq!(tracker.side, Side::Long);
+    }
+
+    #[test]
+    fn target_ladder_sizes_rungs_off_a_pyramided_position() {
+        let mut tracker = PositionTracker::new_with_config(0.0, 50.0, 2);
+        tracker
+            .set_target_ladder(vec![(2.0, 0.5), (4.0, 0.5)])
+            .unwrap();
+        // Two one-contract lots at 100 and 101. The first rung is 2 points above their
+        // 100.5 average entry, the second 4 above the 101 lot left after it
+        run(
+            &mut tracker,
+            &[(1, 100.0), (1, 101.0), (HOLD, 102.5), (HOLD, 105.0)],
+        );
+        assert_eq!(tracker.trades.len(), 2);
+        assert_eq!(tracker.trades[0].entry_price, 100.0);
+        assert_eq!(tracker.trades[0].quantity, 1.0);
+        assert_eq!(tracker.trades[0].exit_price, 102.5);
+        assert_eq!(tracker.trades[1].entry_price, 101.0);
+        assert_eq!(tracker.trades[1].quantity, 1.0);
+        assert_eq!(tracker.trades[1].exit_price, 105.0);
+        assert_eq!(tracker.side, Side::Flat);
+        assert_eq!(tracker.open_quantity, 0.0);
+    }
+
+    fn bracket_trade(exit_price: f64) -> Trade {
+        let mut tracker = PositionTracker::new(0.0, 50.0);
+        // A wider default stop that the bracket overrides
+        tracker.stop_loss_points = Some(10.0);
+        tracker
+            .enter_with_bracket(Side::Long, 2.0, 4.0, 100.0, 0, &ctx())
+            .unwrap();
+        tracker.check_exits(101.0, MINUTE_US, &ctx());
+        tracker.check_exits(exit_price, 2 * MINUTE_US, &ctx());
+        assert_eq!(tracker.side, Side::Flat);
+        assert_eq!(tracker.trades.len(), 1);
+        tracker.trades.pop().unwrap()
+    }
+
+    #[test]
+    fn bracket_target_hit_first_is_two_r() {
+        let trade = bracket_trade(104.0);
+        assert_eq!(trade.exit_reason, ExitReason::Target);
+        assert_eq!(trade.initial_risk, Some(100.0));
+        assert_eq!(trade.pnl, 200.0);
+        assert_eq!(trade.r_multiple, Some(2.0));
+    }
+
+    #[test]
+    fn bracket_stop_hit_first_is_minus_one_r() {
+        let trade = bracket_trade(98.0);
+        assert_eq!(trade.exit_reason, ExitReason::StopLoss);
+        assert_eq!(trade.initial_risk, Some(100.0));
+        assert_eq!(trade.r_multiple, Some(-1.0));
+    }
+
+    #[test]
+    fn one_equity_point_per_signal_on_every_branch() {
+        let mut tracker = PositionTracker::new(2.0, 50.0);
+        tracker.initial_capital = 100_000.0;
+        // Flat, enter, hold, reverse, exit, stay flat
+        let steps = [
+            (0, 100.0),
+            (1, 100.0),
+            (1, 101.0),
+            (-1, 102.0),
+            (0, 101.0),
+            (0, 101.0),
+        ];
+        run(&mut tracker, &steps);
+        assert_eq!(tracker.equity_curve.len(), steps.len());
+        assert_eq!(tracker.equity_timestamps.len(), steps.len());
+        // The reversal's point is after both transitions: the long's realized PnL net of
+        // its round-turn commission, with the new short marked at its entry
+        assert_eq!(tracker.equity_curve[3], 100_000.0 + 100.0 - 4.0);
+        assert_eq!(
+            tracker.equity_curve[5],
+            100_000.0 + 100.0 - 4.0 + 50.0 - 4.0
+        );
+    }
+
+    #[test]
+    fn hold_keeps_the_position_until_flat() {
+        let mut tracker = PositionTracker::new(0.0, 50.0);
+        run(
+            &mut tracker,
+            &[(1, 100.0), (HOLD, 101.0), (HOLD, 102.0), (0, 103.0)],
+        );
+        assert_eq!(tracker.equity_curve.len(), 4);
+        assert_eq!(tracker.trades.len(), 1);
+        let trade = &tracker.trades[0];
+        assert_eq!((trade.entry_price, trade.exit_price), (100.0, 103.0));
+        assert_eq!(trade.exit_time_us, 3 * MINUTE_US);
+        assert_eq!(trade.exit_reason, ExitReason::Signal);
+    }
+
+    #[test]
+    fn hold_still_lets_engine_exits_fire() {
+        let mut tracker = PositionTracker::new(0.0, 50.0);
+        tracker.stop_loss_points = Some(2.0);
+        run(&mut tracker, &[(1, 100.0), (HOLD, 97.5), (HOLD, 99.0)]);
+        assert_eq!(tracker.side, Side::Flat);
+        assert_eq!(tracker.trades[0].exit_reason, ExitReason::StopLoss);
+    }
+
+    #[test]
+    fn trade_returns_in_ticks_and_percent() {
+        let mut tracker = PositionTracker::new(0.0, 50.0);
+        tracker.tick_size = 0.25;
+        run(&mut tracker, &[(1, 100.0), (-1, 104.0), (0, 105.0)]);
+        let long = &tracker.trades[0];
+        assert_eq!(long.pnl_ticks, 16.0);
+        assert_eq!(long.return_pct, 4.0);
+        // Signed by direction: the short lost a point
+        let short = &tracker.trades[1];
+        assert_eq!(short.pnl_ticks, -4.0);
+        assert!((short.return_pct + 100.0 / 104.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn partial_exit_then_close_records_two_trades() {
+        let mut tracker = PositionTracker::new(0.0, 50.0);
+        tracker.quantity = 4.0;
+        run(&mut tracker, &[(1, 100.0)]);
+        tracker
+            .process_partial_exit(0.5, 102.0, MINUTE_US, &ctx())
+            .unwrap();
+        assert_eq!(tracker.open_quantity, 2.0);
+        assert_eq!(tracker.entry_price, 100.0);
+        tracker.close_position(103.0, 2 * MINUTE_US, &ctx());
+        let got: Vec<(f64, f64, bool)> = tracker
+            .trades
+            .iter()
+            .map(|t| (t.quantity, t.pnl, t.partial))
+            .collect();
+        assert_eq!(got, [(2.0, 200.0, true), (2.0, 300.0, false)]);
+        assert!(tracker.process_partial_exit(1.5, 103.0, 0, &ctx()).is_err());
+    }
+
+    #[test]
+    fn volume_scaled_slippage_matches_fixed_at_the_reference_volume() {
+        let fixed = SlippageModel::Fixed(2.0);
+        let scaled = SlippageModel::VolumeScaled {
+            base: 2.0,
+            ref_volume: 100.0,
+        };
+        let at = |model: SlippageModel, volume: f64| {
+            let ctx = FillContext {
+                volume,
+                ..FillContext::default()
+            };
+            model.slippage(&ctx, 0.25)
+        };
+        for volume in [50.0, 100.0, 200.0] {
+            assert_eq!(at(fixed, volume), 0.5);
+        }
+        assert_eq!(at(scaled, 100.0), at(fixed, 100.0));
+        // Thinner bars slip further, busier ones less
+        assert_eq!(at(scaled, 50.0), 1.0);
+        assert_eq!(at(scaled, 200.0), 0.25);
+    }
+
+    #[test]
+    fn cooldown_after_a_stop_delays_re_entry() {
+        let mut tracker = PositionTracker::new(0.0, 50.0);
+        tracker.stop_loss_points = Some(2.0);
+        tracker.cooldown_secs = Some(300);
+        // Stopped out at 1m. Re-entries at 3m to 5m fall inside the cooldown; 6m is past it
+        run(
+            &mut tracker,
+            &[
+                (1, 100.0),
+                (1, 97.0),
+                (0, 97.0),
+                (1, 98.0),
+                (1, 98.0),
+                (1, 98.0),
+                (1, 99.0),
+            ],
+        );
+        assert_eq!(tracker.trades.len(), 1);
+        assert_eq!(tracker.trades[0].exit_reason, ExitReason::StopLoss);
+        assert_eq!(tracker.side, Side::Long);
+        assert_eq!(tracker.entry_price, 99.0);
+        assert_eq!(tracker.entry_time_us, 6 * MINUTE_US);
+    }
+
+    #[test]
+    fn cooldown_after_any_exit_also_follows_signal_exits() {
+        let steps = [(1, 100.0), (0, 101.0), (1, 102.0), (1, 103.0)];
+        let entry_after = |any_exit: bool| {
+            let mut tracker = PositionTracker::new(0.0, 50.0);
+            tracker.cooldown_secs = Some(120);
+            tracker.cooldown_after_any_exit = any_exit;
+            run(&mut tracker, &steps);
+            assert_eq!(tracker.trades[0].exit_reason, ExitReason::Signal);
+            (tracker.entry_price, tracker.entry_time_us)
+        };
+        // By default only stops start a cooldown
+        assert_eq!(entry_after(false), (102.0, 2 * MINUTE_US));
+        assert_eq!(entry_after(true), (103.0, 3 * MINUTE_US));
+    }
+
+    #[test]
+    fn max_holding_time_closes_a_position_the_signal_still_holds() {
+        let mut tracker = PositionTracker::new(0.0, 50.0);
+        tracker.max_holding_secs = Some(150);
+        run(
+            &mut tracker,
+            &[(1, 100.0), (1, 101.0), (1, 102.0), (1, 103.0), (1, 104.0)],
+        );
+        // First check past 150 s is at 3m; the still-long signal doesn't re-enter
+        assert_eq!(tracker.trades.len(), 1);
+        let trade = &tracker.trades[0];
+        assert_eq!(trade.exit_reason, ExitReason::MaxHoldTime);
+        assert_eq!(trade.exit_time_us, 3 * MINUTE_US);
+        assert_eq!(trade.exit_price, 103.0);
+        assert_eq!(tracker.side, Side::Flat);
+    }
+
+    #[test]
+    fn long_only_turns_short_signals_into_exits() {
+        let mut tracker = PositionTracker::new(0.0, 50.0);
+        for (i, signal) in [1, -1, 1, -1].into_iter().enumerate() {
+            let signal = Direction::LongOnly.clamp(signal);
+            let price = 100.0 + i as f64;
+            tracker.process_signal(signal, price, i as i64 * MINUTE_US, &ctx());
+            let expected = if i % 2 == 0 { Side::Long } else { Side::Flat };
+            assert_eq!(tracker.side, expected);
+        }
+        assert_eq!(tracker.trades.len(), 2);
+        assert!(tracker.trades.iter().all(|t| t.side == Side::Long));
+    }
+
+    #[test]
+    fn average_pyramiding_merges_entries_into_one_trade() {
+        let mut tracker = PositionTracker::new_with_config(0.0, 50.0, 2);
+        tracker.pyramid_mode = PyramidMode::Average;
+        run(&mut tracker, &[(1, 100.0), (1, 103.0)]);
+        assert_eq!(tracker.lots.len(), 1);
+        assert_eq!((tracker.open_quantity, tracker.entry_price), (2.0, 101.5));
+
+        tracker.process_signal(0, 105.0, 2 * MINUTE_US, &ctx());
+        assert_eq!(tracker.trades.len(), 1);
+        let trade = &tracker.trades[0];
+        assert_eq!((trade.quantity, trade.entry_price), (2.0, 101.5));
+        // 3.5 points on two contracts at $50 a point
+        assert_eq!(trade.pnl, 350.0);
+    }
+
+    #[test]
+    fn equity_curve_marks_an_open_loss_before_the_trade_closes() {
+        let mut tracker = PositionTracker::new(0.0, 50.0);
+        run(
+            &mut tracker,
+            &[(1, 100.0), (1, 98.0), (1, 97.0), (0, 101.0)],
+        );
+        // Down 2 and 3 points while open, then closed 1 point up
+        assert_eq!(tracker.equity_curve, [0.0, -100.0, -150.0, 50.0]);
+        assert_eq!(tracker.trades.len(), 1);
+        assert_eq!(tracker.trades[0].pnl, 50.0);
+    }
 }
@@ -0,0 +1,137 @@
+//! Paced replay of a SCID file as a Python iterator of ticks or completed bars.
+
+use std::time::Duration;
+
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::bar::{Bar, BarInterval};
+use crate::scid::{ScidFile, Tick};
+
+/// Longest single sleep, so Ctrl-C is noticed promptly during long gaps
+const SLEEP_QUANTUM: Duration = Duration::from_millis(100);
+
+/// Iterator over a SCID file that sleeps between items according to the original
+/// inter-tick gaps divided by `speed` (0 = no sleeping). Invalid records are skipped.
+#[pyclass(module = "backtest._engine")]
+pub struct Replay {
+    scid: ScidFile,
+    /// Index of the next record to read
+    next: usize,
+    speed: f64,
+    /// Yield completed bars of this interval instead of ticks
+    interval: Option<BarInterval>,
+    /// Bar still collecting ticks
+    current: Option<Bar>,
+    /// Timestamp of the last tick consumed, the reference for the next sleep
+    last_ts_us: Option<i64>,
+}
+
+impl Replay {
+    /// Replay `scid` from the first record at or after `start_us` (None = from the start).
+    pub fn new(
+        scid: ScidFile,
+        speed: f64,
+        interval: Option<BarInterval>,
+        start_us: Option<i64>,
+    ) -> Result<Self, String> {
+        if !(speed.is_finite() && speed >= 0.0) {
+            return Err(format!("speed must be >= 0, got {speed}"));
+        }
+        let next = start_us.map_or(0, |ts| scid.find_index_at_or_after(ts));
+        Ok(Replay {
+            scid,
+            next,
+            speed,
+            interval,
+            current: None,
+            last_ts_us: None,
+        })
+    }
+
+    /// Sleep for the gap since the last consumed tick, scaled by `speed`, with the GIL
+    /// released and pending signals checked after every quantum.
+    fn pace(&mut self, py: Python<'_>, timestamp_us: i64) -> PyResult<()> {
+        if let (Some(last), true) = (self.last_ts_us, self.speed > 0.0) {
+            let gap_us = (timestamp_us - last).max(0) as f64 / self.speed;
+            let mut remaining = Duration::from_secs_f64(gap_us / 1_000_000.0);
+            while !remaining.is_zero() {
+                let chunk = remaining.min(SLEEP_QUANTUM);
+                py.detach(|| std::thread::sleep(chunk));
+                py.check_signals()?;
+                remaining -= chunk;
+            }
+        }
+        self.last_ts_us = Some(timestamp_us);
+        Ok(())
+    }
+}
+
+#[pymethods]
+impl Replay {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python<'_>) -> PyResult<Option<Py<PyDict>>> {
+        while self.next < self.scid.num_records {
+            let index = self.next;
+            let tick = self.scid.tick(index);
+            self.next += 1;
+            if !tick.is_valid() {
+                continue;
+            }
+            self.pace(py, tick.timestamp_us)?;
+
+            let Some(interval) = self.interval else {
+                return tick_dict(py, &tick).map(Some);
+            };
+            let start = interval.bar_start(tick.timestamp_us);
+            match self.current.as_mut() {
+                Some(bar) if bar.timestamp_us == start => bar.update(&tick, index),
+                _ => {
+                    if let Some(done) = self.current.replace(Bar::from_tick(start, &tick, index)) {
+                        return bar_dict(py, &done).map(Some);
+                    }
+                }
+            }
+        }
+        // The last bar completes when the file runs out
+        match self.current.take() {
+            Some(bar) => bar_dict(py, &bar).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+fn tick_dict(py: Python<'_>, tick: &Tick) -> PyResult<Py<PyDict>> {
+    let d = PyDict::new(py);
+    d.set_item("timestamp", tick.timestamp_us as f64 / 1_000_000.0)?;
+    d.set_item("open", tick.open)?;
+    d.set_item("price", tick.price)?;
+    d.set_item("bid", tick.bid)?;
+    d.set_item("ask", tick.ask)?;
+    d.set_item("volume", tick.volume)?;
+    d.set_item("bid_volume", tick.bid_volume)?;
+    d.set_item("ask_volume", tick.ask_volume)?;
+    d.set_item("num_trades", tick.num_trades)?;
+    Ok(d.into())
+}
+
+fn bar_dict(py: Python<'_>, bar: &Bar) -> PyResult<Py<PyDict>> {
+    let d = PyDict::new(py);
+    d.set_item("timestamp", bar.timestamp_us as f64 / 1_000_000.0)?;
+    d.set_item("open", bar.open)?;
+    d.set_item("high", bar.high)?;
+    d.set_item("low", bar.low)?;
+    d.set_item("close", bar.close)?;
+    d.set_item("volume", bar.volume)?;
+    d.set_item("bid_volume", bar.bid_volume)?;
+    d.set_item("ask_volume", bar.ask_volume)?;
+    d.set_item("num_trades", bar.num_trades)?;
+    d.set_item("tick_count", bar.tick_count)?;
+    d.set_item("delta", bar.delta)?;
+    d.set_item("delta_high", bar.delta_high)?;
+    d.set_item("delta_low", bar.delta_low)?;
+    Ok(d.into())
+}
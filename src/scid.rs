@@ -1,11 +1,36 @@
-use memmap2::Mmap;
-use std::fs::File;
+use memmap2::{Mmap, MmapMut};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::ops::{Add, Range, Sub};
 use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::columns::TickColumns;
 
 const HEADER_SIZE: usize = 56;
+/// Size of `RawScidRecord`, the fields we decode. Files may declare larger records; the
+/// bytes past these fields are skipped.
 const RECORD_SIZE: usize = 40;
+const HEADER_VERSION: u16 = 1;
 /// Microseconds between 1899-12-30 and 1970-01-01 (Unix epoch).
 const EPOCH_OFFSET_US: i64 = 2_209_161_600_000_000;
+/// Day counts since 1899-12-30 accepted as an old-layout double DateTime (1982 to 2064).
+const LEGACY_DAYS_RANGE: Range<f64> = 30_000.0..60_000.0;
+const US_PER_DAY: f64 = 86_400_000_000.0;
+/// Plausible timestamp range for market data: 1990-01-01 to 2100-01-01 UTC.
+const MIN_VALID_TS_US: i64 = 631_152_000_000_000;
+const MAX_VALID_TS_US: i64 = 4_102_444_800_000_000;
+/// Default cap on the decompressed size of a gzip or zstd SCID file (8 GiB)
+pub const DEFAULT_MAX_DECOMPRESSED_SIZE: usize = 8 << 30;
+
+/// Sierra Chart pre-allocates unfilled records with FLT_MAX prices; any price this large
+/// (after the x100 scaling) is such a placeholder.
+const PLACEHOLDER_PRICE: f64 = f32::MAX as f64 / 100.0;
+/// Raw open-field markers Sierra Chart writes on the first and last records of a trade
+/// split into sub-trades (s_IntradayRecord::FIRST/LAST_SUB_TRADE_OF_UNBUNDLED_TRADE,
+/// -1.99900095e37 and -1.99900197e37, which round to these f32 values)
+const FIRST_SUB_TRADE_OPEN: f32 = -1.999_001e37;
+const LAST_SUB_TRADE_OPEN: f32 = -1.999_002e37;
 
 #[repr(C, packed)]
 #[derive(Clone, Copy)]
@@ -25,6 +50,9 @@ pub struct RawScidRecord {
 pub struct Tick {
     /// Unix timestamp in microseconds
     pub timestamp_us: i64,
+    /// Record open; 0 for plain trade records, the true open for aggregated records
+    pub open: f64,
+    /// Record close
     pub price: f64,
     pub bid: f64,
     pub ask: f64,
@@ -34,78 +62,1485 @@ pub struct Tick {
     pub num_trades: u32,
 }
 
+/// Why a record is dropped before it reaches bars or a strategy.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SkipReason {
+    NonPositivePrice,
+    /// NaN or infinite price, bid or ask
+    NonFinite,
+    /// Unfilled record with an FLT_MAX price, bid or ask
+    Placeholder,
+    /// Timestamp before 1990 or after 2100
+    TimestampOutOfRange,
+}
+
+impl Tick {
+    /// Whether the record holds a real tick (see `skip_reason`).
+    #[inline]
+    pub fn is_valid(&self) -> bool {
+        self.skip_reason().is_none()
+    }
+
+    /// The reason this tick should be skipped, or `None` if it is usable.
+    pub fn skip_reason(&self) -> Option<SkipReason> {
+        let prices = [self.price, self.bid, self.ask];
+        if !prices.iter().all(|p| p.is_finite()) {
+            Some(SkipReason::NonFinite)
+        } else if prices.iter().any(|p| p.abs() >= PLACEHOLDER_PRICE) {
+            Some(SkipReason::Placeholder)
+        } else if self.price <= 0.0 {
+            Some(SkipReason::NonPositivePrice)
+        } else if !(MIN_VALID_TS_US..MAX_VALID_TS_US).contains(&self.timestamp_us) {
+            Some(SkipReason::TimestampOutOfRange)
+        } else {
+            None
+        }
+    }
+
+    /// Aggressor volume imbalance: ask volume minus bid volume.
+    #[inline]
+    pub fn delta(&self) -> i64 {
+        self.ask_volume as i64 - self.bid_volume as i64
+    }
+
+    /// Kind of record, from the marker in the open field.
+    pub fn trade_type(&self) -> TradeType {
+        if self.open == 0.0 {
+            return TradeType::Normal;
+        }
+        match (self.open * 100.0) as f32 {
+            FIRST_SUB_TRADE_OPEN => TradeType::FirstSubTrade,
+            LAST_SUB_TRADE_OPEN => TradeType::LastSubTrade,
+            _ => TradeType::Aggregated,
+        }
+    }
+}
+
+/// What a record holds, per its open field.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TradeType {
+    /// A single trade (open 0). Also the middle records of an unbundled trade.
+    Normal,
+    /// First record of one trade split into several sub-trade records
+    FirstSubTrade,
+    /// Last record of an unbundled trade
+    LastSubTrade,
+    /// Several trades aggregated into one record, whose open is the true open
+    Aggregated,
+}
+
+impl TradeType {
+    /// Code for the `trade_type` column: 0 normal, 1 first sub-trade, 2 last sub-trade,
+    /// 3 aggregated.
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+}
+
+/// How the records of an unbundled trade (from a `FirstSubTrade` record through the next
+/// `LastSubTrade` record) count towards volume and trade counts. Prices always come from
+/// every record.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Unbundle {
+    /// Every record counts as stored
+    #[default]
+    Ignore,
+    /// Only the first record's volumes and trade count
+    First,
+    /// Only the last record's volumes and trade count
+    Last,
+    /// The summed volumes as one trade, on the last record
+    Sum,
+}
+
+impl std::str::FromStr for Unbundle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "ignore" => Ok(Unbundle::Ignore),
+            "first" => Ok(Unbundle::First),
+            "last" => Ok(Unbundle::Last),
+            "sum" => Ok(Unbundle::Sum),
+            _ => Err(format!("Unknown unbundle mode: {s}")),
+        }
+    }
+}
+
+/// Applies an `Unbundle` mode to records read in order, tracking whether they are inside
+/// an unbundled trade. Records that don't count keep their prices with zero volumes and
+/// trade count.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Unbundler {
+    mode: Unbundle,
+    in_bundle: bool,
+    /// Volumes of the bundle so far, under `Unbundle::Sum`
+    volume: u32,
+    bid_volume: u32,
+    ask_volume: u32,
+}
+
+impl Unbundler {
+    pub fn new(mode: Unbundle) -> Self {
+        Unbundler {
+            mode,
+            ..Default::default()
+        }
+    }
+
+    /// `tick` as it counts under the mode, given the records before it.
+    pub fn apply(&mut self, tick: &Tick) -> Tick {
+        if self.mode == Unbundle::Ignore {
+            return *tick;
+        }
+        let trade_type = tick.trade_type();
+        let first = trade_type == TradeType::FirstSubTrade;
+        if first {
+            // A new bundle also ends one left unterminated
+            self.in_bundle = true;
+            (self.volume, self.bid_volume, self.ask_volume) = (0, 0, 0);
+        } else if !self.in_bundle {
+            return *tick;
+        }
+        let last = trade_type == TradeType::LastSubTrade;
+        if last {
+            self.in_bundle = false;
+        }
+        let mut out = *tick;
+        match self.mode {
+            Unbundle::Ignore => {}
+            Unbundle::First if first => {}
+            Unbundle::Last if last => {}
+            Unbundle::Sum => {
+                self.volume = self.volume.saturating_add(tick.volume);
+                self.bid_volume = self.bid_volume.saturating_add(tick.bid_volume);
+                self.ask_volume = self.ask_volume.saturating_add(tick.ask_volume);
+                if last {
+                    out.volume = self.volume;
+                    out.bid_volume = self.bid_volume;
+                    out.ask_volume = self.ask_volume;
+                    out.num_trades = 1;
+                } else {
+                    zero_counts(&mut out);
+                }
+            }
+            _ => zero_counts(&mut out),
+        }
+        out
+    }
+}
+
+fn zero_counts(tick: &mut Tick) {
+    tick.volume = 0;
+    tick.bid_volume = 0;
+    tick.ask_volume = 0;
+    tick.num_trades = 0;
+}
+
+/// Counts of skipped records by reason.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SkipStats {
+    pub non_positive_price: usize,
+    pub non_finite: usize,
+    pub placeholder: usize,
+    pub timestamp_out_of_range: usize,
+    /// Valid ticks earlier than one already read, dropped under `TickOrder::Skip`
+    pub out_of_order: usize,
+}
+
+impl SkipStats {
+    /// Count `tick` if it should be skipped. Returns true if the tick is usable.
+    pub fn accept(&mut self, tick: &Tick) -> bool {
+        match tick.skip_reason() {
+            None => return true,
+            Some(SkipReason::NonPositivePrice) => self.non_positive_price += 1,
+            Some(SkipReason::NonFinite) => self.non_finite += 1,
+            Some(SkipReason::Placeholder) => self.placeholder += 1,
+            Some(SkipReason::TimestampOutOfRange) => self.timestamp_out_of_range += 1,
+        }
+        false
+    }
+
+    pub fn total(&self) -> usize {
+        self.non_positive_price
+            + self.non_finite
+            + self.placeholder
+            + self.timestamp_out_of_range
+            + self.out_of_order
+    }
+
+    /// Fail if more than `max_fraction` of `num_records` were skipped.
+    pub fn check(&self, num_records: usize, max_fraction: f64) -> Result<(), String> {
+        if num_records == 0 {
+            return Ok(());
+        }
+        let fraction = self.total() as f64 / num_records as f64;
+        if fraction > max_fraction {
+            return Err(format!(
+                "{} of {} records invalid ({:.1}% > {:.1}% allowed): \
+                 {} non-positive price, {} non-finite, {} placeholder, \
+                 {} timestamp out of range, {} out of order",
+                self.total(),
+                num_records,
+                fraction * 100.0,
+                max_fraction * 100.0,
+                self.non_positive_price,
+                self.non_finite,
+                self.placeholder,
+                self.timestamp_out_of_range,
+                self.out_of_order,
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Add for SkipStats {
+    type Output = SkipStats;
+
+    fn add(self, other: SkipStats) -> SkipStats {
+        SkipStats {
+            non_positive_price: self.non_positive_price + other.non_positive_price,
+            non_finite: self.non_finite + other.non_finite,
+            placeholder: self.placeholder + other.placeholder,
+            timestamp_out_of_range: self.timestamp_out_of_range + other.timestamp_out_of_range,
+            out_of_order: self.out_of_order + other.out_of_order,
+        }
+    }
+}
+
+impl Sub for SkipStats {
+    type Output = SkipStats;
+
+    fn sub(self, other: SkipStats) -> SkipStats {
+        SkipStats {
+            non_positive_price: self.non_positive_price - other.non_positive_price,
+            non_finite: self.non_finite - other.non_finite,
+            placeholder: self.placeholder - other.placeholder,
+            timestamp_out_of_range: self.timestamp_out_of_range - other.timestamp_out_of_range,
+            out_of_order: self.out_of_order - other.out_of_order,
+        }
+    }
+}
+
+/// Diagnostics from `ScidFile::validate`.
+#[derive(Clone, Debug, Default)]
+pub struct ValidationReport {
+    pub num_records: usize,
+    /// Records whose timestamp is earlier than the previous record's
+    pub non_monotonic_count: usize,
+    pub first_non_monotonic_index: Option<usize>,
+    /// Records with the same timestamp as the previous record
+    pub duplicate_count: usize,
+    /// Earliest and latest record timestamps (None for an empty file)
+    pub min_timestamp_us: Option<i64>,
+    pub max_timestamp_us: Option<i64>,
+    /// Gaps above the threshold as (index of the record after the gap, gap length in us)
+    pub gaps: Vec<(usize, i64)>,
+}
+
+/// What to do with records timestamped earlier than a record before them, as found in
+/// files concatenated from overlapping downloads.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TickOrder {
+    /// Fail if the file is not in timestamp order
+    Error,
+    /// Drop each valid tick earlier than the latest one so far, counting it in
+    /// `SkipStats::out_of_order`
+    #[default]
+    Skip,
+    /// Read the records in stable timestamp order
+    Sort,
+}
+
+impl std::str::FromStr for TickOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "error" => Ok(TickOrder::Error),
+            "skip" => Ok(TickOrder::Skip),
+            "sort" => Ok(TickOrder::Sort),
+            _ => Err(format!("Unknown tick order handling: {s}")),
+        }
+    }
+}
+
+/// How the record DateTime field is stored.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DateTimeLayout {
+    /// SCDateTimeMS: int64 microseconds since 1899-12-30
+    #[default]
+    Microseconds,
+    /// Older files: double days since 1899-12-30, with the time of day as the fraction
+    Days,
+}
+
+impl DateTimeLayout {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DateTimeLayout::Microseconds => "microseconds",
+            DateTimeLayout::Days => "days",
+        }
+    }
+
+    /// Guess the layout from the first record's DateTime bytes. Read as a double, an int64
+    /// microsecond count is a tiny denormal, so a plausible day count means the old layout.
+    fn detect(first_datetime: i64) -> Self {
+        let days = f64::from_bits(first_datetime as u64);
+        if LEGACY_DAYS_RANGE.contains(&days) {
+            DateTimeLayout::Days
+        } else {
+            DateTimeLayout::Microseconds
+        }
+    }
+
+    /// Unix microseconds of a raw DateTime field.
+    #[inline]
+    fn to_unix_us(self, raw: i64) -> i64 {
+        match self {
+            DateTimeLayout::Microseconds => raw - EPOCH_OFFSET_US,
+            DateTimeLayout::Days => {
+                (f64::from_bits(raw as u64) * US_PER_DAY).round() as i64 - EPOCH_OFFSET_US
+            }
+        }
+    }
+}
+
+/// How an SCID file on disk is compressed, detected from its leading magic bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    fn detect(bytes: &[u8]) -> Self {
+        match bytes {
+            [0x1f, 0x8b, ..] => Compression::Gzip,
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+}
+
+/// Bytes behind a `ScidFile`: the mapped file, or a decompressed copy in memory.
+enum Storage {
+    Mapped(Mmap),
+    Owned(Vec<u8>),
+}
+
+impl Storage {
+    /// Map the file at `path`, decompressing it into memory if it is gzip or zstd
+    /// compressed. Fails rather than decompress more than `max_decompressed_size` bytes.
+    fn load(path: &Path, max_decompressed_size: usize) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| format!("open: {e}"))?;
+        let mmap = unsafe { Mmap::map(&file) }.map_err(|e| format!("mmap: {e}"))?;
+        let reader: Box<dyn Read + '_> = match Compression::detect(&mmap) {
+            Compression::None => return Ok(Storage::Mapped(mmap)),
+            Compression::Gzip => Box::new(flate2::read::MultiGzDecoder::new(&mmap[..])),
+            Compression::Zstd => Box::new(
+                zstd::stream::read::Decoder::new(&mmap[..]).map_err(|e| format!("zstd: {e}"))?,
+            ),
+        };
+        let mut bytes = Vec::new();
+        reader
+            .take(max_decompressed_size as u64 + 1)
+            .read_to_end(&mut bytes)
+            .map_err(|e| format!("decompress: {e}"))?;
+        if bytes.len() > max_decompressed_size {
+            return Err(format!(
+                "Decompressed SCID data exceeds the {max_decompressed_size}-byte limit"
+            ));
+        }
+        Ok(Storage::Owned(bytes))
+    }
+
+    fn bytes(&self) -> &[u8] {
+        match self {
+            Storage::Mapped(mmap) => mmap,
+            Storage::Owned(bytes) => bytes,
+        }
+    }
+}
+
 pub struct ScidFile {
-    _mmap: Mmap,
+    _storage: Storage,
+    /// Start of the map, moved forward past the records `open_range` leaves out
     ptr: *const u8,
+    header_size: usize,
+    /// Record stride declared in the header, at least RECORD_SIZE
+    record_size: usize,
+    header: ScidHeader,
+    /// Header format version
+    pub version: u16,
+    /// DateTime encoding detected from the first record
+    pub datetime_layout: DateTimeLayout,
     pub num_records: usize,
+    /// Whether the records are in non-decreasing timestamp order, found on first use
+    sorted: OnceLock<bool>,
 }
 
-// Safety: the mmap is read-only and lives as long as ScidFile
+// Safety: the storage is read-only and lives as long as ScidFile
 unsafe impl Send for ScidFile {}
 unsafe impl Sync for ScidFile {}
 
 impl ScidFile {
+    /// Open the SCID file at `path`. Gzip and zstd compressed files (e.g. `.scid.zst`)
+    /// are recognized by their magic bytes and decompressed in full into memory, up to
+    /// `DEFAULT_MAX_DECOMPRESSED_SIZE`; plain files are memory-mapped.
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, String> {
-        let file = File::open(path.as_ref()).map_err(|e| format!("open: {e}"))?;
-        let mmap = unsafe { Mmap::map(&file) }.map_err(|e| format!("mmap: {e}"))?;
+        Self::open_with_limit(path, DEFAULT_MAX_DECOMPRESSED_SIZE)
+    }
 
-        // Validate header
-        if mmap.len() < HEADER_SIZE {
-            return Err("File too small for SCID header".into());
+    /// `open` with a cap on the decompressed size of a compressed file, so a corrupt file
+    /// or decompression bomb fails instead of exhausting memory.
+    pub fn open_with_limit<P: AsRef<Path>>(
+        path: P,
+        max_decompressed_size: usize,
+    ) -> Result<Self, String> {
+        Self::from_storage(Storage::load(path.as_ref(), max_decompressed_size)?)
+    }
+
+    /// Open only the records timestamped in `[start_us, end_us)`. The bounds are found by
+    /// binary search, so nothing outside the range is read; assumes time-ordered records.
+    /// A start before the first record simply starts at the first record.
+    pub fn open_range<P: AsRef<Path>>(path: P, start_us: i64, end_us: i64) -> Result<Self, String> {
+        if start_us > end_us {
+            return Err(format!("range start {start_us} is after end {end_us}"));
         }
-        if &mmap[0..4] != b"SCID" {
-            return Err("Invalid SCID magic bytes".into());
+        Ok(Self::open(path)?.narrow_to(start_us, end_us))
+    }
+
+    /// Limit an open file to the records timestamped in `[start_us, end_us)`, found by
+    /// binary search; assumes time-ordered records.
+    pub fn narrow_to(mut self, start_us: i64, end_us: i64) -> Self {
+        let first = self.find_index_at_or_after(start_us);
+        let last = self.find_index_at_or_after(end_us).max(first);
+        // Safety: first <= num_records, so the pointer stays within (or one past) the map
+        self.ptr = unsafe { self.ptr.add(first * self.record_size) };
+        self.num_records = last - first;
+        self
+    }
+
+    /// An in-memory SCID file holding `ticks`, encoded exactly as `write_scid` would
+    /// write them. Ticks must be in non-decreasing timestamp order.
+    pub fn from_ticks(ticks: &[Tick]) -> Result<Self, String> {
+        check_sorted(ticks, i64::MIN)?;
+        let mut mmap = MmapMut::map_anon(HEADER_SIZE + ticks.len() * RECORD_SIZE)
+            .map_err(|e| format!("mmap: {e}"))?;
+        mmap[..HEADER_SIZE].copy_from_slice(&encode_header());
+        for (rec, tick) in mmap[HEADER_SIZE..].chunks_exact_mut(RECORD_SIZE).zip(ticks) {
+            rec.copy_from_slice(&encode_record(tick));
+        }
+        Self::from_mmap(mmap)
+    }
+
+    /// One in-memory SCID file holding the records of `files` back to back, re-encoded as
+    /// `write_scid` would write them. Each file's first valid tick must not be earlier
+    /// than the last valid tick of the files before it. Copies all records, so it needs
+    /// memory for the combined data.
+    pub fn concat(files: Vec<ScidFile>) -> Result<Self, String> {
+        let mut prev_last: Option<(usize, i64)> = None;
+        for (k, file) in files.iter().enumerate() {
+            let first = file.iter().find(Tick::is_valid).map(|t| t.timestamp_us);
+            if let (Some((j, last)), Some(first)) = (prev_last, first) {
+                if first < last {
+                    return Err(format!(
+                        "File {k} starts at {first} us, before the last tick of file {j} at \
+                         {last} us"
+                    ));
+                }
+            }
+            if let Some(last) = file.iter().rev().find(Tick::is_valid) {
+                prev_last = Some((k, last.timestamp_us));
+            }
+        }
+        let total: usize = files.iter().map(|f| f.num_records).sum();
+        let mut mmap = MmapMut::map_anon(HEADER_SIZE + total * RECORD_SIZE)
+            .map_err(|e| format!("mmap: {e}"))?;
+        mmap[..HEADER_SIZE].copy_from_slice(&encode_header());
+        let records = mmap[HEADER_SIZE..].chunks_exact_mut(RECORD_SIZE);
+        for (rec, tick) in records.zip(files.iter().flat_map(ScidFile::iter)) {
+            rec.copy_from_slice(&encode_record(&tick));
         }
+        Self::from_mmap(mmap)
+    }
+
+    fn from_mmap(mmap: MmapMut) -> Result<Self, String> {
+        let mmap = mmap.make_read_only().map_err(|e| format!("mmap: {e}"))?;
+        Self::from_storage(Storage::Mapped(mmap))
+    }
 
-        let data_len = mmap.len() - HEADER_SIZE;
-        if data_len % RECORD_SIZE != 0 {
+    fn from_storage(storage: Storage) -> Result<Self, String> {
+        let bytes = storage.bytes();
+        let header = parse_header(bytes, bytes.len())?;
+        let (header_size, record_size) = (header.header_size as usize, header.record_size as usize);
+        let data_len = bytes.len() - header_size;
+        if !data_len.is_multiple_of(record_size) {
             return Err(format!(
-                "Data length {data_len} not divisible by record size {RECORD_SIZE}"
+                "Data length {data_len} (after a {header_size}-byte header) not divisible by \
+                 the declared record size {record_size}"
             ));
         }
-        let num_records = data_len / RECORD_SIZE;
-        let ptr = mmap.as_ptr();
+        let num_records = data_len / record_size;
+        // The heap buffer of an owned Vec doesn't move with the Storage
+        let ptr = bytes.as_ptr();
 
-        Ok(ScidFile {
-            _mmap: mmap,
+        let mut scid = ScidFile {
+            _storage: storage,
             ptr,
+            header_size,
+            record_size,
+            header,
+            version: header.version,
+            datetime_layout: DateTimeLayout::Microseconds,
             num_records,
-        })
+            sorted: OnceLock::new(),
+        };
+        if num_records > 0 {
+            scid.datetime_layout = DateTimeLayout::detect(scid.raw_record(0).sc_datetime);
+        }
+        Ok(scid)
     }
 
     #[inline]
     pub fn raw_record(&self, index: usize) -> &RawScidRecord {
         debug_assert!(index < self.num_records);
         unsafe {
-            let offset = HEADER_SIZE + index * RECORD_SIZE;
+            let offset = self.header_size + index * self.record_size;
             &*(self.ptr.add(offset) as *const RawScidRecord)
         }
     }
 
     #[inline]
     pub fn tick(&self, index: usize) -> Tick {
-        let r = self.raw_record(index);
-        let sc_dt = r.sc_datetime;
-        let close = r.close;
-        let high = r.high;
-        let low = r.low;
-        let total_volume = r.total_volume;
-        let bid_volume = r.bid_volume;
-        let ask_volume = r.ask_volume;
-        let num_trades = r.num_trades;
-        Tick {
-            timestamp_us: sc_dt - EPOCH_OFFSET_US,
-            price: close as f64 / 100.0,
-            bid: low as f64 / 100.0,
-            ask: high as f64 / 100.0,
-            volume: total_volume,
-            bid_volume,
-            ask_volume,
-            num_trades,
+        decode_record(self.raw_record(index), self.datetime_layout)
+    }
+
+    /// Count the records that would be skipped as invalid.
+    pub fn skip_stats(&self) -> SkipStats {
+        self.skip_stats_in(0..self.num_records)
+    }
+
+    /// Count the records in `range` that would be skipped as invalid.
+    pub fn skip_stats_in(&self, range: Range<usize>) -> SkipStats {
+        let mut stats = SkipStats::default();
+        for tick in self.iter_range(range.start, range.end) {
+            stats.accept(&tick);
         }
+        stats
+    }
+
+    /// Index of the first record timestamped at or after `timestamp_us`, or
+    /// `num_records` if there is none. Binary search; assumes time-ordered records.
+    pub fn find_index_at_or_after(&self, timestamp_us: i64) -> usize {
+        let (mut lo, mut hi) = (0, self.num_records);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if self.tick(mid).timestamp_us < timestamp_us {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        lo
+    }
+
+    /// Scan all records for backwards timestamps, duplicated timestamps and time gaps
+    /// longer than `gap_threshold_us`. Read-only; the file is not modified.
+    pub fn validate(&self, gap_threshold_us: i64) -> ValidationReport {
+        let mut report = ValidationReport {
+            num_records: self.num_records,
+            ..Default::default()
+        };
+        let mut prev = i64::MIN;
+        for i in 0..self.num_records {
+            let ts = self.tick(i).timestamp_us;
+            if i > 0 {
+                if ts < prev {
+                    report.non_monotonic_count += 1;
+                    report.first_non_monotonic_index.get_or_insert(i);
+                } else if ts == prev {
+                    report.duplicate_count += 1;
+                } else if ts - prev > gap_threshold_us {
+                    report.gaps.push((i, ts - prev));
+                }
+            }
+            report.min_timestamp_us = Some(report.min_timestamp_us.map_or(ts, |m| m.min(ts)));
+            report.max_timestamp_us = Some(report.max_timestamp_us.map_or(ts, |m| m.max(ts)));
+            prev = ts;
+        }
+        let _ = self.sorted.set(report.non_monotonic_count == 0);
+        report
+    }
+
+    /// Whether the records are in non-decreasing timestamp order. Scans the file on the
+    /// first call (unless `validate` already has) and caches the answer.
+    pub fn is_sorted(&self) -> bool {
+        *self.sorted.get_or_init(|| {
+            (1..self.num_records)
+                .all(|i| self.tick(i - 1).timestamp_us <= self.tick(i).timestamp_us)
+        })
+    }
+
+    /// Fail with the first offending record unless the records are in timestamp order.
+    pub fn check_order(&self) -> Result<(), String> {
+        if self.is_sorted() {
+            return Ok(());
+        }
+        let report = self.validate(i64::MAX);
+        Err(format!(
+            "{} records are earlier than the record before them (first at index {})",
+            report.non_monotonic_count,
+            report.first_non_monotonic_index.unwrap_or(0)
+        ))
+    }
+
+    /// Check every record for timestamps earlier than the record before and for
+    /// non-positive or non-finite trade prices, reporting each problem found with its count
+    /// and first record index. (Not `validate`, which reports timestamp order and gaps.)
+    pub fn check_integrity(&self) -> Result<(), Vec<String>> {
+        let mut backwards = (0, None);
+        let mut bad_price = (0, None);
+        let mut prev = i64::MIN;
+        for (i, tick) in self.iter().enumerate() {
+            if tick.timestamp_us < prev {
+                backwards.0 += 1;
+                backwards.1.get_or_insert(i);
+            }
+            if !(tick.price.is_finite() && tick.price > 0.0) {
+                bad_price.0 += 1;
+                bad_price.1.get_or_insert(i);
+            }
+            prev = tick.timestamp_us;
+        }
+        let mut errors = Vec::new();
+        if let (count, Some(first)) = backwards {
+            errors.push(format!(
+                "{count} records are timestamped earlier than the record before them \
+                 (first at index {first})"
+            ));
+        }
+        if let (count, Some(first)) = bad_price {
+            errors.push(format!(
+                "{count} records have a non-positive or non-finite price (first at index {first})"
+            ));
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Record indices in stable timestamp order. Only the permutation is allocated; the
+    /// records stay in the map.
+    pub fn sorted_indices(&self) -> Vec<usize> {
+        let mut indices: Vec<usize> = (0..self.num_records).collect();
+        if !self.is_sorted() {
+            indices.sort_by_key(|&i| self.tick(i).timestamp_us);
+        }
+        indices
+    }
+
+    /// Copy records `range` (clamped to the file) into columns, invalid ones included.
+    pub fn columns(&self, range: Range<usize>) -> TickColumns {
+        self.iter_range(range.start, range.end).collect()
+    }
+
+    /// The file's header fields.
+    pub fn header(&self) -> ScidHeader {
+        self.header
     }
 
     pub fn ticks(&self) -> Vec<Tick> {
-        (0..self.num_records).map(|i| self.tick(i)).collect()
+        self.iter().collect()
+    }
+
+    /// Decode the records one at a time, straight from the map.
+    pub fn iter(&self) -> ScidIter<'_> {
+        self.iter_range(0, self.num_records)
+    }
+
+    /// `iter` over records `start..end`, clamped to the file.
+    pub fn iter_range(&self, start: usize, end: usize) -> ScidIter<'_> {
+        let end = end.min(self.num_records);
+        ScidIter {
+            scid: self,
+            index: start.min(end),
+            end,
+        }
+    }
+}
+
+/// Merge the ticks of several files into one series in timestamp order. Where files
+/// overlap, a timestamp present in an earlier-listed file drops the later files' ticks at
+/// that timestamp; ticks sharing a timestamp within one file are all kept, in file order.
+pub fn merge_tick_vecs(vecs: Vec<Vec<Tick>>) -> Vec<Tick> {
+    merge_by_timestamp(vecs, |tick| tick.timestamp_us)
+}
+
+/// `merge_tick_vecs` for any timestamped items.
+pub(crate) fn merge_by_timestamp<T>(vecs: Vec<Vec<T>>, timestamp_us: impl Fn(&T) -> i64) -> Vec<T> {
+    let mut all: Vec<(usize, T)> = vecs
+        .into_iter()
+        .enumerate()
+        .flat_map(|(file, items)| items.into_iter().map(move |item| (file, item)))
+        .collect();
+    // Stable, and cheap on the usual input of back-to-back sorted runs
+    all.sort_by_key(|(file, item)| (timestamp_us(item), *file));
+    let mut merged = Vec::with_capacity(all.len());
+    // Latest timestamp and the file it was taken from
+    let mut owner: Option<(i64, usize)> = None;
+    for (file, item) in all {
+        let ts = timestamp_us(&item);
+        match owner {
+            Some((t, owner_file)) if t == ts && owner_file != file => continue,
+            _ => owner = Some((ts, file)),
+        }
+        merged.push(item);
+    }
+    merged
+}
+
+/// Iterator decoding a range of `ScidFile` records in place, without copying the file.
+pub struct ScidIter<'a> {
+    scid: &'a ScidFile,
+    /// Next record from the front
+    index: usize,
+    /// One past the next record from the back
+    end: usize,
+}
+
+impl Iterator for ScidIter<'_> {
+    type Item = Tick;
+
+    #[inline]
+    fn next(&mut self) -> Option<Tick> {
+        if self.index >= self.end {
+            return None;
+        }
+        let tick = self.scid.tick(self.index);
+        self.index += 1;
+        Some(tick)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.end - self.index;
+        (len, Some(len))
+    }
+}
+
+impl DoubleEndedIterator for ScidIter<'_> {
+    fn next_back(&mut self) -> Option<Tick> {
+        if self.index >= self.end {
+            return None;
+        }
+        self.end -= 1;
+        Some(self.scid.tick(self.end))
+    }
+}
+
+impl ExactSizeIterator for ScidIter<'_> {}
+
+/// Fields of the 56-byte SCID file header (Sierra Chart's s_IntradayFileHeader). The
+/// rest of the header is reserved.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScidHeader {
+    /// Header format version
+    pub version: u16,
+    /// Bytes before the first record
+    pub header_size: u32,
+    /// Bytes per record
+    pub record_size: u32,
+    /// Sierra Chart's UTCStartIndex field (0 in files it writes today)
+    pub utc_start_index: u32,
+}
+
+impl ScidHeader {
+    /// Read just the header of the SCID file at `path` (decompressing only its start if
+    /// the file is gzip or zstd compressed), without checking its sizes against the file,
+    /// so non-standard variants can be inspected.
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let file = File::open(path.as_ref()).map_err(|e| format!("open: {e}"))?;
+        let mut file = BufReader::new(file);
+        let magic = file.fill_buf().map_err(|e| format!("read: {e}"))?;
+        let mut reader: Box<dyn Read> = match Compression::detect(magic) {
+            Compression::None => Box::new(file),
+            Compression::Gzip => Box::new(flate2::bufread::MultiGzDecoder::new(file)),
+            Compression::Zstd => Box::new(
+                zstd::stream::read::Decoder::with_buffer(file).map_err(|e| format!("zstd: {e}"))?,
+            ),
+        };
+        let mut bytes = [0u8; HEADER_SIZE];
+        reader
+            .read_exact(&mut bytes)
+            .map_err(|_| "File too small for SCID header".to_string())?;
+        Self::parse(&bytes)
+    }
+
+    /// Decode the header at the start of `bytes`, checking only the magic bytes.
+    pub fn parse(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() < HEADER_SIZE {
+            return Err("File too small for SCID header".into());
+        }
+        if &bytes[0..4] != b"SCID" {
+            return Err("Invalid SCID magic bytes".into());
+        }
+        let word = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+        Ok(ScidHeader {
+            header_size: word(4),
+            record_size: word(8),
+            version: u16::from_le_bytes(bytes[12..14].try_into().unwrap()),
+            utc_start_index: word(16),
+        })
+    }
+}
+
+/// Decode the header at the start of `bytes` and check its sizes against a file of
+/// `file_len` bytes.
+fn parse_header(bytes: &[u8], file_len: usize) -> Result<ScidHeader, String> {
+    let header = ScidHeader::parse(bytes)?;
+    let (header_size, record_size) = (header.header_size as usize, header.record_size as usize);
+    if header_size < HEADER_SIZE || header_size > file_len {
+        return Err(format!(
+            "Invalid SCID header size {header_size}: must be at least {HEADER_SIZE} \
+             and no larger than the {file_len}-byte file"
+        ));
+    }
+    // Smaller records can't hold the fields we decode; larger ones carry extra fields
+    // after them, which are skipped
+    if record_size < RECORD_SIZE {
+        return Err(format!(
+            "Unsupported SCID record size {record_size} (header size {header_size}): \
+             records must be at least {RECORD_SIZE} bytes"
+        ));
+    }
+    Ok(header)
+}
+
+/// Every integrity problem found in the SCID file at `path`: bad magic bytes, a header or
+/// record size this reader can't use, a data length that isn't a whole number of records,
+/// then (if the layout is sound) the record checks of `ScidFile::check_integrity`. A
+/// compressed file is checked after decompression. Empty for a clean file; only an
+/// unreadable file is an error.
+pub fn check_integrity<P: AsRef<Path>>(path: P) -> Result<Vec<String>, String> {
+    let storage = Storage::load(path.as_ref(), DEFAULT_MAX_DECOMPRESSED_SIZE)?;
+    let bytes = storage.bytes();
+    let file_len = bytes.len();
+    if file_len < HEADER_SIZE {
+        return Ok(vec![format!(
+            "File is {file_len} bytes, too small for the {HEADER_SIZE}-byte SCID header"
+        )]);
+    }
+
+    let mut errors = Vec::new();
+    if &bytes[0..4] != b"SCID" {
+        errors.push("Invalid SCID magic bytes".to_string());
+    }
+    let word = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap()) as usize;
+    let (header_size, record_size) = (word(4), word(8));
+    let header_ok = (HEADER_SIZE..=file_len).contains(&header_size);
+    if !header_ok {
+        errors.push(format!(
+            "Invalid SCID header size {header_size}: must be at least {HEADER_SIZE} and no \
+             larger than the {file_len}-byte file"
+        ));
+    }
+    if record_size < RECORD_SIZE {
+        errors.push(format!(
+            "Unsupported SCID record size {record_size}: records must be at least \
+             {RECORD_SIZE} bytes"
+        ));
+    } else if header_ok && !(file_len - header_size).is_multiple_of(record_size) {
+        errors.push(format!(
+            "Data length {} (after a {header_size}-byte header) is not a multiple of the \
+             record size {record_size}",
+            file_len - header_size
+        ));
+    }
+    if errors.is_empty() {
+        if let Err(record_errors) = ScidFile::from_storage(storage)?.check_integrity() {
+            errors.extend(record_errors);
+        }
+    }
+    Ok(errors)
+}
+
+/// Convert a raw record to a tick: DateTime to Unix-us, prices from the x100 floats.
+#[inline]
+fn decode_record(r: &RawScidRecord, layout: DateTimeLayout) -> Tick {
+    let sc_dt = r.sc_datetime;
+    let open = r.open;
+    let close = r.close;
+    let high = r.high;
+    let low = r.low;
+    let total_volume = r.total_volume;
+    let bid_volume = r.bid_volume;
+    let ask_volume = r.ask_volume;
+    let num_trades = r.num_trades;
+    Tick {
+        timestamp_us: layout.to_unix_us(sc_dt),
+        open: open as f64 / 100.0,
+        price: close as f64 / 100.0,
+        bid: low as f64 / 100.0,
+        ask: high as f64 / 100.0,
+        volume: total_volume,
+        bid_volume,
+        ask_volume,
+        num_trades,
+    }
+}
+
+/// Sequential SCID reader over buffered file IO, for files too large to map into memory.
+/// Records are returned as stored, including ones `Tick::is_valid` rejects.
+pub struct ScidReader {
+    reader: BufReader<File>,
+    /// Record stride declared in the header
+    record_size: usize,
+    /// Header format version
+    pub version: u16,
+    /// DateTime encoding, detected from the first record once it has been read
+    pub datetime_layout: Option<DateTimeLayout>,
+}
+
+impl ScidReader {
+    /// Open `path` and position the reader at the first record.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let file = File::open(path.as_ref()).map_err(|e| format!("open: {e}"))?;
+        let file_len = file.metadata().map_err(|e| format!("stat: {e}"))?.len() as usize;
+        let mut reader = BufReader::new(file);
+        let mut header = [0u8; HEADER_SIZE];
+        reader
+            .read_exact(&mut header)
+            .map_err(|_| "File too small for SCID header".to_string())?;
+        let header = parse_header(&header, file_len)?;
+        // Skip any header bytes past the fields we read
+        reader
+            .seek_relative((header.header_size as usize - HEADER_SIZE) as i64)
+            .map_err(|e| format!("read: {e}"))?;
+        Ok(ScidReader {
+            reader,
+            record_size: header.record_size as usize,
+            version: header.version,
+            datetime_layout: None,
+        })
+    }
+
+    /// The next record, None at the end of the file, or an error for a read failure or a
+    /// truncated final record.
+    pub fn next_tick(&mut self) -> Option<Result<Tick, String>> {
+        let mut buf = [0u8; RECORD_SIZE];
+        let mut filled = 0;
+        while filled < RECORD_SIZE {
+            match self.reader.read(&mut buf[filled..]) {
+                Ok(0) if filled == 0 => return None,
+                Ok(0) => {
+                    return Some(Err(format!(
+                        "Truncated SCID record: {filled} of {RECORD_SIZE} bytes"
+                    )))
+                }
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Some(Err(format!("read: {e}"))),
+            }
+        }
+        if self.record_size > RECORD_SIZE {
+            if let Err(e) = self
+                .reader
+                .seek_relative((self.record_size - RECORD_SIZE) as i64)
+            {
+                return Some(Err(format!("read: {e}")));
+            }
+        }
+        // Safety: RawScidRecord is packed plain data of exactly RECORD_SIZE bytes
+        let raw: RawScidRecord = unsafe { std::ptr::read_unaligned(buf.as_ptr().cast()) };
+        let layout = *self
+            .datetime_layout
+            .get_or_insert_with(|| DateTimeLayout::detect(raw.sc_datetime));
+        Some(Ok(decode_record(&raw, layout)))
+    }
+}
+
+impl IntoIterator for ScidReader {
+    type Item = Tick;
+    type IntoIter = ScidReaderIter;
+
+    fn into_iter(self) -> ScidReaderIter {
+        ScidReaderIter {
+            reader: self,
+            error: None,
+        }
+    }
+}
+
+/// Iterator over the records of a `ScidReader`. Iteration ends at the first read error,
+/// which `error` then returns.
+pub struct ScidReaderIter {
+    reader: ScidReader,
+    error: Option<String>,
+}
+
+impl ScidReaderIter {
+    /// The error that ended iteration early, if any.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
+
+impl Iterator for ScidReaderIter {
+    type Item = Tick;
+
+    fn next(&mut self) -> Option<Tick> {
+        if self.error.is_some() {
+            return None;
+        }
+        match self.reader.next_tick()? {
+            Ok(tick) => Some(tick),
+            Err(e) => {
+                self.error = Some(e);
+                None
+            }
+        }
+    }
+}
+
+fn encode_header() -> [u8; HEADER_SIZE] {
+    let mut header = [0u8; HEADER_SIZE];
+    header[0..4].copy_from_slice(b"SCID");
+    header[4..8].copy_from_slice(&(HEADER_SIZE as u32).to_le_bytes());
+    header[8..12].copy_from_slice(&(RECORD_SIZE as u32).to_le_bytes());
+    header[12..14].copy_from_slice(&HEADER_VERSION.to_le_bytes());
+    header
+}
+
+/// Inverse of `ScidFile::tick`: Unix-us back to SC datetime, prices back to the raw x100 floats.
+fn encode_record(tick: &Tick) -> [u8; RECORD_SIZE] {
+    let mut rec = [0u8; RECORD_SIZE];
+    let sc_datetime = tick.timestamp_us + EPOCH_OFFSET_US;
+    rec[0..8].copy_from_slice(&sc_datetime.to_le_bytes());
+    rec[8..12].copy_from_slice(&((tick.open * 100.0) as f32).to_le_bytes());
+    rec[12..16].copy_from_slice(&((tick.ask * 100.0) as f32).to_le_bytes());
+    rec[16..20].copy_from_slice(&((tick.bid * 100.0) as f32).to_le_bytes());
+    rec[20..24].copy_from_slice(&((tick.price * 100.0) as f32).to_le_bytes());
+    rec[24..28].copy_from_slice(&tick.num_trades.to_le_bytes());
+    rec[28..32].copy_from_slice(&tick.volume.to_le_bytes());
+    rec[32..36].copy_from_slice(&tick.bid_volume.to_le_bytes());
+    rec[36..40].copy_from_slice(&tick.ask_volume.to_le_bytes());
+    rec
+}
+
+fn check_sorted(ticks: &[Tick], after_us: i64) -> Result<(), String> {
+    let mut prev = after_us;
+    for (i, t) in ticks.iter().enumerate() {
+        if t.timestamp_us < prev {
+            return Err(format!(
+                "Tick {i} timestamp {} is earlier than the preceding record {prev}",
+                t.timestamp_us
+            ));
+        }
+        prev = t.timestamp_us;
+    }
+    Ok(())
+}
+
+fn write_records(file: File, ticks: &[Tick], with_header: bool) -> Result<(), String> {
+    let mut w = BufWriter::new(file);
+    if with_header {
+        w.write_all(&encode_header())
+            .map_err(|e| format!("write: {e}"))?;
+    }
+    for t in ticks {
+        w.write_all(&encode_record(t))
+            .map_err(|e| format!("write: {e}"))?;
+    }
+    w.flush().map_err(|e| format!("write: {e}"))
+}
+
+/// Write ticks to a new SCID file, replacing any existing file at `path`.
+/// Ticks must be in non-decreasing timestamp order.
+pub fn write_scid<P: AsRef<Path>>(path: P, ticks: &[Tick]) -> Result<(), String> {
+    check_sorted(ticks, i64::MIN)?;
+    let file = File::create(path.as_ref()).map_err(|e| format!("create: {e}"))?;
+    write_records(file, ticks, true)
+}
+
+/// Append ticks to an existing SCID file (or create it if missing). The first appended
+/// tick must not be earlier than the file's last record.
+pub fn append_scid<P: AsRef<Path>>(path: P, ticks: &[Tick]) -> Result<(), String> {
+    if !path.as_ref().exists() {
+        return write_scid(path, ticks);
+    }
+    let last_us = {
+        let existing = ScidFile::open(path.as_ref())?;
+        if existing.datetime_layout != DateTimeLayout::Microseconds {
+            return Err("Cannot append to an SCID file with the old days DateTime layout".into());
+        }
+        if existing.record_size != RECORD_SIZE {
+            return Err(format!(
+                "Cannot append to an SCID file with {}-byte records",
+                existing.record_size
+            ));
+        }
+        if existing.num_records > 0 {
+            existing.tick(existing.num_records - 1).timestamp_us
+        } else {
+            i64::MIN
+        }
+    };
+    check_sorted(ticks, last_us)?;
+    let file = OpenOptions::new()
+        .append(true)
+        .open(path.as_ref())
+        .map_err(|e| format!("open: {e}"))?;
+    write_records(file, ticks, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tick(marker: Option<f32>, volume: u32) -> Tick {
+        Tick {
+            timestamp_us: 1_704_153_600_000_000,
+            open: marker.map_or(0.0, |m| m as f64 / 100.0),
+            price: 100.0,
+            bid: 99.75,
+            ask: 100.0,
+            volume,
+            bid_volume: 0,
+            ask_volume: volume,
+            num_trades: 1,
+        }
+    }
+
+    /// A three-record unbundled trade of 2 + 3 + 5 contracts, then a plain 7-lot trade.
+    fn records() -> [Tick; 4] {
+        [
+            tick(Some(FIRST_SUB_TRADE_OPEN), 2),
+            tick(None, 3),
+            tick(Some(LAST_SUB_TRADE_OPEN), 5),
+            tick(None, 7),
+        ]
+    }
+
+    fn unbundled(mode: Unbundle) -> Vec<(u32, u32, u32)> {
+        let mut unbundler = Unbundler::new(mode);
+        records()
+            .iter()
+            .map(|t| unbundler.apply(t))
+            .map(|t| (t.volume, t.ask_volume, t.num_trades))
+            .collect()
+    }
+
+    #[test]
+    fn sub_trade_markers_are_recognised() {
+        let types: Vec<TradeType> = records().iter().map(Tick::trade_type).collect();
+        assert_eq!(
+            types,
+            [
+                TradeType::FirstSubTrade,
+                TradeType::Normal,
+                TradeType::LastSubTrade,
+                TradeType::Normal
+            ]
+        );
+    }
+
+    #[test]
+    fn unbundle_ignore_keeps_every_record() {
+        assert_eq!(
+            unbundled(Unbundle::Ignore),
+            [(2, 2, 1), (3, 3, 1), (5, 5, 1), (7, 7, 1)]
+        );
+    }
+
+    #[test]
+    fn unbundle_first_counts_only_the_first_record() {
+        assert_eq!(
+            unbundled(Unbundle::First),
+            [(2, 2, 1), (0, 0, 0), (0, 0, 0), (7, 7, 1)]
+        );
+    }
+
+    #[test]
+    fn unbundle_last_counts_only_the_last_record() {
+        assert_eq!(
+            unbundled(Unbundle::Last),
+            [(0, 0, 0), (0, 0, 0), (5, 5, 1), (7, 7, 1)]
+        );
+    }
+
+    #[test]
+    fn unbundle_sum_puts_the_total_on_the_last_record() {
+        assert_eq!(
+            unbundled(Unbundle::Sum),
+            [(0, 0, 0), (0, 0, 0), (10, 10, 1), (7, 7, 1)]
+        );
+    }
+
+    #[test]
+    fn unbundle_keeps_prices() {
+        let mut unbundler = Unbundler::new(Unbundle::Sum);
+        for t in records() {
+            let out = unbundler.apply(&t);
+            assert_eq!((out.price, out.bid, out.ask), (t.price, t.bid, t.ask));
+        }
+    }
+
+    #[test]
+    fn merged_ticks_keep_the_first_listed_file_on_overlap() {
+        let at = |secs: i64, price: f64| Tick {
+            timestamp_us: 1_704_153_600_000_000 + secs * 1_000_000,
+            price,
+            ..tick(None, 1)
+        };
+        // File 1 has two ticks at second 2; file 0 has none there but owns second 1
+        let merged = merge_tick_vecs(vec![
+            vec![at(1, 10.0), at(3, 10.5)],
+            vec![
+                at(0, 20.0),
+                at(1, 21.0),
+                at(2, 22.0),
+                at(2, 22.5),
+                at(3, 23.0),
+            ],
+        ]);
+        let prices: Vec<f64> = merged.iter().map(|t| t.price).collect();
+        assert_eq!(prices, [20.0, 10.0, 22.0, 22.5, 10.5]);
+    }
+
+    /// A path in the temp dir unique to this test and process.
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("scid_{}_{name}", std::process::id()))
+    }
+
+    fn sample_ticks() -> Vec<Tick> {
+        (0..50)
+            .map(|i| Tick {
+                timestamp_us: 1_704_153_600_000_000 + i * 250_000,
+                price: 4800.0 + (i % 7) as f64 * 0.25,
+                bid: 4800.0 + (i % 7) as f64 * 0.25 - 0.25,
+                ask: 4800.0 + (i % 7) as f64 * 0.25,
+                ..tick(None, i as u32 + 1)
+            })
+            .collect()
+    }
+
+    /// Every field of each tick, for comparing tick lists.
+    fn fields(ticks: &[Tick]) -> Vec<String> {
+        ticks.iter().map(|t| format!("{t:?}")).collect()
+    }
+
+    #[test]
+    fn compressed_files_read_like_plain_ones() {
+        let ticks = sample_ticks();
+        let plain = temp_path("plain.scid");
+        write_scid(&plain, &ticks).unwrap();
+        let raw = std::fs::read(&plain).unwrap();
+        std::fs::remove_file(&plain).unwrap();
+
+        let gz_path = temp_path("ticks.scid.gz");
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gz.write_all(&raw).unwrap();
+        std::fs::write(&gz_path, gz.finish().unwrap()).unwrap();
+        let zst_path = temp_path("ticks.scid.zst");
+        std::fs::write(&zst_path, zstd::encode_all(&raw[..], 0).unwrap()).unwrap();
+
+        for path in [&gz_path, &zst_path] {
+            let scid = ScidFile::open(path).unwrap();
+            assert_eq!(fields(&scid.ticks()), fields(&ticks));
+            assert_eq!(
+                ScidHeader::read(path).unwrap().record_size,
+                RECORD_SIZE as u32
+            );
+            // One byte short of the decompressed size
+            let err = ScidFile::open_with_limit(path, raw.len() - 1)
+                .err()
+                .unwrap();
+            assert!(err.contains("limit"), "{err}");
+            assert!(ScidFile::open_with_limit(path, raw.len()).is_ok());
+        }
+        std::fs::remove_file(&gz_path).unwrap();
+        std::fs::remove_file(&zst_path).unwrap();
+    }
+
+    #[test]
+    fn written_and_appended_ticks_read_back() {
+        let ticks = sample_ticks();
+        let path = temp_path("append.scid");
+        let _ = std::fs::remove_file(&path);
+        // Appending to a missing file creates it
+        append_scid(&path, &ticks[..20]).unwrap();
+        append_scid(&path, &ticks[20..]).unwrap();
+        let scid = ScidFile::open(&path).unwrap();
+        assert_eq!(scid.num_records, ticks.len());
+        assert_eq!(fields(&scid.ticks()), fields(&ticks));
+        assert_eq!(&std::fs::read(&path).unwrap()[..4], b"SCID");
+
+        // Out-of-order appends are refused and leave the file untouched
+        let err = append_scid(&path, &ticks[..1]).unwrap_err();
+        assert!(err.contains("earlier than the preceding record"), "{err}");
+        assert_eq!(ScidFile::open(&path).unwrap().num_records, ticks.len());
+
+        write_scid(&path, &ticks[..5]).unwrap();
+        assert_eq!(
+            fields(&ScidFile::open(&path).unwrap().ticks()),
+            fields(&ticks[..5])
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    /// Swap records `a` and `b` of the SCID file at `path` in place.
+    fn swap_records(path: &Path, a: usize, b: usize) {
+        let mut bytes = std::fs::read(path).unwrap();
+        let at = |i: usize| HEADER_SIZE + i * RECORD_SIZE;
+        let rec_a: Vec<u8> = bytes[at(a)..at(a) + RECORD_SIZE].to_vec();
+        bytes.copy_within(at(b)..at(b) + RECORD_SIZE, at(a));
+        bytes[at(b)..at(b) + RECORD_SIZE].copy_from_slice(&rec_a);
+        std::fs::write(path, bytes).unwrap();
+    }
+
+    #[test]
+    fn validate_reports_a_backward_timestamp_and_gaps() {
+        let start = 1_704_153_600_000_000;
+        // One record a second, with a two-hour gap before record 8 and a duplicate at 9
+        let secs = [0, 1, 2, 3, 4, 5, 6, 7, 7_207, 7_207, 7_208];
+        let ticks: Vec<Tick> = secs
+            .iter()
+            .map(|&s| Tick {
+                timestamp_us: start + s * 1_000_000,
+                ..tick(None, 1)
+            })
+            .collect();
+        let path = temp_path("validate.scid");
+        write_scid(&path, &ticks).unwrap();
+        // Record 5 now reads second 4, earlier than the second-5 record before it
+        swap_records(&path, 4, 5);
+
+        let report = ScidFile::open(&path).unwrap().validate(3_600_000_000);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(report.num_records, ticks.len());
+        assert_eq!(report.non_monotonic_count, 1);
+        assert_eq!(report.first_non_monotonic_index, Some(5));
+        assert_eq!(report.duplicate_count, 1);
+        assert_eq!(report.gaps, [(8, 7_200_000_000)]);
+        assert_eq!(report.min_timestamp_us, Some(start));
+        assert_eq!(report.max_timestamp_us, Some(start + 7_208_000_000));
+    }
+
+    /// `ticks` written to a temp SCID file, then `patch`ed, then checked.
+    fn integrity_errors(name: &str, ticks: &[Tick], patch: impl Fn(&mut Vec<u8>)) -> Vec<String> {
+        let path = temp_path(name);
+        write_scid(&path, ticks).unwrap();
+        let mut bytes = std::fs::read(&path).unwrap();
+        patch(&mut bytes);
+        std::fs::write(&path, bytes).unwrap();
+        let errors = check_integrity(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        errors
+    }
+
+    #[test]
+    fn integrity_check_passes_a_clean_file() {
+        assert!(integrity_errors("clean.scid", &sample_ticks(), |_| {}).is_empty());
+    }
+
+    #[test]
+    fn integrity_check_reports_every_layout_problem() {
+        let errors = integrity_errors("layout.scid", &sample_ticks(), |bytes| {
+            bytes[0..4].copy_from_slice(b"SCIX");
+            // Half a record left over at the end
+            bytes.extend_from_slice(&[0; RECORD_SIZE / 2]);
+        });
+        assert_eq!(errors.len(), 2, "{errors:?}");
+        assert_eq!(errors[0], "Invalid SCID magic bytes");
+        assert!(errors[1].starts_with("Data length"), "{errors:?}");
+
+        let errors = integrity_errors("header.scid", &sample_ticks(), |bytes| {
+            bytes[4..8].copy_from_slice(&40u32.to_le_bytes());
+        });
+        assert_eq!(errors.len(), 1, "{errors:?}");
+        assert!(errors[0].starts_with("Invalid SCID header size 40"));
+    }
+
+    #[test]
+    fn integrity_check_reports_every_record_problem() {
+        let mut ticks = sample_ticks();
+        ticks[7].price = 0.0;
+        ticks[9].price = -1.0;
+        let errors = integrity_errors("records.scid", &ticks, |bytes| {
+            // Swap records 20 and 21, putting 21's earlier timestamp second
+            let at = |i: usize| HEADER_SIZE + i * RECORD_SIZE;
+            let first = bytes[at(20)..at(21)].to_vec();
+            bytes.copy_within(at(21)..at(22), at(20));
+            bytes[at(21)..at(22)].copy_from_slice(&first);
+        });
+        assert_eq!(
+            errors,
+            [
+                "1 records are timestamped earlier than the record before them (first at \
+                 index 21)",
+                "2 records have a non-positive or non-finite price (first at index 7)",
+            ]
+        );
+    }
+
+    #[test]
+    fn record_open_is_read_apart_from_the_close() {
+        let path = temp_path("open.scid");
+        let mut ticks = sample_ticks()[..3].to_vec();
+        ticks[0].price = 0.0;
+        write_scid(&path, &ticks).unwrap();
+        // Record 1 holds an aggregated bar: open 4799.50, close 4800.25
+        let mut bytes = std::fs::read(&path).unwrap();
+        let at = HEADER_SIZE + RECORD_SIZE;
+        bytes[at + 8..at + 12].copy_from_slice(&479_950.0f32.to_le_bytes());
+        bytes[at + 20..at + 24].copy_from_slice(&480_025.0f32.to_le_bytes());
+        std::fs::write(&path, bytes).unwrap();
+
+        let scid = ScidFile::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let record = scid.tick(1);
+        assert_eq!((record.open, record.price), (4799.5, 4800.25));
+        assert_eq!(record.trade_type(), TradeType::Aggregated);
+        // The bar opens at the first valid record's close, not its open, the invalid
+        // record before it or the record after
+        let (bars, _) =
+            crate::bar::aggregate_bars(&scid, crate::bar::BarInterval::new(60), Unbundle::Ignore);
+        assert_eq!(bars.len(), 1);
+        assert_eq!(bars[0].open, 4800.25);
+        assert_eq!(bars[0].first_tick_index, 1);
+    }
+
+    #[test]
+    fn header_record_size_must_cover_the_decoded_fields() {
+        let ticks = sample_ticks();
+        let path = temp_path("record_size.scid");
+        write_scid(&path, &ticks).unwrap();
+        let written = std::fs::read(&path).unwrap();
+        let with_record_size = |size: u32| {
+            let mut bytes = written.clone();
+            bytes[8..12].copy_from_slice(&size.to_le_bytes());
+            bytes
+        };
+
+        // 48-byte records: the usual 40 bytes then 8 bytes of extra fields
+        let mut wide = with_record_size(48);
+        wide.truncate(HEADER_SIZE);
+        for record in written[HEADER_SIZE..].chunks(RECORD_SIZE) {
+            wide.extend_from_slice(record);
+            wide.extend_from_slice(&[0xAB; 8]);
+        }
+        std::fs::write(&path, wide).unwrap();
+        let read = ScidFile::open(&path).unwrap().ticks();
+        assert_eq!(fields(&read), fields(&ticks));
+
+        std::fs::write(&path, with_record_size(32)).unwrap();
+        let err = ScidFile::open(&path).err().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(err.starts_with("Unsupported SCID record size 32"), "{err}");
     }
 }
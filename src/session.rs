@@ -0,0 +1,233 @@
+//! Wall-clock helpers for session rules (e.g. flat-by-time-of-day).
+
+use chrono::{DateTime, Offset, TimeZone};
+use chrono_tz::Tz;
+
+use crate::metrics::{civil_from_days, days_from_civil};
+
+const US_PER_SEC: i64 = 1_000_000;
+const SECS_PER_DAY: i64 = 86_400;
+
+/// Converts Unix timestamps to local wall-clock time, using a fixed UTC offset or an
+/// IANA time zone whose offset follows daylight saving time.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SessionClock {
+    /// Local time minus UTC, in seconds (e.g. -5 * 3600 for US Eastern standard time);
+    /// ignored when `zone` is set
+    pub utc_offset_secs: i64,
+    /// Time zone giving the offset at each instant
+    pub zone: Option<Tz>,
+}
+
+impl SessionClock {
+    pub fn new(utc_offset_secs: i64) -> Self {
+        SessionClock {
+            utc_offset_secs,
+            zone: None,
+        }
+    }
+
+    /// Clock for the IANA time zone `zone`, e.g. `America/New_York`.
+    pub fn with_zone(zone: Tz) -> Self {
+        SessionClock {
+            utc_offset_secs: 0,
+            zone: Some(zone),
+        }
+    }
+
+    /// Local time minus UTC, in seconds, at a Unix-us timestamp.
+    pub fn utc_offset_at(&self, timestamp_us: i64) -> i64 {
+        let Some(zone) = self.zone else {
+            return self.utc_offset_secs;
+        };
+        let utc = DateTime::from_timestamp(timestamp_us.div_euclid(US_PER_SEC), 0)
+            .unwrap_or_default()
+            .naive_utc();
+        i64::from(zone.offset_from_utc_datetime(&utc).fix().local_minus_utc())
+    }
+
+    /// Seconds since local midnight for a Unix-us timestamp.
+    pub fn secs_of_day(&self, timestamp_us: i64) -> i64 {
+        (timestamp_us.div_euclid(US_PER_SEC) + self.utc_offset_at(timestamp_us))
+            .rem_euclid(SECS_PER_DAY)
+    }
+
+    /// Unix-us timestamp of the local midnight that starts the day containing `timestamp_us`.
+    pub fn day_start_us(&self, timestamp_us: i64) -> i64 {
+        let local_secs = timestamp_us.div_euclid(US_PER_SEC) + self.utc_offset_at(timestamp_us);
+        let midnight = local_secs - local_secs.rem_euclid(SECS_PER_DAY);
+        // The offset at midnight can differ from the one at `timestamp_us` on a day with a
+        // DST change, so convert back with the offset in force around midnight
+        let guess = midnight - self.utc_offset_at(timestamp_us);
+        (midnight - self.utc_offset_at(guess * US_PER_SEC)) * US_PER_SEC
+    }
+}
+
+/// Look up an IANA time zone name such as `America/New_York`.
+pub fn parse_timezone(name: &str) -> Result<Tz, String> {
+    name.parse::<Tz>()
+        .map_err(|_| format!("Unknown time zone: {name}"))
+}
+
+/// Format a Unix-us timestamp as ISO-8601 UTC, e.g. `2024-03-01T14:30:00Z`, with
+/// microseconds only when the timestamp has a fractional second.
+pub fn iso8601_utc(timestamp_us: i64) -> String {
+    let secs = timestamp_us.div_euclid(US_PER_SEC);
+    let micros = timestamp_us.rem_euclid(US_PER_SEC);
+    let (year, month, day) = civil_from_days(secs.div_euclid(SECS_PER_DAY));
+    let sod = secs.rem_euclid(SECS_PER_DAY);
+    let (h, m, s) = (sod / 3600, sod / 60 % 60, sod % 60);
+    if micros == 0 {
+        format!("{year:04}-{month:02}-{day:02}T{h:02}:{m:02}:{s:02}Z")
+    } else {
+        format!("{year:04}-{month:02}-{day:02}T{h:02}:{m:02}:{s:02}.{micros:06}Z")
+    }
+}
+
+/// Parse an ISO-8601 date or date-time into a Unix-us timestamp: `YYYY-MM-DD`, optionally
+/// followed by `T` or a space and `HH:MM[:SS[.ffffff]]`, then `Z` or a `+HH:MM`/`-HH:MM`
+/// offset. Without an offset the time is taken as UTC.
+pub fn parse_iso8601(s: &str) -> Result<i64, String> {
+    let invalid = || format!("Invalid ISO-8601 date-time: {s}");
+    if !s.is_ascii() {
+        return Err(invalid());
+    }
+    let field = |part: &str| part.parse::<u32>().map_err(|_| invalid());
+    let (date, rest) = s.split_at(s.len().min(10));
+    let ymd: Vec<&str> = date.split('-').collect();
+    let [year, month, day] = ymd[..] else {
+        return Err(invalid());
+    };
+    let (year, month, day) = (field(year)? as i32, field(month)?, field(day)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return Err(invalid());
+    }
+    let mut secs = days_from_civil(year, month, day) * SECS_PER_DAY;
+    let mut micros = 0;
+
+    let mut time = match rest.chars().next() {
+        None => "",
+        Some('T' | ' ') => &rest[1..],
+        Some(_) => return Err(invalid()),
+    };
+    // Trailing UTC designator or offset
+    if let Some(t) = time.strip_suffix('Z') {
+        time = t;
+    } else if let Some(at) = time.rfind(['+', '-']) {
+        let sign = if time.as_bytes()[at] == b'+' { 1 } else { -1 };
+        let offset_secs = parse_time_of_day(&time[at + 1..]).map_err(|_| invalid())?;
+        secs -= sign * offset_secs;
+        time = &time[..at];
+    }
+    if !time.is_empty() {
+        let (hms, frac) = time.split_once('.').unwrap_or((time, ""));
+        secs += parse_time_of_day(hms).map_err(|_| invalid())?;
+        if !frac.is_empty() {
+            if !frac.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(invalid());
+            }
+            // Microsecond precision; further digits are dropped
+            let digits = &frac[..frac.len().min(6)];
+            micros = field(digits)? as i64 * 10_i64.pow(6 - digits.len() as u32);
+        }
+    }
+    Ok(secs * US_PER_SEC + micros)
+}
+
+/// Parse "HH:MM" or "HH:MM:SS" into seconds after midnight.
+pub fn parse_time_of_day(s: &str) -> Result<i64, String> {
+    let parts: Vec<&str> = s.split(':').collect();
+    if parts.len() < 2 || parts.len() > 3 {
+        return Err(format!("Invalid time of day (expected HH:MM[:SS]): {s}"));
+    }
+    let mut fields = [0i64; 3];
+    for (i, p) in parts.iter().enumerate() {
+        fields[i] = p
+            .parse()
+            .map_err(|_| format!("Invalid time of day (expected HH:MM[:SS]): {s}"))?;
+    }
+    let [h, m, sec] = fields;
+    if !(0..24).contains(&h) || !(0..60).contains(&m) || !(0..60).contains(&sec) {
+        return Err(format!("Time of day out of range: {s}"));
+    }
+    Ok(h * 3600 + m * 60 + sec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::{ExitReason, FillContext, PositionTracker, Side};
+
+    fn utc(s: &str) -> i64 {
+        parse_iso8601(s).unwrap()
+    }
+
+    fn new_york() -> SessionClock {
+        SessionClock::with_zone(parse_timezone("America/New_York").unwrap())
+    }
+
+    #[test]
+    fn offset_follows_spring_forward_and_fall_back() {
+        let clock = new_york();
+        // 2024-03-10 02:00 EST becomes 03:00 EDT at 07:00 UTC
+        assert_eq!(clock.utc_offset_at(utc("2024-03-10T06:59:59Z")), -5 * 3600);
+        assert_eq!(clock.utc_offset_at(utc("2024-03-10T07:00:00Z")), -4 * 3600);
+        // 2024-11-03 02:00 EDT falls back to 01:00 EST at 06:00 UTC
+        assert_eq!(clock.utc_offset_at(utc("2024-11-03T05:59:59Z")), -4 * 3600);
+        assert_eq!(clock.utc_offset_at(utc("2024-11-03T06:00:00Z")), -5 * 3600);
+    }
+
+    #[test]
+    fn the_open_is_0930_local_on_both_sides_of_each_change() {
+        let clock = new_york();
+        let open = 9 * 3600 + 30 * 60;
+        for ts in [
+            "2024-03-08T14:30:00Z",
+            "2024-03-11T13:30:00Z",
+            "2024-11-01T13:30:00Z",
+            "2024-11-04T14:30:00Z",
+        ] {
+            assert_eq!(clock.secs_of_day(utc(ts)), open, "{ts}");
+        }
+    }
+
+    #[test]
+    fn days_with_a_change_start_at_local_midnight() {
+        let clock = new_york();
+        // The 23-hour day: midnight is still EST
+        let spring = utc("2024-03-10T05:00:00Z");
+        assert_eq!(clock.day_start_us(utc("2024-03-10T12:00:00Z")), spring);
+        assert_eq!(clock.day_start_us(utc("2024-03-11T03:59:59Z")), spring);
+        assert_eq!(
+            clock.day_start_us(utc("2024-03-11T04:00:00Z")),
+            utc("2024-03-11T04:00:00Z")
+        );
+        // The 25-hour day: midnight is still EDT
+        let fall = utc("2024-11-03T04:00:00Z");
+        assert_eq!(clock.day_start_us(utc("2024-11-03T12:00:00Z")), fall);
+        assert_eq!(clock.day_start_us(utc("2024-11-04T04:59:59Z")), fall);
+    }
+
+    #[test]
+    fn flat_time_tracks_daylight_saving() {
+        // 16:00 New York is 20:00 UTC in summer; a fixed EST offset would put it at 21:00
+        let exit_at = |clock: SessionClock| {
+            let mut tracker = PositionTracker::new(0.0, 50.0);
+            tracker.flat_time_secs = Some(16 * 3600);
+            tracker.session_clock = clock;
+            let ctx = FillContext::default();
+            tracker.process_signal(1, 100.0, utc("2024-03-11T19:55:00Z"), &ctx);
+            tracker.process_signal(1, 100.0, utc("2024-03-11T20:01:00Z"), &ctx);
+            tracker.process_signal(1, 100.0, utc("2024-03-11T21:01:00Z"), &ctx);
+            assert_eq!(tracker.side, Side::Flat);
+            let trade = &tracker.trades[0];
+            assert_eq!(trade.exit_reason, ExitReason::EndOfSession);
+            trade.exit_time_us
+        };
+        assert_eq!(exit_at(new_york()), utc("2024-03-11T20:01:00Z"));
+        assert_eq!(
+            exit_at(SessionClock::new(-5 * 3600)),
+            utc("2024-03-11T21:01:00Z")
+        );
+    }
+}
@@ -0,0 +1,50 @@
+//! Post-processing of strategy signal arrays before they reach the position tracker.
+
+use crate::position::HOLD;
+
+/// Debounce and hold-filter a signal array (1 = long, -1 = short, 0 = flat).
+///
+/// A new signal value only takes effect once it has been seen on `confirm_bars`
+/// consecutive bars (0 or 1 = immediately). Once a position is taken, no exit or reversal
+/// is acted on until `min_hold_bars` bars after the entry bar. Engine exits such as stops
+/// and the end-of-data close are outside this filter and still happen. `HOLD` passes
+/// through unchanged and neither confirms nor breaks a streak.
+pub fn filter_signals(signals: &[i32], min_hold_bars: usize, confirm_bars: usize) -> Vec<i32> {
+    let mut out = Vec::with_capacity(signals.len());
+    // Signal the engine is acting on, and the bar it was adopted on
+    let mut current = 0;
+    let mut entry_bar = 0;
+    // Latest raw value and how many consecutive bars it has been seen on
+    let mut candidate = 0;
+    let mut streak = 0;
+    for (i, &signal) in signals.iter().enumerate() {
+        if signal == HOLD {
+            out.push(HOLD);
+            continue;
+        }
+        if signal == candidate {
+            streak += 1;
+        } else {
+            candidate = signal;
+            streak = 1;
+        }
+        let held = current != 0 && i - entry_bar < min_hold_bars;
+        if candidate != current && streak >= confirm_bars && !held {
+            current = candidate;
+            entry_bar = i;
+        }
+        out.push(current);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hold_passes_through_without_breaking_a_streak() {
+        let signals = [1, HOLD, 1, 0, HOLD, 0];
+        assert_eq!(filter_signals(&signals, 0, 2), [0, HOLD, 1, 1, HOLD, 0]);
+    }
+}
@@ -0,0 +1,229 @@
+//! Strategies written in Rust, run by `engine::run_rust_backtest` without calling into
+//! Python, and the built-in ones exposed by name.
+
+use std::collections::HashMap;
+
+use crate::bar::Bar;
+
+/// A bar strategy called once per bar as it closes.
+pub trait Strategy: Send {
+    /// Signal for bar `index` (1 = long, -1 = short, 0 = flat, `position::HOLD` = keep
+    /// the position). `bars` holds the bars up to and including `index`, so later bars
+    /// cannot be seen.
+    fn on_bar(&mut self, bars: &[Bar], index: usize) -> i32;
+}
+
+/// Long while the fast simple moving average of closes is above the slow one, short
+/// while it is below; flat until `slow` bars have closed.
+#[derive(Clone, Debug)]
+pub struct SmaCross {
+    pub fast: usize,
+    pub slow: usize,
+}
+
+impl SmaCross {
+    pub fn new(fast: usize, slow: usize) -> Result<Self, String> {
+        if fast == 0 || fast >= slow {
+            return Err(format!(
+                "sma_cross needs 0 < fast < slow, got fast={fast} slow={slow}"
+            ));
+        }
+        Ok(SmaCross { fast, slow })
+    }
+}
+
+impl Strategy for SmaCross {
+    fn on_bar(&mut self, bars: &[Bar], index: usize) -> i32 {
+        if index + 1 < self.slow {
+            return 0;
+        }
+        let sma = |period: usize| {
+            bars[index + 1 - period..=index]
+                .iter()
+                .map(|b| b.close)
+                .sum::<f64>()
+                / period as f64
+        };
+        match sma(self.fast).partial_cmp(&sma(self.slow)) {
+            Some(std::cmp::Ordering::Greater) => 1,
+            Some(std::cmp::Ordering::Less) => -1,
+            _ => 0,
+        }
+    }
+}
+
+/// Goes long when a bar closes above the highest high of the `period` bars before it and
+/// short when it closes below their lowest low, holding the position in between.
+#[derive(Clone, Debug)]
+pub struct ChannelBreakout {
+    pub period: usize,
+    position: i32,
+}
+
+impl ChannelBreakout {
+    pub fn new(period: usize) -> Result<Self, String> {
+        if period == 0 {
+            return Err("breakout needs period > 0".into());
+        }
+        Ok(ChannelBreakout {
+            period,
+            position: 0,
+        })
+    }
+}
+
+impl Strategy for ChannelBreakout {
+    fn on_bar(&mut self, bars: &[Bar], index: usize) -> i32 {
+        if index < self.period {
+            return 0;
+        }
+        let channel = &bars[index - self.period..index];
+        let high = channel.iter().map(|b| b.high).fold(f64::MIN, f64::max);
+        let low = channel.iter().map(|b| b.low).fold(f64::MAX, f64::min);
+        let close = bars[index].close;
+        if close > high {
+            self.position = 1;
+        } else if close < low {
+            self.position = -1;
+        }
+        self.position
+    }
+}
+
+/// Build the built-in strategy `name` from `params`:
+/// `"sma_cross"` (`fast`, default 10; `slow`, default 30) or `"breakout"` (`period`,
+/// default 20).
+pub fn builtin(name: &str, params: &HashMap<String, f64>) -> Result<Box<dyn Strategy>, String> {
+    let known: &[&str] = match name {
+        "sma_cross" => &["fast", "slow"],
+        "breakout" => &["period"],
+        _ => return Err(format!("Unknown strategy: {name}")),
+    };
+    if let Some(key) = params.keys().find(|k| !known.contains(&k.as_str())) {
+        return Err(format!("Unknown parameter for {name}: {key}"));
+    }
+    let period = |key: &str, default: usize| match params.get(key) {
+        None => Ok(default),
+        Some(&v) if v >= 0.0 && v.fract() == 0.0 => Ok(v as usize),
+        Some(v) => Err(format!(
+            "{name} parameter {key} must be a whole number, got {v}"
+        )),
+    };
+    Ok(match name {
+        "sma_cross" => Box::new(SmaCross::new(period("fast", 10)?, period("slow", 30)?)?),
+        _ => Box::new(ChannelBreakout::new(period("period", 20)?)?),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bar::{BarInterval, BarOptions};
+    use crate::datasource::{DataSource, InMemoryDataSource};
+    use crate::engine::{run_rust_backtest, BacktestConfig};
+    use crate::position::Side;
+    use crate::scid::Tick;
+
+    fn tick(timestamp_us: i64, price: f64) -> Tick {
+        Tick {
+            timestamp_us,
+            open: 0.0,
+            price,
+            bid: price,
+            ask: price,
+            volume: 1,
+            bid_volume: 0,
+            ask_volume: 1,
+            num_trades: 1,
+        }
+    }
+
+    fn minute_bars() -> BarOptions {
+        BarOptions::new(BarInterval::new(60))
+    }
+
+    #[test]
+    fn sma_cross_trades_each_crossover() {
+        let source = InMemoryDataSource::from_synthetic(30_000, 4_800.0, 0.0005, 7);
+        let options = minute_bars();
+        let bars = options.build(&source, None).unwrap().bars;
+        let mut expected = SmaCross::new(5, 20).unwrap();
+        let signals: Vec<i32> = (0..bars.len()).map(|i| expected.on_bar(&bars, i)).collect();
+        // A trade closes whenever a held side changes, plus the one still open at the end
+        let changes = signals
+            .windows(2)
+            .filter(|w| w[0] != 0 && w[0] != w[1])
+            .count();
+        let open_at_end = usize::from(signals[signals.len() - 1] != 0);
+
+        let config = BacktestConfig::default();
+        let mut strategy = SmaCross::new(5, 20).unwrap();
+        let results = run_rust_backtest(&source, None, &options, &mut strategy, &config).unwrap();
+        assert!(changes > 0);
+        assert_eq!(results.trades.len(), changes + open_at_end);
+        assert!(results
+            .trades
+            .windows(2)
+            .all(|w| w[0].side != w[1].side && w[0].exit_time_us == w[1].entry_time_us));
+        let pnl: f64 = results.trades.iter().map(|t| t.pnl).sum();
+        let last = results.equity_curve[results.equity_curve.len() - 1];
+        assert!((last - config.metrics.initial_capital - pnl).abs() < 1e-6);
+    }
+
+    #[test]
+    fn sma_cross_is_flat_until_the_slow_average_exists() {
+        let source = InMemoryDataSource::from_synthetic(600, 100.0, 0.001, 1);
+        let bars = minute_bars().build(&source, None).unwrap().bars;
+        assert_eq!(bars.len(), 10);
+        let mut strategy = SmaCross::new(2, 20).unwrap();
+        assert!((0..bars.len()).all(|i| strategy.on_bar(&bars, i) == 0));
+    }
+
+    #[test]
+    fn breakout_goes_long_on_the_first_close_above_the_channel() {
+        let start = 1_704_153_600_000_000;
+        // Five flat minutes around 100, then a minute closing at 105
+        let ticks: Vec<Tick> = (0..6 * 60)
+            .map(|s| {
+                let price = if s >= 5 * 60 {
+                    105.0
+                } else if s % 2 == 0 {
+                    100.0
+                } else {
+                    100.5
+                };
+                tick(start + s * 1_000_000, price)
+            })
+            .collect();
+        let source = InMemoryDataSource::from_ticks(ticks);
+        assert_eq!(source.num_ticks(), 360);
+        let mut strategy = ChannelBreakout::new(3).unwrap();
+        let results = run_rust_backtest(
+            &source,
+            None,
+            &minute_bars(),
+            &mut strategy,
+            &BacktestConfig::default(),
+        )
+        .unwrap();
+        assert_eq!(results.trades.len(), 1);
+        let trade = &results.trades[0];
+        assert_eq!(trade.side, Side::Long);
+        assert_eq!(trade.entry_time_us, start + 5 * 60_000_000);
+        assert_eq!(trade.entry_price, 105.0);
+    }
+
+    #[test]
+    fn builtin_rejects_unknown_names_and_parameters() {
+        let mut params = HashMap::new();
+        assert!(builtin("momentum", &params).is_err());
+        params.insert("fast".to_string(), 30.0);
+        assert!(builtin("sma_cross", &params).is_err());
+        params.insert("fast".to_string(), 2.5);
+        assert!(builtin("sma_cross", &params).is_err());
+        params.insert("period".to_string(), 5.0);
+        assert!(builtin("breakout", &params).is_err());
+        params.remove("fast");
+        assert!(builtin("breakout", &params).is_ok());
+    }
+}